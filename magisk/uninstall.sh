@@ -0,0 +1,9 @@
+#!/system/bin/sh
+MODDIR=${0%/*}
+
+# --- остановить демон: по SIGTERM он сам возвращает исходные значения
+pkill -TERM -x perf_daemon
+sleep 2
+
+# --- если демон был убит раньше, вернуть значения из журнала
+"$MODDIR/system/bin/perf_daemon" --restore >/dev/null 2>&1
@@ -0,0 +1,71 @@
+use std::{env, path::PathBuf};
+
+/// Command line options. On the phone the daemon is started by service.sh without any.
+#[derive(Debug, Default)]
+pub struct Args {
+    /// `--root <dir>` (or `MORA_ROOT`): resolve every device path under `dir`
+    /// instead of `/`, so the daemon can run against a fake `tiro` tree.
+    pub root: Option<PathBuf>,
+    /// `--device <name>`: pick a device descriptor instead of `ro.product.device`.
+    pub device: Option<String>,
+    /// `--device-file <file>`: use this descriptor file as-is.
+    pub device_file: Option<PathBuf>,
+    /// `--simulate <trace.jsonl>`: replay a sensor trace through the controller and exit.
+    pub simulate: Option<PathBuf>,
+    /// `--config <file>` for `--simulate` (defaults to built-in config).
+    pub sim_config: Option<PathBuf>,
+    /// `--games <file>` for `--simulate` (defaults to an empty list).
+    pub sim_games: Option<PathBuf>,
+    /// `--out <file>` for `--simulate` (defaults to stdout).
+    pub sim_out: Option<PathBuf>,
+    /// `--restore-thermal`: undo the thermal takeover recorded on disk and exit.
+    pub restore_thermal: bool,
+    /// `--restore`: put back every device value a previous run changed (thermal included) and exit.
+    pub restore: bool,
+}
+
+fn path_arg(it: &mut impl Iterator<Item = String>, name: &str) -> Option<PathBuf> {
+    let v = it.next().map(PathBuf::from);
+    if v.is_none() {
+        eprintln!("CLI: {} needs a path", name);
+    }
+    v
+}
+
+pub fn parse() -> Args {
+    let mut args = Args::default();
+    let mut it = env::args().skip(1);
+    while let Some(a) = it.next() {
+        match a.as_str() {
+            "--root" => match it.next() {
+                Some(v) => args.root = Some(PathBuf::from(v)),
+                None => eprintln!("CLI: --root needs a directory"),
+            },
+            "--device" => match it.next() {
+                Some(v) => args.device = Some(v),
+                None => eprintln!("CLI: --device needs a name"),
+            },
+            "--device-file" => args.device_file = path_arg(&mut it, "--device-file"),
+            "--simulate" => args.simulate = path_arg(&mut it, "--simulate"),
+            "--config" => args.sim_config = path_arg(&mut it, "--config"),
+            "--games" => args.sim_games = path_arg(&mut it, "--games"),
+            "--out" => args.sim_out = path_arg(&mut it, "--out"),
+            "--restore-thermal" => args.restore_thermal = true,
+            "--restore" => args.restore = true,
+            _ => {
+                if let Some(v) = a.strip_prefix("--root=") {
+                    args.root = Some(PathBuf::from(v));
+                } else {
+                    eprintln!("CLI: unknown argument {}", a);
+                }
+            }
+        }
+    }
+
+    if args.root.is_none() {
+        args.root = env::var_os("MORA_ROOT")
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from);
+    }
+    args
+}
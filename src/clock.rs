@@ -0,0 +1,13 @@
+//! Local wall-clock time for time-of-day rules. The control loop itself runs on `Instant`.
+
+/// Minutes since local midnight (0..1440), None if the libc conversion fails.
+pub fn minute_of_day() -> Option<u16> {
+    unsafe {
+        let t = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&t, &mut tm).is_null() {
+            return None;
+        }
+        Some((tm.tm_hour * 60 + tm.tm_min) as u16)
+    }
+}
@@ -10,7 +10,8 @@ use std::{
     time::Duration,
 };
 
-/// Poll config.json for changes. On parse/validation errors resets to default.
+/// Poll config.json for changes. On parse errors resets to default; sections failing validation
+/// run on their defaults and the error is kept in `last_config_error`.
 pub fn spawn(shared: Arc<RwLock<SharedState>>, path: PathBuf) {
     thread::spawn(move || {
         let mut last_mtime: Option<u64> = None;
@@ -22,12 +23,12 @@ pub fn spawn(shared: Arc<RwLock<SharedState>>, path: PathBuf) {
                 .map(|d| d.as_secs());
 
             if mtime.is_some() && mtime != last_mtime {
-                let cfg = load_or_init(path.as_path());
+                let (cfg, err) = load_or_init(path.as_path());
                 {
                     let mut s = shared.write().unwrap();
                     s.config = cfg;
                     s.config_rev = s.config_rev.wrapping_add(1);
-                    s.last_config_error = None;
+                    s.last_config_error = err;
                 }
                 last_mtime = mtime;
             } else if mtime.is_none() && last_mtime.is_some() {
@@ -56,7 +57,11 @@ pub fn apply_and_persist(
     path: &Path,
     mut cfg: UserConfig,
 ) -> Result<(), String> {
-    cfg.validate_and_normalize()?;
+    // API callers get the error instead of a silently reset section.
+    let resets = cfg.validate_and_normalize();
+    if !resets.is_empty() {
+        return Err(resets.join("; "));
+    }
     write_config_atomic(path, &cfg).map_err(|e| e.to_string())?;
     {
         let mut s = shared.write().unwrap();
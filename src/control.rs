@@ -0,0 +1,787 @@
+//! Per-iteration control decisions, separated from sensing and sysfs writes.
+//!
+//! `main.rs` samples the device into `Inputs`, calls `Controller::step` and then
+//! actuates the returned `Decisions`. `sim.rs` feeds recorded/scripted inputs through
+//! the very same `Controller` on a virtual clock, so policy changes can be
+//! regression-tested without a phone.
+
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::*,
+    cpu::avg_util,
+    critical::{CriticalGuard, CriticalLevel},
+    device::DeviceDescriptor,
+    domain::{Domain, DomainTuning, ThermalLimit},
+    fan::{CurveTracker, Fan},
+    fan_setpoint::{FanSetpoint, FanSetpointStatus},
+    fmt::fmt_c,
+    games::{AppEntry, GamesRuntime, SplitChargeConfig},
+    pid::{self, PidStatus, ThermalPid},
+    profiles::{active_schedule, select_active_perf, select_base_led, ActivePerf, BaseLedSelection, RuleContext},
+    tempzone::{predicted_temp_mc, zone_with_hysteresis, SlopeEstimator, TempZone},
+    thermal::{read_control_temp_mc, read_soc_temp_mc},
+    user_config::{FanControlMode, FanSetpointSensor, ThermalControlMode, UserConfig},
+};
+
+pub const SCREEN_OFF_CORE_SAVER_SECS: u64 = 30 * 60;
+/// Longest loop sleep, scaled or not; must stay below the watchdog's minimum `stall_s` (20 s).
+pub const MAX_SLEEP_MS: u64 = 15_000;
+
+fn default_true() -> bool { true }
+
+/// Per-game flags for the current foreground package.
+#[derive(Clone, Debug)]
+pub struct GameFlags {
+    pub active: bool,
+    pub fan_min_level: u8,
+    pub gpu_turbo: bool,
+    pub disable_thermal_limit: bool,
+    pub split_charge: SplitChargeConfig,
+}
+
+impl Default for GameFlags {
+    fn default() -> Self {
+        Self {
+            active: false,
+            fan_min_level: GAME_FAN_BASE,
+            gpu_turbo: false,
+            disable_thermal_limit: false,
+            split_charge: SplitChargeConfig::default(),
+        }
+    }
+}
+
+impl GameFlags {
+    pub fn resolve(games: &GamesRuntime, pkg: Option<&str>) -> Self {
+        match pkg {
+            Some(p) if games.is_game(p) => Self {
+                active: true,
+                fan_min_level: games.game_fan_min_level(p),
+                gpu_turbo: games.game_gpu_turbo(p),
+                disable_thermal_limit: games.game_disable_thermal_limit(p),
+                split_charge: games.game_split_charge(p),
+            },
+            _ => Self::default(),
+        }
+    }
+}
+
+/// Everything the decision logic reads from the device in one loop iteration.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Inputs {
+    #[serde(default)]
+    pub cpu_avg_mc: Option<i32>,
+    #[serde(default)]
+    pub gpu_avg_mc: Option<i32>,
+    #[serde(default)]
+    pub batt_temp_mc: Option<i32>,
+    /// Hotspot (max) per domain label from the descriptor's per-domain sensors.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub domain_temps_mc: BTreeMap<String, i32>,
+    /// Hottest plausible SoC/battery reading, suspect sensors included (critical layer).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub soc_hot_mc: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub batt_hot_mc: Option<i32>,
+    /// Per-core utilization in %, indexed by cpu number.
+    #[serde(default)]
+    pub cpu_utils: Vec<u8>,
+    /// GPU busy %.
+    #[serde(default)]
+    pub ug: u8,
+    /// Debounced screen state.
+    #[serde(default = "default_true")]
+    pub screen_on: bool,
+    /// Hardware charging state (before the `charging.enabled` config switch).
+    #[serde(default)]
+    pub charging: bool,
+    #[serde(default)]
+    pub battery_percent: Option<u8>,
+    /// Last detected foreground package.
+    #[serde(default)]
+    pub pkg: Option<String>,
+    /// A background process exceeded BG_CPU_THRESHOLD_PCT during screen-off.
+    #[serde(default)]
+    pub bg_over: bool,
+    /// Local time, minutes since midnight (time-of-day profile rules).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub minute_of_day: Option<u16>,
+    /// Manual fan level from `/api/fan/override`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fan_override: Option<u8>,
+
+    /// Resolved from `pkg` against games.json (not part of traces).
+    #[serde(skip)]
+    pub game: GameFlags,
+    /// App rule for `pkg` from games.json `apps` (not part of traces).
+    #[serde(skip)]
+    pub app: Option<AppEntry>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct CapDecision {
+    pub label: &'static str,
+    pub util: u8,
+    pub min: u64,
+    pub max: u64,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct BatterySaverStatus {
+    pub active: bool,
+    #[serde(rename = "override")]
+    pub override_on: bool,
+    pub disabled_cores: Vec<usize>,
+    pub reapply_in_sec: Option<u64>,
+}
+
+/// Thermal cut applied to one domain and why.
+#[derive(Clone, Debug, Serialize)]
+pub struct DomainThermal {
+    pub label: &'static str,
+    pub temp_mc: Option<i32>,
+    /// Share of the global cut this domain takes (100 = hotspot / no attribution).
+    pub share_percent: u8,
+    pub reduce_percent: f32,
+    /// Empty while unthrottled.
+    pub reason: String,
+}
+
+/// What the daemon should do after one iteration.
+#[derive(Clone, Debug, Serialize)]
+pub struct Decisions {
+    pub control_temp_mc: Option<i32>,
+    /// `control_temp` formula in use: `normal`, `charging` or `gaming`.
+    pub control_formula: &'static str,
+    /// Control temperature slope (°C/min), once enough history exists.
+    pub temp_slope_c_per_min: Option<f32>,
+    /// Temperature the curve was evaluated at (control temp + predictive lead).
+    pub zone_temp_mc: Option<i32>,
+    pub zone: TempZone,
+    pub effective_zone: TempZone,
+    /// Critical safeguard level (overrides zones, PID and game bypasses).
+    pub critical: CriticalLevel,
+    /// Hottest CPU/GPU reading the critical layer compared.
+    pub soc_hot_mc: Option<i32>,
+    /// PID output (`thermal_control.mode = "pid"` only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pid: Option<PidStatus>,
+    pub caps: Vec<CapDecision>,
+    /// Hottest domain by its own sensors (None without per-domain temps).
+    pub hotspot: Option<&'static str>,
+    pub thermal_domains: Vec<DomainThermal>,
+    pub fan_level: u8,
+    /// Fan setpoint controller output (`fan_control.mode = "setpoint"` only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fan_setpoint: Option<FanSetpointStatus>,
+    /// Manual level in force (overrides curves and `use_phone_cooler`, not the critical layer).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fan_override: Option<u8>,
+    pub offline_cores: Vec<usize>,
+    pub battery_saver: BatterySaverStatus,
+    pub screen_off_saver_cores: Vec<usize>,
+    pub charging_effective: bool,
+    pub game_mode: bool,
+    pub idle_mode: bool,
+    pub profile: String,
+    /// Rule that selected a custom profile.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile_rule: Option<String>,
+    /// Foreground package whose app rule applies.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_rule: Option<String>,
+    /// Active time-of-day schedule.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<String>,
+    pub led: BaseLedSelection,
+    pub sleep_ms: u64,
+
+    #[serde(skip)]
+    pub zone_changed: bool,
+    #[serde(skip)]
+    pub idle_changed: bool,
+}
+
+fn battery_saver_disable_delay(override_dur: Duration) -> Duration {
+    let s = override_dur.as_secs_f64();
+    if s <= 10.0 {
+        Duration::from_secs(5)
+    } else if s >= 20.0 {
+        Duration::from_secs(30)
+    } else {
+        let t = (s - 10.0) / 10.0;
+        let ms = 5000.0 + t * 25000.0;
+        Duration::from_millis(ms.round() as u64)
+    }
+}
+
+/// Smart battery saver runtime state.
+#[derive(Debug, Default)]
+struct BatterySaver {
+    high_streak: Duration,
+    override_on: bool,
+    override_since: Option<Instant>,
+    reapply_at: Option<Instant>,
+}
+
+impl BatterySaver {
+    fn reset(&mut self) {
+        self.high_streak = Duration::ZERO;
+        self.override_on = false;
+        self.override_since = None;
+        self.reapply_at = None;
+    }
+
+    /// Override state machine: heavy base-core load for 15s re-enables all cores,
+    /// cores are parked again after a delay that grows with the override duration.
+    fn update(&mut self, base_util: u8, now: Instant, dt: Duration) {
+        if self.override_on {
+            if base_util >= 90 {
+                // Still heavy -> cancel pending re-apply.
+                self.reapply_at = None;
+            } else {
+                if self.reapply_at.is_none() {
+                    let since = self.override_since.unwrap_or(now);
+                    let delay = battery_saver_disable_delay(now.duration_since(since));
+                    self.reapply_at = Some(now + delay);
+                }
+                if self.reapply_at.map(|t| now >= t).unwrap_or(false) {
+                    self.reset();
+                }
+            }
+        }
+
+        if !self.override_on {
+            if base_util >= 90 {
+                self.high_streak += dt;
+                if self.high_streak >= Duration::from_secs(15) {
+                    self.override_on = true;
+                    self.override_since = Some(now);
+                    self.reapply_at = None;
+                    self.high_streak = Duration::ZERO;
+                }
+            } else {
+                self.high_streak = Duration::ZERO;
+            }
+        }
+    }
+}
+
+pub struct Controller {
+    pub cpu: Vec<Domain>,
+    pub gpu: Domain,
+    /// Built-in/device tunables per domain (`domains()` order), before config overrides.
+    tuning_defaults: Vec<DomainTuning>,
+
+    /// Cores that battery saver / screen-off saver may park.
+    pub parkable_cpus: Vec<usize>,
+    /// Prime cores, parked first (battery < 50%).
+    prime_cpus: Vec<usize>,
+    /// Cores whose load decides the battery saver override (never parked by mora).
+    base_cpus: Vec<usize>,
+
+    last_zone: TempZone,
+    critical: CriticalGuard,
+    control_formula: &'static str,
+    slope: SlopeEstimator,
+    pid: ThermalPid,
+    fan_level: u8,
+    fan_soc: CurveTracker,
+    fan_batt: CurveTracker,
+    fan_sp: FanSetpoint,
+    game_mode: bool,
+    last_pkg: Option<String>,
+
+    idle_mode: bool,
+    idle_accum: Duration,
+
+    screen_on: bool,
+    screen_off_since: Option<Instant>,
+
+    saver: BatterySaver,
+    stable_for: Duration,
+    /// Profile/preset knobs selected by the last step.
+    perf: Option<ActivePerf>,
+}
+
+impl Controller {
+    pub fn new(dev: &'static DeviceDescriptor, gpu_freqs: &'static [u64], now: Instant) -> Result<Self, String> {
+        let cpu: Vec<Domain> = dev
+            .clusters
+            .iter()
+            .map(|c| {
+                Domain::new(
+                    &c.label, &c.freqs, &c.min_path(), &c.max_path(), &c.cpus, false, now,
+                    DomainTuning::builtin(c.base_ratio, c.down_after_ms),
+                )
+            })
+            .collect::<Result<_, _>>()?;
+        let g = &dev.gpu;
+        let gpu = Domain::new(
+            "GPU", gpu_freqs, &g.min, &g.max, &[], true, now,
+            DomainTuning::builtin(g.base_ratio, g.down_after_ms),
+        )?;
+        let tuning_defaults = cpu.iter().chain(std::iter::once(&gpu)).map(|d| *d.tuning()).collect();
+
+        Ok(Self {
+            cpu,
+            gpu,
+            tuning_defaults,
+            parkable_cpus: dev.parkable_cpus(),
+            prime_cpus: dev.prime_cpus(),
+            base_cpus: dev.base_cpus(),
+            last_zone: TempZone::COOL,
+            critical: CriticalGuard::default(),
+            control_formula: "normal",
+            slope: SlopeEstimator::default(),
+            pid: ThermalPid::default(),
+            fan_level: 0,
+            fan_soc: CurveTracker::new(now),
+            fan_batt: CurveTracker::new(now),
+            fan_sp: FanSetpoint::default(),
+            game_mode: false,
+            last_pkg: None,
+            idle_mode: false,
+            idle_accum: Duration::ZERO,
+            screen_on: true,
+            screen_off_since: None,
+            saver: BatterySaver::default(),
+            stable_for: Duration::ZERO,
+            perf: None,
+        })
+    }
+
+    /// Profile/preset in effect since the last `step`.
+    pub fn active_perf(&self) -> Option<&ActivePerf> {
+        self.perf.as_ref()
+    }
+
+    /// All domains in apply order (CPU clusters, then GPU).
+    pub fn domains(&self) -> impl Iterator<Item = &Domain> {
+        self.cpu.iter().chain(std::iter::once(&self.gpu))
+    }
+
+    pub fn step(&mut self, inp: &Inputs, cfg: &UserConfig, now: Instant, dt: Duration) -> Decisions {
+        let game = &inp.game;
+        let screen_on = inp.screen_on;
+
+        if screen_on != self.screen_on {
+            self.screen_on = screen_on;
+            self.screen_off_since = if screen_on { None } else { Some(now) };
+        }
+
+        let game_entered = game.active && !self.game_mode;
+        self.game_mode = game.active;
+        // Entering a game or switching between games immediately applies that game's fan minimum.
+        let pkg_changed = inp.pkg != self.last_pkg;
+        self.last_pkg = inp.pkg.clone();
+        if cfg.use_phone_cooler && game.active && (game_entered || pkg_changed) && self.fan_level < game.fan_min_level {
+            self.fan_level = game.fan_min_level.min(5);
+        }
+        let game_mode = self.game_mode;
+
+        // Active profile: Gaming/Normal or a custom one whose rule matches.
+        let ctx = RuleContext {
+            pkg: inp.pkg.as_deref(),
+            game_mode,
+            charging: inp.charging,
+            battery_percent: inp.battery_percent,
+            screen_on,
+            minute_of_day: inp.minute_of_day,
+            band: self.last_zone.band,
+        };
+        let perf = select_active_perf(cfg, &ctx, inp.app.as_ref());
+        let schedule = active_schedule(cfg, inp.minute_of_day, game_mode);
+
+        // Domain tunables: `domain_tuning`, the active profile's overrides, the preset, then the
+        // schedule (hot-reloaded). Mins follow them: ~50% in game unless `min_ratio` says otherwise.
+        for (d, base) in self.cpu.iter_mut().chain(std::iter::once(&mut self.gpu)).zip(&self.tuning_defaults) {
+            let layers = std::iter::once(&cfg.domain_tuning)
+                .chain(perf.domain_tuning())
+                .chain(schedule.and_then(|s| s.domain_tuning.as_ref()));
+            d.retune(base.resolve(d.label, layers));
+            d.min_freq = d.tuned_min_freq(game_mode);
+        }
+
+        // charging config switch (defaults to ON)
+        let charging = inp.charging;
+        let charging_effective = charging && cfg.charging.enabled;
+
+        // Thermal zone from the control temperature (formula picked by state).
+        let (control_formula, formula) = cfg.control_temp.select(game_mode, charging_effective);
+        let control_temp_mc = read_control_temp_mc(formula, inp.batt_temp_mc, inp.cpu_avg_mc, inp.gpu_avg_mc);
+        if control_formula != self.control_formula {
+            // A different formula is a step in the signal, not a real rise/fall.
+            self.control_formula = control_formula;
+            self.slope.clear();
+        }
+        // dT/dt over the recent history; steep slopes move the curve input ahead of the reading.
+        let pc = &cfg.thermal_predict;
+        let temp_slope = match control_temp_mc {
+            Some(t) if pc.enabled => self.slope.update(now, t, Duration::from_secs(pc.window_s as u64)),
+            _ => {
+                self.slope.clear();
+                None
+            }
+        };
+        let zone_temp_mc = control_temp_mc.map(|t| predicted_temp_mc(t, temp_slope, pc));
+        let zone = if let Some(t) = zone_temp_mc {
+            zone_with_hysteresis(t, self.last_zone, perf.thermal_curve(cfg))
+        } else {
+            self.last_zone
+        };
+        let zone_changed = zone != self.last_zone;
+        self.last_zone = zone;
+
+        // Critical layer: raw SoC hotspot and battery, independent of formula and game bypasses.
+        // Includes sensors excluded from control (traces may only carry the averages).
+        let soc_hot_mc = inp
+            .domain_temps_mc
+            .values()
+            .copied()
+            .chain(inp.cpu_avg_mc)
+            .chain(inp.gpu_avg_mc)
+            .chain(inp.soc_hot_mc)
+            .max();
+        let batt_hot_mc = inp.batt_hot_mc.max(inp.batt_temp_mc);
+        let critical = self.critical.update(soc_hot_mc, batt_hot_mc, &cfg.critical);
+
+        let utils: Vec<u8> = self.cpu.iter().map(|d| avg_util(&inp.cpu_utils, &d.cpus)).collect();
+        let max_cpu_cluster = utils.iter().copied().max().unwrap_or(0);
+        let ug = inp.ug;
+
+        // ------------------------------
+        // Smart battery saver (CPU core hotplug)
+        // ------------------------------
+        let bs_enabled = perf.battery_saver_enabled(cfg);
+        let mut offline_by_battery: Vec<usize> = Vec::new();
+        let mut offline_by_screen_off: Vec<usize> = Vec::new();
+
+        // Battery thresholds (tiro cores in brackets):
+        //  - <50%  => disable the prime cluster [cpu7]
+        //  - <35%  => disable all parkable clusters [cpu5,cpu6,cpu7]
+        // Overrides:
+        //  - charging OR game_mode => always enable all
+        //  - if base cores >=90% for 15s => enable all cores
+        if bs_enabled && !charging && !game_mode {
+            if let Some(pct) = inp.battery_percent {
+                if pct < 35 {
+                    offline_by_battery.extend(self.parkable_cpus.iter().copied());
+                } else if pct < 50 {
+                    offline_by_battery.extend(self.prime_cpus.iter().copied());
+                }
+            }
+        }
+
+        if offline_by_battery.is_empty() {
+            // Feature disabled / not applicable => reset battery-saver state.
+            self.saver.reset();
+        } else {
+            // Base cores are never parked by mora, so their load is read straight from utils.
+            let base_util = avg_util(&inp.cpu_utils, &self.base_cpus);
+            self.saver.update(base_util, now, dt);
+            if self.saver.override_on {
+                offline_by_battery.clear();
+            }
+        }
+
+        // Additional saver: if the screen stays OFF for 30 minutes, temporarily park the parkable cores.
+        // Battery-based saver remains the priority; when the screen turns back ON, only the
+        // extra screen-off restriction is removed and the battery policy keeps whatever cores
+        // should still stay offline for the current battery %.
+        if !charging && !game_mode {
+            if let Some(since) = self.screen_off_since {
+                if now.duration_since(since) >= Duration::from_secs(SCREEN_OFF_CORE_SAVER_SECS) {
+                    offline_by_screen_off.extend(self.parkable_cpus.iter().copied());
+                }
+            }
+        }
+
+        let mut offline_cores: Vec<usize> = offline_by_battery.clone();
+        for c in offline_by_screen_off.iter().copied() {
+            if !offline_cores.contains(&c) {
+                offline_cores.push(c);
+            }
+        }
+        offline_cores.sort_unstable();
+
+        let battery_saver = BatterySaverStatus {
+            active: bs_enabled && !charging && !game_mode && !self.saver.override_on && !offline_by_battery.is_empty(),
+            override_on: self.saver.override_on,
+            disabled_cores: offline_by_battery,
+            reapply_in_sec: self.saver.reapply_at.map(|t| if t > now { (t - now).as_secs() } else { 0 }),
+        };
+
+        // ------------------------------
+        // Profile/LED selection
+        // ------------------------------
+        let profile = perf.profile.name.clone();
+        let profile_rule = perf.rule.clone();
+        let led = select_base_led(cfg, &perf.profile, perf.app.as_ref(), schedule, screen_on, charging_effective, game_mode);
+
+        // fan
+        let mut fan_setpoint = None;
+        if cfg.use_phone_cooler {
+            // Profile/preset offset shifts the curve input; unknown SoC stays unknown.
+            let pf = perf.fan();
+            let offset_mc = (pf.temp_offset_c * 1000.0).round() as i32;
+            let soc = read_soc_temp_mc(inp.cpu_avg_mc, inp.gpu_avg_mc).map(|t| t + offset_mc);
+            let batt = inp.batt_temp_mc.map(|t| t + offset_mc);
+            fan_setpoint = if cfg.fan_control.mode == FanControlMode::Setpoint {
+                let sc = &cfg.fan_control.setpoint;
+                let t = match sc.sensor {
+                    FanSetpointSensor::Soc => soc,
+                    FanSetpointSensor::Battery => batt,
+                };
+                match t {
+                    Some(t) => Some(self.fan_sp.update(t, self.fan_level, sc, now, dt)),
+                    None => self.fan_sp.last(),
+                }
+            } else {
+                self.fan_sp.reset();
+                None
+            };
+            // The setpoint level stands in for both curves; without a reading yet, curves apply.
+            let (soc_level, batt_level) = if let Some(s) = &fan_setpoint {
+                (s.level, s.level)
+            } else {
+                // Profile curves replace the global ones; the game curve replaces the SoC one in games.
+                let curves = &cfg.fan_curves;
+                let soc_curve = match game_mode {
+                    true => pf.game.as_ref().or(curves.game.as_ref()),
+                    false => None,
+                }
+                .or(pf.soc.as_ref())
+                .unwrap_or(&curves.soc);
+                let batt_curve = pf.battery.as_ref().unwrap_or(&curves.battery);
+                (self.fan_soc.update(soc_curve, soc, now), self.fan_batt.update(batt_curve, batt, now))
+            };
+            // At 100% battery, don't keep the cooler running just because charging is connected.
+            // Game mode is excluded so per-game fan rules still work while playing.
+            let cooler_charging_effective = charging_effective && !(inp.battery_percent == Some(100) && !game_mode);
+            let mut target = Fan::target_level(
+                soc_level,
+                batt_level,
+                screen_on,
+                cooler_charging_effective,
+                game_mode,
+                game.fan_min_level,
+            );
+            if let Some(min) = pf.min_level {
+                if screen_on || cooler_charging_effective {
+                    target = target.max(min);
+                }
+            }
+            if let Some(max) = pf.max_level {
+                // Never below the per-game fan minimum.
+                target = target.min(if game_mode { max.max(game.fan_min_level) } else { max });
+            }
+            // Quiet hours: hard ceiling, per-game minimums included.
+            if let Some(max) = schedule.and_then(|s| s.fan_max_level) {
+                target = target.min(max);
+            }
+            self.fan_level = Fan::smooth_step(self.fan_level, target);
+        } else {
+            self.fan_sp.reset();
+            self.fan_level = 0;
+        }
+        if let Some(level) = inp.fan_override {
+            self.fan_level = level.min(5);
+        }
+        if critical.active() {
+            self.fan_level = 5;
+        }
+
+        // idle mode
+        let idle_cond = !screen_on && !inp.bg_over && max_cpu_cluster < IDLE_CPU_MAX && ug < IDLE_GPU_MAX;
+
+        if idle_cond { self.idle_accum += dt; } else { self.idle_accum = Duration::ZERO; }
+
+        let was_idle = self.idle_mode;
+        if !self.idle_mode && self.idle_accum >= Duration::from_secs(IDLE_ENTER_SECS) {
+            self.idle_mode = true;
+
+            if !charging_effective && !game_mode {
+                for d in self.cpu.iter_mut().chain(std::iter::once(&mut self.gpu)) {
+                    d.idx = d.base_index;
+                }
+            }
+        }
+        if self.idle_mode && !idle_cond {
+            self.idle_mode = false;
+        }
+        let idle_mode = self.idle_mode;
+
+        // GPU turbo: pin min/max to maximum while foreground game requests it.
+        let gpu_turbo_active = game_mode && screen_on && game.gpu_turbo && !critical.active();
+        if gpu_turbo_active {
+            self.gpu.min_freq = self.gpu.max_freq;
+        }
+
+        // desired idx update
+        let mut any_step = false;
+        for (d, &u) in self.cpu.iter_mut().zip(utils.iter()) {
+            any_step |= d.desired_step_update(u, now, dt);
+        }
+        if !gpu_turbo_active {
+            any_step |= self.gpu.desired_step_update(ug, now, dt);
+        } else {
+            // Force GPU domain to max immediately while turbo is active.
+            let top = self.gpu.freqs.len() - 1;
+            self.gpu.force_idx(top, now);
+        }
+
+        // caps
+        let thermal_bypass = game_mode && game.disable_thermal_limit && !critical.active();
+        let effective_zone = if thermal_bypass { TempZone::COOL } else { zone };
+        let pid = if thermal_bypass || cfg.thermal_control.mode != ThermalControlMode::Pid {
+            self.pid.reset();
+            None
+        } else if let Some(t) = control_temp_mc {
+            let pc = &cfg.thermal_control.pid;
+            let sp = pid::setpoint_mc(pc, game_mode, charging_effective);
+            Some(self.pid.update(t, sp, pc, dt))
+        } else {
+            self.pid.last()
+        };
+        let limit = match (&pid, cfg.thermal_control.mode) {
+            _ if critical.active() => ThermalLimit::FLOOR,
+            (Some(p), _) => p.limit(),
+            (None, ThermalControlMode::Pid) => ThermalLimit::NONE,
+            (None, ThermalControlMode::Zones) => ThermalLimit::from_zone(effective_zone),
+        };
+
+        // Per-domain attribution: the hotspot domain takes the full cut, cooler ones a share of it.
+        let att = &cfg.thermal_attribution;
+        let hotspot = self
+            .domains()
+            .filter_map(|d| inp.domain_temps_mc.get(d.label).map(|&t| (d.label, t)))
+            .max_by_key(|&(_, t)| t);
+        let why = match (&pid, control_temp_mc) {
+            _ if critical.active() => format!(
+                "critical: soc {} | batt {}",
+                soc_hot_mc.map(fmt_c).unwrap_or_else(|| "?".to_string()),
+                inp.batt_temp_mc.map(fmt_c).unwrap_or_else(|| "?".to_string())
+            ),
+            (Some(p), Some(t)) => format!("pid: {} vs setpoint {}", fmt_c(t), fmt_c(p.setpoint_mc)),
+            (_, Some(t)) => format!("{}: control {}", effective_zone, fmt_c(t)),
+            _ => format!("{}", effective_zone),
+        };
+        let spread_mc = (att.spread_c * 1000.0) as i32;
+        let min_share = att.min_share_percent as i32 * 10;
+
+        let mut any_cap_change = false;
+        let mut thermal_domains = Vec::with_capacity(self.cpu.len() + 1);
+        for d in self.cpu.iter_mut().chain(std::iter::once(&mut self.gpu)) {
+            let temp_mc = inp.domain_temps_mc.get(d.label).copied();
+            let share = match (att.enabled && !critical.active(), temp_mc, hotspot) {
+                (true, Some(t), Some((_, hot))) => (1000 - (hot - t) * 1000 / spread_mc.max(1)).clamp(min_share, 1000),
+                _ => 1000,
+            };
+            let dl = limit.scaled(share as u32);
+            any_cap_change |= d.plan_cap(dl);
+
+            let reduce_percent = dl.reduce_percent();
+            let reason = match (reduce_percent > 0.0, hotspot, temp_mc) {
+                (false, _, _) => String::new(),
+                (true, Some((hl, hot)), Some(t)) if att.enabled && share < 1000 => {
+                    format!("{}; {}% share, {} below hotspot {}", why, share / 10, fmt_c(hot - t), hl)
+                }
+                (true, Some((hl, hot)), Some(_)) if att.enabled => format!("{}; hotspot {} {}", why, hl, fmt_c(hot)),
+                (true, _, _) => why.clone(),
+            };
+            thermal_domains.push(DomainThermal {
+                label: d.label,
+                temp_mc,
+                share_percent: (share / 10) as u8,
+                reduce_percent,
+                reason,
+            });
+        }
+
+        let caps: Vec<CapDecision> = self
+            .cpu
+            .iter()
+            .zip(utils.iter().copied())
+            .chain(std::iter::once((&self.gpu, ug)))
+            .map(|(d, util)| CapDecision {
+                label: d.label,
+                util,
+                min: d.effective_min(),
+                max: d.cap_freq(),
+            })
+            .collect();
+
+        // sleep selection
+        if any_cap_change || any_step { self.stable_for = Duration::ZERO; } else { self.stable_for += dt; }
+        let stable_for = self.stable_for;
+
+        let sleep_scale = perf.sleep_scale();
+        let sleep_ms = match zone {
+            _ if critical.active() => 450,
+            z if z.fast_poll => 450,
+            _ => {
+                if idle_mode {
+                    MAX_SLEEP_MS
+                } else if any_cap_change || any_step {
+                    750
+                } else if game_mode || charging_effective {
+                    if stable_for >= Duration::from_secs(30) { 3000 } else { 1500 }
+                } else if !screen_on {
+                    12000
+                } else if stable_for >= Duration::from_secs(60) {
+                    8000
+                } else if stable_for >= Duration::from_secs(30) {
+                    5000
+                } else {
+                    2000
+                }
+            }
+        };
+        let sleep_ms = match zone {
+            _ if critical.active() => sleep_ms,
+            z if z.fast_poll => sleep_ms,
+            _ if sleep_scale != 1.0 => ((sleep_ms as f32 * sleep_scale).round() as u64).clamp(300, MAX_SLEEP_MS),
+            _ => sleep_ms,
+        };
+        self.perf = Some(perf);
+
+        Decisions {
+            control_temp_mc,
+            control_formula,
+            temp_slope_c_per_min: temp_slope.map(|s| s * 60.0 / 1000.0),
+            zone_temp_mc,
+            zone,
+            effective_zone,
+            critical,
+            soc_hot_mc,
+            pid,
+            caps,
+            hotspot: hotspot.map(|(l, _)| l),
+            thermal_domains,
+            fan_level: self.fan_level,
+            fan_setpoint,
+            fan_override: inp.fan_override,
+            offline_cores,
+            battery_saver,
+            screen_off_saver_cores: offline_by_screen_off,
+            charging_effective,
+            game_mode,
+            idle_mode,
+            profile,
+            profile_rule,
+            app_rule: inp.app.as_ref().map(|a| a.package.clone()),
+            schedule: schedule.map(|s| s.name.clone()),
+            led,
+            sleep_ms,
+            zone_changed,
+            idle_changed: idle_mode != was_idle,
+        }
+    }
+}
@@ -1,6 +1,8 @@
 
 use std::time::Instant;
 
+use crate::sysfs;
+
 #[derive(Clone)]
 pub struct CpuStatSample {
     per_cpu: Vec<(u64, u64)>, // (idle_all, total)
@@ -8,7 +10,7 @@ pub struct CpuStatSample {
 }
 
 fn parse_proc_stat_percpu() -> Option<Vec<(u64, u64)>> {
-    let s = std::fs::read_to_string("/proc/stat").ok()?;
+    let s = std::fs::read_to_string(sysfs::path("/proc/stat")).ok()?;
     let mut out: Vec<(u64, u64)> = Vec::new();
 
     for line in s.lines() {
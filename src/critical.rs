@@ -0,0 +1,119 @@
+//! Critical-temperature safeguard.
+//!
+//! Sits above zones, PID and every per-game bypass: past the critical temperature all
+//! domains are pinned to their table minimum and the fan to max; past the shutdown-warning
+//! temperature `main.rs` additionally hands thermal back to the stock services.
+
+use serde::Serialize;
+
+use crate::user_config::CriticalConfig;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CriticalLevel {
+    #[default]
+    None,
+    Critical,
+    Shutdown,
+}
+
+impl CriticalLevel {
+    pub fn active(self) -> bool {
+        self != CriticalLevel::None
+    }
+}
+
+#[derive(Default)]
+pub struct CriticalGuard {
+    level: CriticalLevel,
+}
+
+fn c_to_mc(c: f32) -> i32 {
+    (c * 1000.0).round() as i32
+}
+
+impl CriticalGuard {
+    /// Raise immediately to the highest level reached; drop one level once both temperatures
+    /// are `release_c` below that level's thresholds. Missing readings never lower the level.
+    pub fn update(&mut self, soc_mc: Option<i32>, batt_mc: Option<i32>, cfg: &CriticalConfig) -> CriticalLevel {
+        let over = |soc_c: f32, batt_c: f32, margin_c: f32| {
+            soc_mc.is_some_and(|t| t >= c_to_mc(soc_c - margin_c)) || batt_mc.is_some_and(|t| t >= c_to_mc(batt_c - margin_c))
+        };
+
+        let reached = if over(cfg.soc_shutdown_c, cfg.batt_shutdown_c, 0.0) {
+            CriticalLevel::Shutdown
+        } else if over(cfg.soc_critical_c, cfg.batt_critical_c, 0.0) {
+            CriticalLevel::Critical
+        } else {
+            CriticalLevel::None
+        };
+        if reached >= self.level {
+            self.level = reached;
+            return self.level;
+        }
+
+        let holds = match self.level {
+            CriticalLevel::Shutdown => over(cfg.soc_shutdown_c, cfg.batt_shutdown_c, cfg.release_c),
+            CriticalLevel::Critical => over(cfg.soc_critical_c, cfg.batt_critical_c, cfg.release_c),
+            CriticalLevel::None => false,
+        };
+        if !holds && (soc_mc.is_some() || batt_mc.is_some()) {
+            self.level = match self.level {
+                CriticalLevel::Shutdown => CriticalLevel::Critical,
+                _ => CriticalLevel::None,
+            };
+        }
+        self.level
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use CriticalLevel::{Critical, None as Off, Shutdown};
+
+    fn c(v: f32) -> Option<i32> {
+        Some(c_to_mc(v))
+    }
+
+    #[test]
+    fn holds_until_release_margin() {
+        let cfg = CriticalConfig::default();
+        let mut g = CriticalGuard::default();
+        assert_eq!(g.update(c(100.0), c(40.0), &cfg), Critical);
+        // Below the threshold but within release_c (3 °C): held.
+        assert_eq!(g.update(c(97.5), c(40.0), &cfg), Critical);
+        assert_eq!(g.update(c(97.0), c(40.0), &cfg), Critical);
+        assert_eq!(g.update(c(96.9), c(40.0), &cfg), Off);
+    }
+
+    #[test]
+    fn either_sensor_holds_the_level() {
+        let cfg = CriticalConfig::default();
+        let mut g = CriticalGuard::default();
+        assert_eq!(g.update(c(80.0), c(60.0), &cfg), Critical);
+        // SoC is cool, the battery is still within its margin.
+        assert_eq!(g.update(c(60.0), c(58.0), &cfg), Critical);
+        assert_eq!(g.update(c(60.0), c(56.0), &cfg), Off);
+    }
+
+    #[test]
+    fn shutdown_releases_one_level_at_a_time() {
+        let cfg = CriticalConfig::default();
+        let mut g = CriticalGuard::default();
+        assert_eq!(g.update(c(111.0), c(40.0), &cfg), Shutdown);
+        // Cold at once: still one level per update.
+        assert_eq!(g.update(c(50.0), c(40.0), &cfg), Critical);
+        assert_eq!(g.update(c(50.0), c(40.0), &cfg), Off);
+    }
+
+    #[test]
+    fn missing_readings_keep_the_level() {
+        let cfg = CriticalConfig::default();
+        let mut g = CriticalGuard::default();
+        assert_eq!(g.update(c(101.0), None, &cfg), Critical);
+        assert_eq!(g.update(None, None, &cfg), Critical);
+        assert_eq!(g.update(c(90.0), None, &cfg), Off);
+    }
+}
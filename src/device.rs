@@ -0,0 +1,462 @@
+//! Device descriptors: CPU clusters, GPU, thermal sensors, fan/LED/trigger nodes.
+//!
+//! Selection order at startup:
+//! 1) `config/devices/<ro.product.device>.json` (user-supplied, overrides built-ins)
+//! 2) built-in descriptor whose `devices` list contains `ro.product.device`
+//! 3) autodetect from `/sys/devices/system/cpu/cpufreq/policy*`
+//! 4) built-in `tiro` (last resort, e.g. no cpufreq at all)
+//!
+//! The active descriptor is process-global (like the sysfs root) and exposed at `/api/device`,
+//! so an autodetected one can be saved as a starting point for a device file.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::OnceLock,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::sysfs;
+
+pub const DEVICES_DIR: &str = "/data/adb/modules/mora_perf_deamon/config/devices";
+
+const BUILTIN: &[(&str, &str)] = &[("tiro", include_str!("assets/devices/tiro.json"))];
+
+const CPUFREQ_DIR: &str = "/sys/devices/system/cpu/cpufreq";
+
+static DEVICE: OnceLock<DeviceDescriptor> = OnceLock::new();
+
+fn default_base_ratio() -> f32 { 0.48 }
+fn default_down_after_ms() -> [u64; 2] { [6000, 3000] }
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ClusterDesc {
+    pub label: String,
+    /// cpufreq policy number (`policyN`).
+    pub policy: u32,
+    pub cpus: Vec<usize>,
+    /// Available frequencies in kHz, ascending.
+    pub freqs: Vec<u64>,
+    /// Base (idle reset) step as a fraction of the table.
+    #[serde(default = "default_base_ratio")]
+    pub base_ratio: f32,
+    /// Low-util hold before stepping down: [util < down1, util < down2] in ms.
+    #[serde(default = "default_down_after_ms")]
+    pub down_after_ms: [u64; 2],
+    /// Cores of this cluster may be taken offline by battery/screen-off saver.
+    #[serde(default)]
+    pub parkable: bool,
+    /// Governor switched in/out of game mode (None = leave alone).
+    #[serde(default)]
+    pub game_governor: Option<String>,
+    #[serde(default)]
+    pub normal_governor: Option<String>,
+    /// Zone types whose hotspot (max) is this cluster's temperature; empty = all CPU zones.
+    #[serde(default)]
+    pub thermal_types: Vec<String>,
+}
+
+impl ClusterDesc {
+    fn policy_node(&self, node: &str) -> String {
+        format!("{}/policy{}/{}", CPUFREQ_DIR, self.policy, node)
+    }
+
+    pub fn min_path(&self) -> String {
+        self.policy_node("scaling_min_freq")
+    }
+
+    pub fn max_path(&self) -> String {
+        self.policy_node("scaling_max_freq")
+    }
+
+    pub fn governor_path(&self) -> String {
+        self.policy_node("scaling_governor")
+    }
+}
+
+fn default_gpu_min() -> String { "/sys/class/kgsl/kgsl-3d0/devfreq/min_freq".to_string() }
+fn default_gpu_max() -> String { "/sys/class/kgsl/kgsl-3d0/devfreq/max_freq".to_string() }
+fn default_gpu_busy_percent() -> String { "/sys/class/kgsl/kgsl-3d0/gpu_busy_percentage".to_string() }
+fn default_gpu_gpubusy() -> String { "/sys/class/kgsl/kgsl-3d0/gpubusy".to_string() }
+fn default_gpu_base_ratio() -> f32 { 0.50 }
+fn default_gpu_down_after_ms() -> [u64; 2] { [5000, 3000] }
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GpuDesc {
+    #[serde(default = "default_gpu_min")]
+    pub min: String,
+    #[serde(default = "default_gpu_max")]
+    pub max: String,
+    #[serde(default = "default_gpu_busy_percent")]
+    pub busy_percent: String,
+    #[serde(default = "default_gpu_gpubusy")]
+    pub gpubusy: String,
+    /// Fallback table in Hz (the kgsl list is preferred when readable).
+    #[serde(default)]
+    pub freqs: Vec<u64>,
+    #[serde(default = "default_gpu_base_ratio")]
+    pub base_ratio: f32,
+    #[serde(default = "default_gpu_down_after_ms")]
+    pub down_after_ms: [u64; 2],
+    /// Zone types whose hotspot (max) is the GPU temperature; empty = all GPU zones.
+    #[serde(default)]
+    pub thermal_types: Vec<String>,
+}
+
+impl Default for GpuDesc {
+    fn default() -> Self {
+        Self {
+            min: default_gpu_min(),
+            max: default_gpu_max(),
+            busy_percent: default_gpu_busy_percent(),
+            gpubusy: default_gpu_gpubusy(),
+            freqs: Vec::new(),
+            base_ratio: default_gpu_base_ratio(),
+            down_after_ms: default_gpu_down_after_ms(),
+            thermal_types: Vec::new(),
+        }
+    }
+}
+
+/// Thermal sensors are matched by `thermal_zone*/type` against glob patterns (`*`, `?`),
+/// because numeric zone ids shift between kernel builds. Explicit ids are still accepted for
+/// a group whose pattern list is empty.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ThermalDesc {
+    /// Zone types averaged into the CPU temperature, e.g. `cpu-*-*`.
+    #[serde(default)]
+    pub cpu_types: Vec<String>,
+    /// Zone types averaged into the GPU temperature, e.g. `gpuss-*`.
+    #[serde(default)]
+    pub gpu_types: Vec<String>,
+    /// Battery zone type(s); the lowest matching zone id is used.
+    #[serde(default)]
+    pub battery_types: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cpu_zone_ids: Vec<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub gpu_zone_ids: Vec<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub battery_zone_id: Option<u32>,
+}
+
+impl ThermalDesc {
+    fn qcom_defaults() -> Self {
+        Self {
+            cpu_types: vec!["cpu-*-*".to_string()],
+            gpu_types: vec!["gpuss-*".to_string()],
+            battery_types: vec!["battery".to_string()],
+            ..Self::default()
+        }
+    }
+}
+
+fn default_fan_enable() -> String { "/sys/kernel/fan/fan_enable".to_string() }
+fn default_fan_level() -> String { "/sys/kernel/fan/fan_speed_level".to_string() }
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FanDesc {
+    #[serde(default = "default_fan_enable")]
+    pub enable: String,
+    #[serde(default = "default_fan_level")]
+    pub level: String,
+}
+
+impl Default for FanDesc {
+    fn default() -> Self {
+        Self { enable: default_fan_enable(), level: default_fan_level() }
+    }
+}
+
+fn default_led_base() -> String { "/sys/class/leds/aw22xxx_led".to_string() }
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LedDesc {
+    #[serde(default = "default_led_base")]
+    pub base: String,
+}
+
+impl Default for LedDesc {
+    fn default() -> Self {
+        Self { base: default_led_base() }
+    }
+}
+
+fn default_trigger_left_mode() -> String { "/proc/nubia_key/sar0/mode_operation".to_string() }
+fn default_trigger_right_mode() -> String { "/proc/nubia_key/sar1/mode_operation".to_string() }
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TriggerDesc {
+    /// Nodes that switch the stock shoulder-trigger handling on/off.
+    #[serde(default = "default_trigger_left_mode")]
+    pub left_mode: String,
+    #[serde(default = "default_trigger_right_mode")]
+    pub right_mode: String,
+}
+
+impl Default for TriggerDesc {
+    fn default() -> Self {
+        Self { left_mode: default_trigger_left_mode(), right_mode: default_trigger_right_mode() }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeviceDescriptor {
+    pub name: String,
+    /// `ro.product.device` values this descriptor applies to.
+    #[serde(default)]
+    pub devices: Vec<String>,
+    pub clusters: Vec<ClusterDesc>,
+    #[serde(default)]
+    pub gpu: GpuDesc,
+    #[serde(default)]
+    pub thermal: ThermalDesc,
+    #[serde(default)]
+    pub fan: FanDesc,
+    #[serde(default)]
+    pub leds: LedDesc,
+    #[serde(default)]
+    pub triggers: TriggerDesc,
+
+    /// Where this descriptor came from (filled at load time).
+    #[serde(default, skip_deserializing)]
+    pub source: String,
+}
+
+impl DeviceDescriptor {
+    /// Sort tables, drop empty clusters and check what the control loop relies on.
+    pub fn validate_and_normalize(&mut self) -> Result<(), String> {
+        for c in &mut self.clusters {
+            c.freqs.sort_unstable();
+            c.freqs.dedup();
+            c.cpus.sort_unstable();
+            c.cpus.dedup();
+            c.base_ratio = c.base_ratio.clamp(0.0, 1.0);
+            // cpu0 can't be hotplugged.
+            if c.cpus.contains(&0) {
+                c.parkable = false;
+            }
+        }
+        self.clusters.retain(|c| !c.freqs.is_empty() && !c.cpus.is_empty());
+        if self.clusters.is_empty() {
+            return Err("no usable clusters".to_string());
+        }
+        self.clusters.sort_by_key(|c| c.policy);
+
+        self.gpu.freqs.sort_unstable();
+        self.gpu.freqs.dedup();
+        self.gpu.base_ratio = self.gpu.base_ratio.clamp(0.0, 1.0);
+        Ok(())
+    }
+
+    /// CPUs that battery/screen-off saver may take offline.
+    pub fn parkable_cpus(&self) -> Vec<usize> {
+        self.clusters.iter().filter(|c| c.parkable).flat_map(|c| c.cpus.iter().copied()).collect()
+    }
+
+    /// CPUs of the last (prime) cluster, parked first by battery saver.
+    pub fn prime_cpus(&self) -> Vec<usize> {
+        match self.clusters.last() {
+            Some(c) if c.parkable => c.cpus.clone(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// CPUs never parked by mora.
+    pub fn base_cpus(&self) -> Vec<usize> {
+        self.clusters.iter().filter(|c| !c.parkable).flat_map(|c| c.cpus.iter().copied()).collect()
+    }
+}
+
+fn parse(s: &str, source: String) -> Result<DeviceDescriptor, String> {
+    let mut d: DeviceDescriptor = serde_json::from_str(s).map_err(|e| e.to_string())?;
+    d.validate_and_normalize()?;
+    d.source = source;
+    Ok(d)
+}
+
+fn builtin(name: &str) -> Option<DeviceDescriptor> {
+    for (key, json) in BUILTIN {
+        let d = match parse(json, format!("built-in {}", key)) {
+            Ok(d) => d,
+            Err(e) => {
+                eprintln!("DEVICE: built-in {} invalid: {}", key, e);
+                continue;
+            }
+        };
+        if *key == name || d.devices.iter().any(|x| x == name) {
+            return Some(d);
+        }
+    }
+    None
+}
+
+fn prop_from_build_prop(key: &str) -> Option<String> {
+    for f in ["/vendor/build.prop", "/system/build.prop"] {
+        let Some(s) = sysfs::read_to_string(&sysfs::path(f)) else { continue };
+        for line in s.lines() {
+            if let Some(v) = line.strip_prefix(key).and_then(|r| r.strip_prefix('=')) {
+                let v = v.trim();
+                if !v.is_empty() {
+                    return Some(v.to_string());
+                }
+            }
+        }
+    }
+    None
+}
+
+/// `ro.product.device` (getprop on the phone, build.prop under a relocated root).
+pub fn product_device() -> Option<String> {
+    if sysfs::is_relocated() {
+        return prop_from_build_prop("ro.product.device");
+    }
+    let out = Command::new("getprop").arg("ro.product.device").output().ok()?;
+    let v = String::from_utf8(out.stdout).ok()?.trim().to_string();
+    if v.is_empty() { None } else { Some(v) }
+}
+
+fn parse_list<T: std::str::FromStr>(s: &str) -> Vec<T> {
+    s.split_whitespace().filter_map(|t| t.parse().ok()).collect()
+}
+
+/// Build a descriptor from cpufreq policies (thermal zones use the Qualcomm type patterns).
+/// Clusters get tiro-like tuning by position: first = little, last = prime (parkable).
+pub fn autodetect() -> Option<DeviceDescriptor> {
+    let dir = sysfs::path(CPUFREQ_DIR);
+    let mut clusters = Vec::new();
+    for ent in fs::read_dir(&dir).ok()?.flatten() {
+        let name = ent.file_name().to_string_lossy().to_string();
+        let Some(policy) = name.strip_prefix("policy").and_then(|n| n.parse::<u32>().ok()) else { continue };
+        let p = ent.path();
+        let cpus: Vec<usize> = parse_list(&sysfs::read_to_string(&p.join("related_cpus")).unwrap_or_default());
+        let freqs: Vec<u64> = parse_list(&sysfs::read_to_string(&p.join("scaling_available_frequencies")).unwrap_or_default());
+        if cpus.is_empty() || freqs.is_empty() {
+            continue;
+        }
+        clusters.push(ClusterDesc {
+            label: format!("CPU{}", policy),
+            policy,
+            cpus,
+            freqs,
+            base_ratio: default_base_ratio(),
+            down_after_ms: default_down_after_ms(),
+            parkable: false,
+            game_governor: None,
+            normal_governor: None,
+            thermal_types: Vec::new(),
+        });
+    }
+    clusters.sort_by_key(|c| c.policy);
+
+    let n = clusters.len();
+    if n == 0 {
+        return None;
+    }
+    clusters[0].base_ratio = 0.62;
+    if n > 1 {
+        let prime = &mut clusters[n - 1];
+        prime.base_ratio = 0.35;
+        prime.down_after_ms = [7000, 4000];
+        prime.parkable = true;
+    }
+
+    let mut d = DeviceDescriptor {
+        name: "autodetected".to_string(),
+        devices: Vec::new(),
+        clusters,
+        gpu: GpuDesc::default(),
+        thermal: ThermalDesc::qcom_defaults(),
+        fan: FanDesc::default(),
+        leds: LedDesc::default(),
+        triggers: TriggerDesc::default(),
+        source: "autodetect".to_string(),
+    };
+    d.validate_and_normalize().ok()?;
+    Some(d)
+}
+
+fn user_file(name: &str) -> Option<DeviceDescriptor> {
+    let path: PathBuf = sysfs::path(DEVICES_DIR).join(format!("{}.json", name));
+    let s = fs::read_to_string(&path).ok()?;
+    match parse(&s, path.display().to_string()) {
+        Ok(d) => Some(d),
+        Err(e) => {
+            eprintln!("DEVICE: {} invalid: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Resolve the descriptor. `name` overrides `ro.product.device`; `autodetect` allows probing
+/// cpufreq when nothing matches (disabled for simulation so results don't depend on the host).
+pub fn resolve(name: Option<&str>, allow_autodetect: bool) -> DeviceDescriptor {
+    let name = name.map(str::to_string).or_else(product_device);
+    let found = name
+        .as_deref()
+        .and_then(|n| user_file(n).or_else(|| builtin(n)))
+        .or_else(|| if allow_autodetect { autodetect() } else { None });
+    let mut d = match found {
+        Some(d) => d,
+        None => {
+            let mut d = builtin("tiro").expect("built-in tiro descriptor");
+            d.source = format!("{} (fallback)", d.source);
+            d
+        }
+    };
+    gpu_fallback(&mut d);
+    d
+}
+
+/// The GPU domain needs some table even if kgsl doesn't list one at runtime.
+fn gpu_fallback(d: &mut DeviceDescriptor) {
+    if d.gpu.freqs.is_empty() {
+        d.gpu.freqs = builtin("tiro").map(|t| t.gpu.freqs).unwrap_or_default();
+    }
+}
+
+/// Install the process-wide descriptor (first call wins).
+pub fn init(d: DeviceDescriptor) -> &'static DeviceDescriptor {
+    DEVICE.get_or_init(|| d)
+}
+
+/// Active descriptor. Falls back to built-in tiro if `init` was never called.
+pub fn get() -> &'static DeviceDescriptor {
+    DEVICE.get_or_init(|| builtin("tiro").expect("built-in tiro descriptor"))
+}
+
+/// Read a user descriptor file from an explicit path (`--device-file`).
+pub fn load_file(path: &Path) -> Result<DeviceDescriptor, String> {
+    let s = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let mut d = parse(&s, path.display().to_string())?;
+    gpu_fallback(&mut d);
+    Ok(d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::Controller;
+    use std::time::Instant;
+
+    #[test]
+    fn device_file_without_gpu_table_gets_fallback() {
+        let path = std::env::temp_dir().join(format!("perf_daemon_dev_{}.json", std::process::id()));
+        let json = r#"{
+            "name": "nogpu",
+            "clusters": [{ "label": "CPU0", "policy": 0, "cpus": [0, 1], "freqs": [300000, 1800000] }]
+        }"#;
+        fs::write(&path, json).unwrap();
+        let d = load_file(&path);
+        let _ = fs::remove_file(&path);
+        let d = d.unwrap();
+        assert!(!d.gpu.freqs.is_empty());
+
+        let d: &'static DeviceDescriptor = Box::leak(Box::new(d));
+        assert!(Controller::new(d, &d.gpu.freqs, Instant::now()).is_ok());
+        assert!(Controller::new(d, &[], Instant::now()).is_err());
+    }
+}
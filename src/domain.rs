@@ -6,7 +6,14 @@ use std::{
     time::{Duration, Instant},
 };
 
-use crate::{fmt, sysfs, tempzone::TempZone};
+use serde::Serialize;
+
+use crate::{
+    config::{DOWN_UTIL1, DOWN_UTIL2, HIGH_JUMP2, HIGH_JUMP4, SPIKE_DELTA2, SPIKE_DELTA4, UP_UTIL},
+    sysfs,
+    tempzone::TempZone,
+    user_config::{DomainTunables, DomainTuningConfig},
+};
 
 pub fn clamp_to_table(freqs: &[u64], cap: u64) -> usize {
     let mut lo = 0usize;
@@ -30,6 +37,106 @@ pub fn mid_freq(freqs: &[u64]) -> u64 {
     freqs[freqs.len() / 2]
 }
 
+/// Thermal limit applied on top of the utilization-driven index.
+#[derive(Clone, Copy, Debug)]
+pub struct ThermalLimit {
+    /// Share of the domain's max frequency that stays available (per mille).
+    pub keep_permille: u32,
+    /// Max table steps the cap may drop per iteration.
+    pub max_step_down: usize,
+}
+
+impl ThermalLimit {
+    pub const NONE: ThermalLimit = ThermalLimit { keep_permille: 1000, max_step_down: 1 };
+    /// Table minimum, reached in one iteration.
+    pub const FLOOR: ThermalLimit = ThermalLimit { keep_permille: 0, max_step_down: usize::MAX };
+
+    /// Apply only `share_permille` of this limit's reduction (step rate unchanged).
+    pub fn scaled(self, share_permille: u32) -> Self {
+        let reduce = 1000u32.saturating_sub(self.keep_permille);
+        Self {
+            keep_permille: 1000 - reduce * share_permille.min(1000) / 1000,
+            ..self
+        }
+    }
+
+    pub fn reduce_percent(&self) -> f32 {
+        1000u32.saturating_sub(self.keep_permille) as f32 / 10.0
+    }
+
+    pub fn from_zone(zone: TempZone) -> Self {
+        Self {
+            keep_permille: 1000 - zone.reduction_percent().min(100) * 10,
+            max_step_down: zone.max_step_down.max(1) as usize,
+        }
+    }
+}
+
+/// Resolved control tunables of one domain.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct DomainTuning {
+    pub up_util: u8,
+    pub spike_delta2: u8,
+    pub spike_delta4: u8,
+    pub high_jump2: u8,
+    pub high_jump4: u8,
+    pub down_util1: u8,
+    pub down_util2: u8,
+    pub down_after_ms: [u64; 2],
+    pub base_ratio: f32,
+    /// None: table minimum, mid-table while in game mode.
+    pub min_ratio: Option<f32>,
+    pub max_ratio: f32,
+}
+
+impl DomainTuning {
+    /// Built-in thresholds with the device descriptor's base ratio and down-step delays.
+    pub fn builtin(base_ratio: f32, down_after_ms: [u64; 2]) -> Self {
+        Self {
+            up_util: UP_UTIL,
+            spike_delta2: SPIKE_DELTA2,
+            spike_delta4: SPIKE_DELTA4,
+            high_jump2: HIGH_JUMP2,
+            high_jump4: HIGH_JUMP4,
+            down_util1: DOWN_UTIL1,
+            down_util2: DOWN_UTIL2,
+            down_after_ms,
+            base_ratio,
+            min_ratio: None,
+            max_ratio: 1.0,
+        }
+    }
+
+    fn overlay(mut self, t: &DomainTunables) -> Self {
+        self.up_util = t.up_util.unwrap_or(self.up_util);
+        self.spike_delta2 = t.spike_delta2.unwrap_or(self.spike_delta2);
+        self.spike_delta4 = t.spike_delta4.unwrap_or(self.spike_delta4);
+        self.high_jump2 = t.high_jump2.unwrap_or(self.high_jump2);
+        self.high_jump4 = t.high_jump4.unwrap_or(self.high_jump4);
+        self.down_util1 = t.down_util1.unwrap_or(self.down_util1);
+        self.down_util2 = t.down_util2.unwrap_or(self.down_util2);
+        self.down_after_ms = t.down_after_ms.unwrap_or(self.down_after_ms);
+        self.base_ratio = t.base_ratio.unwrap_or(self.base_ratio);
+        self.min_ratio = t.min_ratio.or(self.min_ratio);
+        self.max_ratio = t.max_ratio.unwrap_or(self.max_ratio);
+        self
+    }
+
+    fn overlay_config(self, label: &str, c: &DomainTuningConfig) -> Self {
+        let t = self.overlay(&c.all);
+        match c.domains.get(label) {
+            Some(d) => t.overlay(d),
+            None => t,
+        }
+    }
+
+    /// Layers, each over the previous one: built-in/device -> `domain_tuning` -> active
+    /// profile's `domain_tuning` -> preset.
+    pub fn resolve<'a>(self, label: &str, layers: impl IntoIterator<Item = &'a DomainTuningConfig>) -> Self {
+        layers.into_iter().fold(self, |t, c| t.overlay_config(label, c))
+    }
+}
+
 pub struct Domain {
     pub label: &'static str,
     pub freqs: &'static [u64],
@@ -37,8 +144,12 @@ pub struct Domain {
     pub max_freq: u64,
     pub min_path: PathBuf,
     pub max_path: PathBuf,
+    /// CPUs whose utilization drives this domain (empty for the GPU).
+    pub cpus: Vec<usize>,
 
     pub base_index: usize,
+    /// Highest index the desired step may reach (`max_ratio`).
+    pub max_index: usize,
     pub idx: usize,
 
     last_util: u8,
@@ -47,16 +158,7 @@ pub struct Domain {
     low_accum: Duration,
 
     // control params
-    pub up_util: u8,
-    pub spike_delta2: u8,
-    pub spike_delta4: u8,
-    pub high_jump2: u8,
-    pub high_jump4: u8,
-
-    pub down_util1: u8,
-    pub down_util2: u8,
-    pub down_after1: Duration,
-    pub down_after2: Duration,
+    tuning: DomainTuning,
 
     last_applied_idx: usize,
     last_applied_freq: u64,
@@ -69,53 +171,67 @@ impl Domain {
         freqs: &'static [u64],
         min_path: &str,
         max_path: &str,
-        base_index: usize,
+        cpus: &[usize],
         is_gpu: bool,
         now: Instant,
-        up_util: u8,
-        spike_delta2: u8,
-        spike_delta4: u8,
-        high_jump2: u8,
-        high_jump4: u8,
-        down_util1: u8,
-        down_util2: u8,
-        down_after1: Duration,
-        down_after2: Duration,
-    ) -> Self {
-        let min_freq = freqs[0];
-        let max_freq = *freqs.last().unwrap();
-        Self {
+        tuning: DomainTuning,
+    ) -> Result<Self, String> {
+        let (Some(&min_freq), Some(&max_freq)) = (freqs.first(), freqs.last()) else {
+            return Err(format!("{}: empty frequency table", label));
+        };
+        let max_index = base_index_from_ratio(freqs, tuning.max_ratio);
+        let base_index = base_index_from_ratio(freqs, tuning.base_ratio).min(max_index);
+        Ok(Self {
             label,
             freqs,
             min_freq,
             max_freq,
-            min_path: min_path.into(),
-            max_path: max_path.into(),
+            min_path: sysfs::path(min_path),
+            max_path: sysfs::path(max_path),
+            cpus: cpus.to_vec(),
             base_index,
+            max_index,
             idx: base_index,
             last_util: 0,
             max_step_up_next_apply: 1,
             hold_until: now,
             low_accum: Duration::ZERO,
-            up_util,
-            spike_delta2,
-            spike_delta4,
-            high_jump2,
-            high_jump4,
-            down_util1,
-            down_util2,
-            down_after1,
-            down_after2,
+            tuning,
             last_applied_idx: base_index,
             last_applied_freq: freqs[base_index],
             is_gpu,
+        })
+    }
+
+    pub fn tuning(&self) -> &DomainTuning {
+        &self.tuning
+    }
+
+    /// Min frequency from the tunables; without `min_ratio`, mid-table in game mode.
+    pub fn tuned_min_freq(&self, game_mode: bool) -> u64 {
+        match self.tuning.min_ratio {
+            Some(r) => self.freqs[base_index_from_ratio(self.freqs, r)],
+            None if game_mode => mid_freq(self.freqs),
+            None => self.freqs[0],
+        }
+    }
+
+    /// Switch to new tunables (config reload / profile change). The current step is kept,
+    /// only moved into the new base..max window.
+    pub fn retune(&mut self, tuning: DomainTuning) {
+        if tuning == self.tuning {
+            return;
         }
+        self.max_index = base_index_from_ratio(self.freqs, tuning.max_ratio);
+        self.base_index = base_index_from_ratio(self.freqs, tuning.base_ratio).min(self.max_index);
+        self.idx = self.idx.clamp(self.base_index, self.max_index);
+        self.tuning = tuning;
     }
 
     /// Force domain index (used for turbo modes). Allows an immediate jump on next apply.
     pub fn force_idx(&mut self, idx: usize, now: Instant) {
         if self.freqs.is_empty() { return; }
-        let idx = idx.min(self.freqs.len() - 1);
+        let idx = idx.min(self.max_index);
         self.idx = idx;
         // allow immediate jump to requested index on next apply
         self.max_step_up_next_apply = self.freqs.len();
@@ -131,22 +247,23 @@ impl Domain {
         let delta = if util > self.last_util { util - self.last_util } else { 0 };
         self.last_util = util;
 
+        let t = &self.tuning;
         let mut jump_up: usize = 0;
         // Do not treat tiny absolute load as a spike. Previously a jump like
         // 2% -> 22% could raise caps even though the device was practically idle,
         // causing constant cap/log churn in normal screen-on usage.
-        let spike_floor = self.up_util.saturating_sub(20);
+        let spike_floor = t.up_util.saturating_sub(20);
         let spike_allowed = util >= spike_floor;
-        if util >= self.high_jump4 || (spike_allowed && delta >= self.spike_delta4) {
+        if util >= t.high_jump4 || (spike_allowed && delta >= t.spike_delta4) {
             jump_up = 4;
-        } else if util >= self.high_jump2 || (spike_allowed && delta >= self.spike_delta2) {
+        } else if util >= t.high_jump2 || (spike_allowed && delta >= t.spike_delta2) {
             jump_up = 2;
-        } else if util >= self.up_util {
+        } else if util >= t.up_util {
             jump_up = 1;
         }
 
-        if jump_up > 0 && self.idx + 1 < self.freqs.len() {
-            let new_idx = (self.idx + jump_up).min(self.freqs.len() - 1);
+        if jump_up > 0 && self.idx < self.max_index {
+            let new_idx = (self.idx + jump_up).min(self.max_index);
             if new_idx != self.idx {
                 self.idx = new_idx;
                 self.max_step_up_next_apply = jump_up;
@@ -158,17 +275,17 @@ impl Domain {
         }
 
         if now >= self.hold_until {
-            if util <= self.down_util2 {
+            if util <= t.down_util2 {
                 self.low_accum += dt;
-                if self.low_accum >= self.down_after2 {
+                if self.low_accum >= Duration::from_millis(t.down_after_ms[1]) {
                     self.low_accum = Duration::ZERO;
                     if self.idx > self.base_index {
                         self.idx -= 1;
                     }
                 }
-            } else if util <= self.down_util1 {
+            } else if util <= t.down_util1 {
                 self.low_accum += dt;
-                if self.low_accum >= self.down_after1 {
+                if self.low_accum >= Duration::from_millis(t.down_after_ms[0]) {
                     self.low_accum = Duration::ZERO;
                     if self.idx > self.base_index {
                         self.idx -= 1;
@@ -182,27 +299,13 @@ impl Domain {
         self.idx != old_idx
     }
 
-    fn max_step_down_for_zone(zone: TempZone) -> usize {
-        match zone {
-            TempZone::B58 => 4,
-            TempZone::B57 => 3,
-            TempZone::B56 | TempZone::B55 => 2,
-            _ => 1,
-        }
-    }
-
-    pub fn apply(
-        &mut self,
-        zone: TempZone,
-        cache: &mut HashMap<PathBuf, u64>,
-        force_check: bool,
-    ) -> io::Result<bool> {
-        let reduction = zone.reduction_percent();
-        let thermal_cap = if reduction == 0 {
+    /// Compute the cap for this iteration (thermal limit + step limits) without touching sysfs.
+    /// Returns true if the planned cap differs from the previous one.
+    pub fn plan_cap(&mut self, limit: ThermalLimit) -> bool {
+        let thermal_cap = if limit.keep_permille >= 1000 {
             self.max_freq
         } else {
-            let keep = 100u64 - reduction as u64;
-            (self.max_freq.saturating_mul(keep)) / 100u64
+            (self.max_freq.saturating_mul(limit.keep_permille as u64)) / 1000u64
         };
 
         let desired_freq = self.freqs[self.idx];
@@ -217,7 +320,7 @@ impl Domain {
         self.max_step_up_next_apply = 1;
 
         if target_idx + 1 <= self.last_applied_idx {
-            let max_down = Self::max_step_down_for_zone(zone);
+            let max_down = limit.max_step_down;
             let min_allowed = self.last_applied_idx.saturating_sub(max_down);
             if target_idx < min_allowed {
                 target_idx = min_allowed;
@@ -225,21 +328,29 @@ impl Domain {
         }
 
         let target_freq = self.freqs[target_idx];
+        let changed = target_idx != self.last_applied_idx || target_freq != self.last_applied_freq;
+        // Keep routine cap changes quiet. Printing every small cap adjustment
+        // wakes logcat and made the daemon look permanently busy. Important
+        // mode/state transitions are still logged from main.rs.
+        self.last_applied_idx = target_idx;
+        self.last_applied_freq = target_freq;
+        changed
+    }
 
-        // IMPORTANT: protect from min > max during throttling / game-min
-        let eff_min = self.min_freq.min(target_freq);
-
-        let _ = sysfs::write_u64_if_needed(&self.min_path, eff_min, cache, force_check)?;
-        let wrote_max = sysfs::write_u64_if_needed(&self.max_path, target_freq, cache, force_check)?;
+    /// Current planned max cap.
+    pub fn cap_freq(&self) -> u64 {
+        self.last_applied_freq
+    }
 
-        if target_idx != self.last_applied_idx || target_freq != self.last_applied_freq || (force_check && wrote_max) {
-            self.last_applied_idx = target_idx;
-            self.last_applied_freq = target_freq;
-            // Keep routine cap changes quiet. Printing every small cap adjustment
-            // wakes logcat and made the daemon look permanently busy. Important
-            // mode/state transitions are still logged from main.rs.
-        }
+    /// Effective min for the planned cap.
+    pub fn effective_min(&self) -> u64 {
+        // IMPORTANT: protect from min > max during throttling / game-min
+        self.min_freq.min(self.last_applied_freq)
+    }
 
-        Ok(wrote_max)
+    /// Write the planned min/max to sysfs. Returns true if max was written.
+    pub fn write_cap(&self, cache: &mut HashMap<PathBuf, u64>, force_check: bool) -> io::Result<bool> {
+        let _ = sysfs::write_u64_if_needed(&self.min_path, self.effective_min(), cache, force_check)?;
+        sysfs::write_u64_if_needed(&self.max_path, self.last_applied_freq, cache, force_check)
     }
 }
@@ -1,12 +1,93 @@
 
-use std::{collections::HashMap, path::{Path, PathBuf}, process::{Command, Stdio}};
+use std::{collections::HashMap, path::PathBuf, process::{Command, Stdio}, time::{Duration, Instant}};
 
-use crate::{config, sysfs};
+use serde::Serialize;
+
+use crate::{device, sysfs, user_config::FanCurve};
+
+/// First retry delay after a failed write or readback; doubles up to `RETRY_MAX`.
+const RETRY_MIN: Duration = Duration::from_secs(1);
+const RETRY_MAX: Duration = Duration::from_secs(60);
+/// Failed checks in a row before the fan is reported as not responding.
+const NOTIFY_AFTER_FAULTS: u32 = 3;
+
+/// Level of one fan curve with per-level hysteresis and dwell.
+pub struct CurveTracker {
+    level: u8,
+    since: Instant,
+}
+
+impl CurveTracker {
+    pub fn new(now: Instant) -> Self {
+        Self { level: 0, since: now }
+    }
+
+    /// Steps up immediately to the highest level whose `up_c` is reached. Steps down only once
+    /// the temperature is below the current level's `down_c` and its dwell has passed, then to
+    /// the highest level whose `down_c` still holds. Unknown temperature => 0.
+    pub fn update(&mut self, curve: &FanCurve, temp_mc: Option<i32>, now: Instant) -> u8 {
+        let Some(t) = temp_mc else {
+            self.level = 0;
+            self.since = now;
+            return 0;
+        };
+        let pts = &curve.levels;
+        // A reloaded curve may have fewer levels.
+        self.level = self.level.min(pts.len() as u8);
+        let up = pts.iter().take_while(|p| t >= p.up_mc()).count() as u8;
+
+        if up > self.level {
+            self.level = up;
+            self.since = now;
+        } else if self.level > 0 {
+            let cur = &pts[self.level as usize - 1];
+            let dwell = Duration::from_secs_f32(cur.dwell_s);
+            if t < cur.down_mc() && now.duration_since(self.since) >= dwell {
+                let hold = pts[..self.level as usize - 1].iter().take_while(|p| t >= p.down_mc()).count() as u8;
+                self.level = hold.max(up);
+                self.since = now;
+            }
+        }
+        self.level
+    }
+}
+
+/// Actuation health, exposed in `/api/state`.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct FanStatus {
+    pub level: u8,
+    /// The last readback matched the level we drive.
+    pub verified: bool,
+    pub faults: u32,
+    /// Failed checks in a row; 0 once the fan reads back fine.
+    pub consecutive_faults: u32,
+    /// Times another component (Nubia parts, a vendor service) changed the fan under us.
+    pub external_resets: u32,
+    pub last_error: Option<String>,
+    pub last_error_ago_s: Option<u64>,
+    pub retry_in_s: Option<u64>,
+}
+
+#[derive(Default)]
+struct FanHealth {
+    verified: bool,
+    /// We wrote since the last readback; a mismatch is our failure, not an external reset.
+    pending: bool,
+    faults: u32,
+    consecutive: u32,
+    external_resets: u32,
+    last_error: Option<String>,
+    last_error_at: Option<Instant>,
+    retry_at: Option<Instant>,
+    backoff: Duration,
+    notified: bool,
+}
 
 pub struct Fan {
     enable_path: PathBuf,
     level_path: PathBuf,
     level: u8, // 0..5
+    health: FanHealth,
 }
 
 impl Fan {
@@ -15,7 +96,10 @@ impl Fan {
         self.level
     }
 
-fn set_nubia_parts_fan_enable(enable: bool) {
+fn set_nubia_parts_fan_enable(enable: bool) -> Result<(), String> {
+    if sysfs::is_relocated() {
+        return Ok(());
+    }
     let val = if enable { "1" } else { "0" };
 
     // Try direct call first (daemon often runs as root).
@@ -28,86 +112,179 @@ fn set_nubia_parts_fan_enable(enable: bool) {
     if st.is_err() || !st.as_ref().ok().map(|x| x.success()).unwrap_or(false) {
         // Fallback via shell.
         let cmd = format!("settings put global nubia_parts_fan_enable {}", val);
-        let _ = Command::new("/system/bin/sh")
+        let st = Command::new("/system/bin/sh")
             .args(["-c", &cmd])
             .stdout(Stdio::null())
             .stderr(Stdio::null())
             .status();
+        match st {
+            Ok(x) if x.success() => {}
+            Ok(x) => return Err(format!("settings put nubia_parts_fan_enable {}: {}", val, x)),
+            Err(e) => return Err(format!("settings put nubia_parts_fan_enable {}: {}", val, e)),
+        }
     }
+    Ok(())
 }
     pub fn new() -> Option<Self> {
-        let enable_path = PathBuf::from(config::FAN_ENABLE);
-        let level_path = PathBuf::from(config::FAN_LEVEL);
+        let fan = &device::get().fan;
+        let enable_path = sysfs::path(&fan.enable);
+        let level_path = sysfs::path(&fan.level);
         if enable_path.exists() && level_path.exists() {
-            Some(Self { enable_path, level_path, level: 0 })
+            // Start from what the fan is doing now, so the first readback isn't a fault.
+            let level = match (sysfs::read_u64(&enable_path), sysfs::read_u64(&level_path)) {
+                (Some(1), Some(lv)) => lv.min(5) as u8,
+                _ => 0,
+            };
+            Some(Self { enable_path, level_path, level, health: FanHealth::default() })
         } else {
             None
         }
     }
 
     pub fn sysfs_ok() -> bool {
-        Path::new(config::FAN_ENABLE).exists() && Path::new(config::FAN_LEVEL).exists()
+        let fan = &device::get().fan;
+        sysfs::path(&fan.enable).exists() && sysfs::path(&fan.level).exists()
     }
 
-    // SoC curve (fan temps -10°C already built into thresholds externally, keep same curve)
-    fn level_from_soc_temp(temp_mc: i32) -> u8 {
-        if temp_mc < 50_000 { 0 }
-        else if temp_mc < 60_000 { 1 }
-        else if temp_mc < 70_000 { 2 }
-        else if temp_mc < 80_000 { 3 }
-        else if temp_mc < 90_000 { 4 }
-        else { 5 }
+    /// Write `self.level` (enable, level, enable again). `nubia` also flips the Nubia switch to
+    /// match: needed on 0 <-> non-zero and whenever something else may have touched it.
+    fn write_level(&mut self, cache: &mut HashMap<PathBuf, u64>, nubia: bool) -> Result<(), String> {
+        self.health.pending = true;
+        if self.level == 0 {
+            sysfs::write_u64_if_needed(&self.enable_path, 0, cache, true)
+                .map_err(|e| format!("fan_enable: {}", e))
+                .and_then(|_| if nubia { Self::set_nubia_parts_fan_enable(false) } else { Ok(()) })
+        } else {
+            let v = self.level as u64;
+            let enable = |cache: &mut HashMap<PathBuf, u64>| {
+                sysfs::write_u64_if_needed(&self.enable_path, 1, cache, true).map_err(|e| format!("fan_enable: {}", e))
+            };
+            enable(cache)
+                .and_then(|_| {
+                    sysfs::write_u64_if_needed(&self.level_path, v, cache, true)
+                        .map_err(|e| format!("fan_speed_level {}: {}", v, e))
+                })
+                .and_then(|_| enable(cache))
+                .and_then(|_| if nubia { Self::set_nubia_parts_fan_enable(true) } else { Ok(()) })
+        }
     }
 
-    // Battery curve when charging
-    fn level_from_battery_temp(temp_mc: i32) -> u8 {
-        if temp_mc < 15_000 { 0 }
-        else if temp_mc < 25_000 { 1 }
-        else if temp_mc < 30_000 { 2 }
-        else if temp_mc < 35_000 { 3 }
-        else if temp_mc < 42_000 { 4 }
-        else { 5 }
+    fn fault(&mut self, msg: String, now: Instant) {
+        let h = &mut self.health;
+        println!("FAN: fault ({})", msg);
+        h.verified = false;
+        h.faults += 1;
+        h.consecutive += 1;
+        h.last_error = Some(msg);
+        h.last_error_at = Some(now);
+        h.backoff = (h.backoff * 2).clamp(RETRY_MIN, RETRY_MAX);
+        h.retry_at = Some(now + h.backoff);
     }
 
     pub fn force_level(&mut self, cache: &mut HashMap<PathBuf, u64>, level: u8) {
         let prev = self.level;
-        let lvl = level.min(5);
-        self.level = lvl;
-
+        self.level = level.min(5);
+        if let Err(e) = self.write_level(cache, (prev == 0) != (self.level == 0)) {
+            self.fault(e, Instant::now());
+        }
+        // Not driving the fan any more: whatever happens to it next isn't a fault of ours.
+        self.health.verified = false;
+        self.health.pending = false;
+        self.health.retry_at = None;
         if self.level == 0 {
-            let _ = sysfs::write_u64_if_needed(&self.enable_path, 0, cache, true);
-            if prev != 0 { Self::set_nubia_parts_fan_enable(false); }
             println!("FAN: off");
+        } else {
+            println!("FAN: {}", self.level);
+        }
+    }
+
+    /// Read the fan back and compare with the level we drive. A mismatch after our own write is
+    /// a fault; one after a good readback is an external reset. Either way the level is rewritten
+    /// (bypassing the write cache) with exponential backoff.
+    pub fn verify(&mut self, cache: &mut HashMap<PathBuf, u64>, now: Instant) {
+        let want_en = (self.level > 0) as u64;
+        let en = sysfs::read_u64(&self.enable_path);
+        let lv = sysfs::read_u64(&self.level_path);
+        let ok = en == Some(want_en) && (self.level == 0 || lv == Some(self.level as u64));
+
+        let h = &mut self.health;
+        if ok {
+            if h.consecutive > 0 {
+                println!("FAN: readback ok again after {} failed checks", h.consecutive);
+            }
+            h.verified = true;
+            h.pending = false;
+            h.consecutive = 0;
+            h.backoff = Duration::ZERO;
+            h.retry_at = None;
+            return;
+        }
+        if h.retry_at.is_some_and(|t| now < t) {
             return;
         }
 
-        let v = self.level as u64;
-        let _ = sysfs::write_u64_if_needed(&self.enable_path, 1, cache, true);
-        let _ = sysfs::write_u64_if_needed(&self.level_path, v, cache, true);
-        let _ = sysfs::write_u64_if_needed(&self.enable_path, 1, cache, true);
-        if prev == 0 && self.level != 0 { Self::set_nubia_parts_fan_enable(true); }
-        println!("FAN: {}", self.level);
+        let show = |v: Option<u64>| v.map(|v| v.to_string()).unwrap_or_else(|| "?".to_string());
+        let mut msg = format!("readback en={} lvl={}, want {}", show(en), show(lv), self.level);
+        if h.verified && !h.pending {
+            h.external_resets += 1;
+            msg = format!("changed externally, {}", msg);
+        }
+        self.fault(msg, now);
+
+        cache.remove(&self.enable_path);
+        cache.remove(&self.level_path);
+        // Fan found off: go through the Nubia switch again as well.
+        if let Err(e) = self.write_level(cache, en == Some(0) && self.level != 0) {
+            println!("FAN: retry failed ({})", e);
+            self.health.last_error = Some(e);
+        }
     }
 
-    pub fn apply(
-        &mut self,
-        cache: &mut HashMap<PathBuf, u64>,
-        soc_temp_mc: i32,
-        batt_temp_mc: Option<i32>,
+    /// The watchdog wrote the fan during a stall. That is neither an external reset nor a fault:
+    /// put our level back (the Nubia switch included, it may have been turned on).
+    pub fn resync(&mut self, cache: &mut HashMap<PathBuf, u64>) {
+        self.health.verified = false;
+        cache.remove(&self.enable_path);
+        cache.remove(&self.level_path);
+        if let Err(e) = self.write_level(cache, true) {
+            self.fault(e, Instant::now());
+        }
+    }
+
+    /// Last error, once per run, after `NOTIFY_AFTER_FAULTS` failed checks in a row.
+    pub fn take_fault_notice(&mut self) -> Option<String> {
+        let h = &mut self.health;
+        if h.notified || h.consecutive < NOTIFY_AFTER_FAULTS {
+            return None;
+        }
+        h.notified = true;
+        h.last_error.clone()
+    }
+
+    pub fn status(&self, now: Instant) -> FanStatus {
+        let h = &self.health;
+        FanStatus {
+            level: self.level,
+            verified: h.verified,
+            faults: h.faults,
+            consecutive_faults: h.consecutive,
+            external_resets: h.external_resets,
+            last_error: h.last_error.clone(),
+            last_error_ago_s: h.last_error_at.map(|t| now.saturating_duration_since(t).as_secs()),
+            retry_in_s: h.retry_at.map(|t| t.saturating_duration_since(now).as_secs()),
+        }
+    }
+
+    /// Curve/charging/game target level before smoothing.
+    pub fn target_level(
+        soc_level: u8,
+        batt_level: u8,
         screen_on: bool,
         charging: bool,
         game_mode: bool,
         game_fan_min_level: u8,
-    ) {
-        let prev = self.level;
-        let soc_level = if soc_temp_mc >= 0 {
-            Self::level_from_soc_temp(soc_temp_mc)
-        } else {
-            0
-        };
-
+    ) -> u8 {
         let mut target = if charging {
-            let batt_level = batt_temp_mc.map(Self::level_from_battery_temp).unwrap_or(0);
             soc_level.max(batt_level)
         } else if screen_on {
             soc_level
@@ -127,30 +304,102 @@ fn set_nubia_parts_fan_enable(enable: bool) {
             target = target.max(base);
         }
 
-        // smooth +/-1
-        let next = if target > self.level {
-            self.level + 1
-        } else if target < self.level {
-            self.level - 1
+        target
+    }
+
+    /// Move at most one level per iteration towards `target`.
+    pub fn smooth_step(cur: u8, target: u8) -> u8 {
+        if target > cur {
+            cur + 1
+        } else if target < cur {
+            cur - 1
         } else {
-            self.level
-        };
+            cur
+        }
+    }
+
+    /// Drive the fan to `level` (as decided by control.rs). Writes only on change; `verify`
+    /// catches anything that didn't stick.
+    pub fn apply(&mut self, cache: &mut HashMap<PathBuf, u64>, level: u8) {
+        let prev = self.level;
+        let next = level.min(5);
 
         if next == self.level { return; }
         self.level = next;
+        if let Err(e) = self.write_level(cache, (prev == 0) != (next == 0)) {
+            self.fault(e, Instant::now());
+        }
 
         if self.level == 0 {
-            let _ = sysfs::write_u64_if_needed(&self.enable_path, 0, cache, true);
-            if prev != 0 { Self::set_nubia_parts_fan_enable(false); }
             println!("FAN: off");
-            return;
+        } else {
+            println!("FAN: {}", self.level);
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user_config::FanCurvesConfig;
+
+    const S: Duration = Duration::from_secs(1);
+
+    fn mc(c: i32) -> Option<i32> {
+        Some(c * 1000)
+    }
+
+    #[test]
+    fn steps_up_at_once_to_highest_level() {
+        let curve = FanCurvesConfig::default().soc;
+        let t0 = Instant::now();
+        let mut tr = CurveTracker::new(t0);
+        assert_eq!(tr.update(&curve, mc(49), t0), 0);
+        assert_eq!(tr.update(&curve, mc(50), t0), 1);
+        assert_eq!(tr.update(&curve, mc(75), t0 + S), 3);
+    }
+
+    #[test]
+    fn steps_down_only_past_hysteresis_and_dwell() {
+        let curve = FanCurvesConfig::default().soc;
+        let t0 = Instant::now();
+        let mut tr = CurveTracker::new(t0);
+        assert_eq!(tr.update(&curve, mc(62), t0), 2);
+        // Below up_c (60) but above down_c (57): held.
+        assert_eq!(tr.update(&curve, mc(58), t0 + 20 * S), 2);
+        // Below down_c, but the 10 s dwell since entering the level hasn't passed.
+        let mut tr = CurveTracker::new(t0);
+        tr.update(&curve, mc(62), t0);
+        assert_eq!(tr.update(&curve, mc(55), t0 + 5 * S), 2);
+        assert_eq!(tr.update(&curve, mc(55), t0 + 10 * S), 1);
+        // Straight to the highest level whose down_c still holds, 0 here.
+        let mut tr = CurveTracker::new(t0);
+        tr.update(&curve, mc(85), t0);
+        assert_eq!(tr.update(&curve, mc(40), t0 + 10 * S), 0);
+    }
+
+    #[test]
+    fn dwell_restarts_on_each_step() {
+        let curve = FanCurvesConfig::default().soc;
+        let t0 = Instant::now();
+        let mut tr = CurveTracker::new(t0);
+        tr.update(&curve, mc(72), t0);
+        assert_eq!(tr.update(&curve, mc(65), t0 + 10 * S), 2);
+        // Level 2 was entered at 10 s: it may be left at 20 s, not before.
+        assert_eq!(tr.update(&curve, mc(50), t0 + 15 * S), 2);
+        assert_eq!(tr.update(&curve, mc(50), t0 + 20 * S), 1);
+    }
+
+    #[test]
+    fn unknown_temperature_and_shorter_curve() {
+        let mut curve = FanCurvesConfig::default().soc;
+        let t0 = Instant::now();
+        let mut tr = CurveTracker::new(t0);
+        tr.update(&curve, mc(95), t0);
+        assert_eq!(tr.update(&curve, None, t0 + S), 0);
 
-        let lvl = self.level as u64;
-        let _ = sysfs::write_u64_if_needed(&self.enable_path, 1, cache, true);
-        let _ = sysfs::write_u64_if_needed(&self.level_path, lvl, cache, true);
-        let _ = sysfs::write_u64_if_needed(&self.enable_path, 1, cache, true);
-        if prev == 0 && self.level != 0 { Self::set_nubia_parts_fan_enable(true); }
-        println!("FAN: {}", self.level);
+        tr.update(&curve, mc(95), t0 + 2 * S);
+        curve.levels.truncate(2);
+        assert_eq!(tr.update(&curve, mc(95), t0 + 3 * S), 2);
     }
 }
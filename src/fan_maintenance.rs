@@ -0,0 +1,179 @@
+//! Fan maintenance (dust-clearing) cycle.
+//!
+//! Every `interval_h`, once the phone is charging with the screen off and the battery cool, the
+//! cooler runs at level 5 for `max_level_s` and then steps down through 4..1 (`step_s` each).
+//! The time of the last completed run is persisted, so the interval holds across reboots.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    sysfs,
+    user_config::{ensure_parent_dir, FanMaintenanceConfig, FAN_MAINTENANCE_PATH},
+};
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct Record {
+    /// Unix time (s) of the last completed run.
+    last_run_s: Option<u64>,
+}
+
+/// Exposed in `/api/state`.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct FanMaintenanceStatus {
+    pub enabled: bool,
+    pub running: bool,
+    /// Level the run is at.
+    pub level: Option<u8>,
+    pub last_run_s: Option<u64>,
+    /// Hours until the next run is due; 0 = due, waiting for the conditions.
+    pub due_in_h: Option<f32>,
+    /// Why a due run can't start (or was aborted) right now.
+    pub waiting_for: Option<&'static str>,
+    pub last_result: Option<String>,
+}
+
+fn unix_s() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn load_record(path: &Path) -> Record {
+    let Ok(s) = fs::read_to_string(path) else { return Record::default() };
+    serde_json::from_str(&s).unwrap_or_else(|e| {
+        println!("FAN: maintenance record {} unreadable ({})", path.display(), e);
+        Record::default()
+    })
+}
+
+fn save_record(path: &Path, rec: &Record) {
+    let res = ensure_parent_dir(path).and_then(|_| {
+        let data = serde_json::to_vec_pretty(rec).map_err(std::io::Error::other)?;
+        fs::write(path, data)
+    });
+    if let Err(e) = res {
+        println!("FAN: maintenance record save failed ({})", e);
+    }
+}
+
+/// What keeps a run from starting (or going on), if anything. `busy`: game, critical safeguard
+/// or manual override.
+pub fn blocker(
+    cfg: &FanMaintenanceConfig,
+    charging: bool,
+    screen_on: bool,
+    batt_temp_mc: Option<i32>,
+    busy: bool,
+) -> Option<&'static str> {
+    if !charging {
+        Some("charging")
+    } else if screen_on {
+        Some("screen off")
+    } else if batt_temp_mc.is_none_or(|t| t as f32 / 1000.0 > cfg.max_batt_temp_c) {
+        Some("cool battery")
+    } else if busy {
+        Some("fan free")
+    } else {
+        None
+    }
+}
+
+pub struct FanMaintenance {
+    file: PathBuf,
+    rec: Record,
+    started: Option<Instant>,
+    level: Option<u8>,
+    waiting_for: Option<&'static str>,
+    last_result: Option<String>,
+}
+
+impl FanMaintenance {
+    pub fn new() -> Self {
+        let file = sysfs::path(FAN_MAINTENANCE_PATH);
+        let rec = load_record(&file);
+        Self { file, rec, started: None, level: None, waiting_for: None, last_result: None }
+    }
+
+    fn due_in_s(&self, cfg: &FanMaintenanceConfig) -> u64 {
+        let interval = cfg.interval_h as u64 * 3600;
+        match self.rec.last_run_s {
+            Some(last) => (last + interval).saturating_sub(unix_s()),
+            None => 0,
+        }
+    }
+
+    fn finish(&mut self, result: String) {
+        println!("FAN: maintenance {}", result);
+        self.started = None;
+        self.level = None;
+        self.last_result = Some(result);
+    }
+
+    /// Level to force while a run is on, None otherwise.
+    pub fn step(&mut self, cfg: &FanMaintenanceConfig, blocker: Option<&'static str>, now: Instant) -> Option<u8> {
+        self.waiting_for = None;
+        let Some(start) = self.started else {
+            if !cfg.enabled || self.due_in_s(cfg) > 0 {
+                return None;
+            }
+            if blocker.is_some() {
+                self.waiting_for = blocker;
+                return None;
+            }
+            println!("FAN: maintenance run started");
+            self.started = Some(now);
+            self.level = Some(5);
+            return self.level;
+        };
+
+        if !cfg.enabled {
+            self.finish("aborted (disabled)".to_string());
+            return None;
+        }
+        if let Some(why) = blocker {
+            self.waiting_for = Some(why);
+            self.finish(format!("aborted (needs {})", why));
+            return None;
+        }
+
+        let elapsed = now.duration_since(start);
+        let full = Duration::from_secs(cfg.max_level_s as u64);
+        let level = if elapsed < full {
+            5
+        } else {
+            let steps = match cfg.step_s {
+                0 => 4,
+                s => ((elapsed - full).as_secs() / s as u64).min(4),
+            };
+            4 - steps as u8
+        };
+        if level == 0 {
+            self.rec.last_run_s = Some(unix_s());
+            save_record(&self.file, &self.rec);
+            self.finish("completed".to_string());
+            return None;
+        }
+        self.level = Some(level);
+        self.level
+    }
+
+    pub fn running(&self) -> bool {
+        self.started.is_some()
+    }
+
+    pub fn status(&self, cfg: &FanMaintenanceConfig) -> FanMaintenanceStatus {
+        FanMaintenanceStatus {
+            enabled: cfg.enabled,
+            running: self.started.is_some(),
+            level: self.level,
+            last_run_s: self.rec.last_run_s,
+            due_in_h: cfg.enabled.then(|| self.due_in_s(cfg) as f32 / 3600.0),
+            waiting_for: self.waiting_for,
+            last_result: self.last_result.clone(),
+        }
+    }
+}
@@ -0,0 +1,217 @@
+//! Closed-loop fan controller (`fan_control.mode = "setpoint"`).
+//!
+//! A PI loop on the fan level (0..5) holds the SoC or battery temperature at a target instead of
+//! following fixed curves. While it runs it learns how fast each level moves the temperature on
+//! this unit; above the target it stays at the lowest level already known to cool fast enough.
+
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::user_config::FanSetpointConfig;
+
+/// Gaps longer than this (idle/suspend) are neither integrated nor learned from.
+const MAX_DT: Duration = Duration::from_secs(30);
+/// Low-pass factor of the per-level rates (sysfs temps are quantized).
+const RATE_ALPHA: f32 = 0.1;
+/// Samples before a level's rate is trusted.
+const MIN_SAMPLES: u32 = 10;
+/// A level cools "fast enough" if it closes the current error within this many minutes.
+const HORIZON_MIN: f32 = 2.0;
+
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct LevelRate {
+    /// Temperature rate (°C/min) while at this level; negative = cooling.
+    pub c_per_min: f32,
+    pub samples: u32,
+}
+
+/// One controller update, exposed in decisions / `/api/state`.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct FanSetpointStatus {
+    pub target_mc: i32,
+    pub error_mc: i32,
+    pub p: f32,
+    pub i: f32,
+    /// Level asked by the PI terms.
+    pub pi_level: u8,
+    /// Level requested after the learned-rate choice and the hold time.
+    pub level: u8,
+    /// Learned rate per level 0..5.
+    pub rates: [LevelRate; 6],
+}
+
+#[derive(Default)]
+pub struct FanSetpoint {
+    /// Accumulated error (°C·s), clamped so the I term stays within 0..max_level.
+    integral: f32,
+    level: u8,
+    level_since: Option<Instant>,
+    last_temp_c: Option<f32>,
+    rates: [LevelRate; 6],
+    last: Option<FanSetpointStatus>,
+}
+
+impl FanSetpoint {
+    /// Drop the loop state; learned rates are kept (they describe the hardware).
+    pub fn reset(&mut self) {
+        let rates = self.rates;
+        *self = Self { rates, ..Self::default() };
+    }
+
+    /// Last output, used when the sensor can't be read this iteration.
+    pub fn last(&self) -> Option<FanSetpointStatus> {
+        self.last
+    }
+
+    /// `applied` is the level the fan actually ran at since the previous update.
+    pub fn update(
+        &mut self,
+        temp_mc: i32,
+        applied: u8,
+        cfg: &FanSetpointConfig,
+        now: Instant,
+        dt: Duration,
+    ) -> FanSetpointStatus {
+        let temp_c = temp_mc as f32 / 1000.0;
+        let dt_s = dt.as_secs_f32();
+
+        if let Some(prev) = self.last_temp_c {
+            if dt <= MAX_DT && dt_s > 0.0 {
+                let rate = (temp_c - prev) / dt_s * 60.0;
+                let r = &mut self.rates[applied.min(5) as usize];
+                r.c_per_min = if r.samples == 0 { rate } else { r.c_per_min + RATE_ALPHA * (rate - r.c_per_min) };
+                r.samples = r.samples.saturating_add(1);
+            }
+        }
+        self.last_temp_c = Some(temp_c);
+
+        let max = cfg.max_level as f32;
+        let err = temp_c - cfg.target_c;
+        let p = cfg.kp * err;
+
+        // Anti-windup as in pid.rs; nothing accumulates inside the deadband.
+        let prev_integral = self.integral;
+        let unsat = p + cfg.ki * self.integral;
+        let pushing_high = unsat >= max && err > 0.0;
+        let pushing_low = unsat <= 0.0 && err < 0.0;
+        if err.abs() > cfg.deadband_c && !pushing_high && !pushing_low && dt <= MAX_DT {
+            self.integral += err * dt_s;
+        }
+        self.integral = if cfg.ki > 0.0 { self.integral.clamp(0.0, max / cfg.ki) } else { 0.0 };
+        let i = cfg.ki * self.integral;
+        let pi_level = (p + i).clamp(0.0, max).round() as u8;
+
+        // Lowest level already known to close the error within the horizon.
+        let mut want = pi_level;
+        if cfg.learn && err > cfg.deadband_c {
+            let needed = -err / HORIZON_MIN;
+            if let Some(l) = (0..pi_level).find(|&l| {
+                let r = self.rates[l as usize];
+                r.samples >= MIN_SAMPLES && r.c_per_min <= needed
+            }) {
+                want = l;
+                // Held below the PI level on purpose: don't wind up meanwhile.
+                self.integral = prev_integral;
+            }
+        }
+
+        let held = self
+            .level_since
+            .is_some_and(|t| now.duration_since(t) < Duration::from_secs_f32(cfg.min_hold_s));
+        if want > self.level || (want < self.level && !held) || self.level_since.is_none() {
+            self.level = want;
+            self.level_since = Some(now);
+        }
+
+        let st = FanSetpointStatus {
+            target_mc: (cfg.target_c * 1000.0).round() as i32,
+            error_mc: (err * 1000.0).round() as i32,
+            p,
+            i,
+            pi_level,
+            level: self.level,
+            rates: self.rates,
+        };
+        self.last = Some(st);
+        st
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP: Duration = Duration::from_secs(3);
+
+    fn cfg() -> FanSetpointConfig {
+        FanSetpointConfig { min_hold_s: 20.0, ..FanSetpointConfig::default() }
+    }
+
+    #[test]
+    fn follows_error_and_holds_before_stepping_down() {
+        let cfg = cfg();
+        let t0 = Instant::now();
+        let mut sp = FanSetpoint::default();
+        assert_eq!(sp.update(55_000, 0, &cfg, t0, STEP).level, 0);
+        // 10 °C over the 62 °C target: P alone asks for 3.
+        let st = sp.update(72_000, 0, &cfg, t0 + STEP, STEP);
+        assert_eq!((st.pi_level, st.level), (3, 3));
+        // Back under the target: held for min_hold_s, then released.
+        assert_eq!(sp.update(60_000, 3, &cfg, t0 + 2 * STEP, STEP).level, 3);
+        let st = sp.update(60_000, 3, &cfg, t0 + Duration::from_secs(24), STEP);
+        assert_eq!(st.level, st.pi_level);
+        assert!(st.level < 3);
+    }
+
+    #[test]
+    fn integral_is_bounded_and_skips_deadband() {
+        let cfg = cfg();
+        let t0 = Instant::now();
+        let mut sp = FanSetpoint::default();
+        // Inside the ±1 °C deadband nothing accumulates.
+        for k in 0..100 {
+            sp.update(62_800, 0, &cfg, t0 + STEP * k, STEP);
+        }
+        assert_eq!(sp.last().unwrap().i, 0.0);
+        // Far above for a long time: pinned at max, the I term never exceeds it.
+        for k in 100..1000 {
+            sp.update(80_000, 5, &cfg, t0 + STEP * k, STEP);
+        }
+        let st = sp.last().unwrap();
+        assert_eq!(st.level, 5);
+        assert!(st.i <= cfg.max_level as f32);
+    }
+
+    #[test]
+    fn uses_lowest_level_known_to_cool_fast_enough() {
+        let cfg = cfg();
+        let t0 = Instant::now();
+        let mut sp = FanSetpoint::default();
+        // Level 1 cools 6 °C/min (0.3 °C per 3 s) on this unit.
+        let mut temp = 80_000;
+        for k in 0..20 {
+            sp.update(temp, 1, &cfg, t0 + STEP * k, STEP);
+            temp -= 300;
+        }
+        assert!(sp.rates[1].samples >= MIN_SAMPLES);
+        assert!((sp.rates[1].c_per_min + 6.0).abs() < 0.01);
+
+        sp.reset();
+        assert_eq!(sp.rates[1].samples, 19, "reset must keep learned rates");
+        // 10 °C over: PI asks for 3, level 1 closes that within the 2 min horizon.
+        let st = sp.update(72_000, 0, &cfg, t0 + STEP * 30, STEP);
+        assert_eq!(st.pi_level, 3);
+        assert_eq!(st.level, 1);
+    }
+
+    #[test]
+    fn long_gaps_are_not_learned() {
+        let cfg = cfg();
+        let t0 = Instant::now();
+        let mut sp = FanSetpoint::default();
+        sp.update(70_000, 2, &cfg, t0, STEP);
+        sp.update(60_000, 2, &cfg, t0 + Duration::from_secs(600), Duration::from_secs(600));
+        assert_eq!(sp.rates[2].samples, 0);
+    }
+}
@@ -1,5 +1,7 @@
 use std::{collections::HashSet, fs, path::Path, process::Command};
 
+use crate::sysfs;
+
 const TOP_APP_PROCS: &[&str] = &[
     "/dev/cpuset/top-app/cgroup.procs",
     "/dev/cpuset/top-app/tasks",
@@ -50,7 +52,7 @@ fn looks_like_package(s: &str) -> bool {
 }
 
 fn read_cmdline(pid: u32) -> Option<String> {
-    let data = fs::read(format!("/proc/{pid}/cmdline")).ok()?;
+    let data = fs::read(sysfs::path(format!("/proc/{pid}/cmdline"))).ok()?;
     let first = data.split(|&b| b == 0).next().unwrap_or(&[]);
     if first.is_empty() {
         return None;
@@ -59,7 +61,7 @@ fn read_cmdline(pid: u32) -> Option<String> {
 }
 
 fn tgid_for_tid(tid: u32) -> u32 {
-    let status = fs::read_to_string(format!("/proc/{tid}/status")).unwrap_or_default();
+    let status = fs::read_to_string(sysfs::path(format!("/proc/{tid}/status"))).unwrap_or_default();
     for line in status.lines() {
         if let Some(rest) = line.strip_prefix("Tgid:") {
             if let Ok(v) = rest.trim().parse::<u32>() {
@@ -71,7 +73,7 @@ fn tgid_for_tid(tid: u32) -> u32 {
 }
 
 fn owner_pid_from_cgroup(pid: u32) -> Option<u32> {
-    let cg = fs::read_to_string(format!("/proc/{pid}/cgroup")).ok()?;
+    let cg = fs::read_to_string(sysfs::path(format!("/proc/{pid}/cgroup"))).ok()?;
     for part in cg.split('/') {
         if let Some(rest) = part.strip_prefix("pid_") {
             let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
@@ -103,7 +105,7 @@ fn candidate_package_for_id(id: u32) -> Option<String> {
     None
 }
 
-fn read_top_app_ids(path: &str) -> Vec<u32> {
+fn read_top_app_ids(path: &Path) -> Vec<u32> {
     fs::read_to_string(path)
         .unwrap_or_default()
         .split_whitespace()
@@ -115,10 +117,11 @@ fn get_top_app_from_cpuset() -> Option<String> {
     let mut seen = HashSet::new();
 
     for path in TOP_APP_PROCS {
-        if !Path::new(path).exists() {
+        let path = sysfs::path(path);
+        if !path.exists() {
             continue;
         }
-        for id in read_top_app_ids(path) {
+        for id in read_top_app_ids(&path) {
             if !seen.insert(id) {
                 continue;
             }
@@ -134,6 +137,9 @@ fn get_top_app_from_cpuset() -> Option<String> {
 }
 
 fn sh_out(cmd: &str) -> Option<String> {
+    if sysfs::is_relocated() {
+        return None;
+    }
     let out = Command::new("/system/bin/sh")
         .args(["-c", cmd])
         .output()
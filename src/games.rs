@@ -9,7 +9,10 @@ use std::{
     process::{Command, Stdio},
 };
 
-use crate::config;
+use crate::{
+    config, sysfs,
+    user_config::{DomainTuningConfig, ExternalLedSetting, FanLedSetting, ProfileFanConfig, ThermalCurveConfig},
+};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TriggerSideConfig {
@@ -150,11 +153,66 @@ impl Default for GameEntry {
     }
 }
 
+/// Non-game app (camera, navigation, video, ...): overrides applied while it is in the
+/// foreground, between the active profile and the preset. No game mode, no game fan minimum.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct AppEntry {
+    pub package: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub domain_tuning: Option<DomainTuningConfig>,
+
+    /// scaling_governor per cluster label.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub governors: BTreeMap<String, String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thermal_curve: Option<ThermalCurveConfig>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fan: Option<ProfileFanConfig>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fan_led: Option<FanLedSetting>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_led: Option<ExternalLedSetting>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sleep_scale: Option<f32>,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+impl AppEntry {
+    /// Clamp values; a knob that doesn't validate is dropped (logged) instead of failing the file.
+    fn normalize(&mut self) {
+        if let Some(t) = &mut self.domain_tuning {
+            t.validate_and_normalize();
+        }
+        if let Some(c) = &mut self.thermal_curve {
+            if let Err(e) = c.validate_and_normalize() {
+                println!("GAMES: app {}: thermal_curve dropped ({})", self.package, e);
+                self.thermal_curve = None;
+            }
+        }
+        if let Some(f) = &mut self.fan {
+            f.validate_and_normalize();
+        }
+        self.sleep_scale = self.sleep_scale.filter(|v| v.is_finite()).map(|v| v.clamp(0.25, 4.0));
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct GamesFile {
     #[serde(default)]
     pub games: Vec<GameEntry>,
 
+    /// Per-app rules for non-game packages.
+    #[serde(default)]
+    pub apps: Vec<AppEntry>,
+
     #[serde(flatten)]
     pub extra: BTreeMap<String, Value>,
 }
@@ -163,6 +221,7 @@ impl Default for GamesFile {
     fn default() -> Self {
         Self {
             games: Vec::new(),
+            apps: Vec::new(),
             extra: BTreeMap::new(),
         }
     }
@@ -179,6 +238,7 @@ pub struct GamesRuntime {
     pub triggers: HashMap<String, TriggersConfig>,
     pub disable_thermal_limit: HashMap<String, bool>,
     pub split_charge: HashMap<String, SplitChargeConfig>,
+    pub apps: HashMap<String, AppEntry>,
 }
 
 impl GamesRuntime {
@@ -217,6 +277,14 @@ impl GamesRuntime {
         driver_pkgs.dedup();
         let driver_string = driver_pkgs.join(",");
 
+        // A package listed as a game is handled as one; its app rule is ignored.
+        let apps: HashMap<String, AppEntry> = file
+            .apps
+            .iter()
+            .filter(|a| !pkg_set.contains(&a.package))
+            .map(|a| (a.package.clone(), a.clone()))
+            .collect();
+
         Self {
             file,
             pkg_set,
@@ -227,9 +295,14 @@ impl GamesRuntime {
             triggers,
             disable_thermal_limit,
             split_charge,
+            apps,
         }
     }
 
+    pub fn app_for(&self, pkg: &str) -> Option<&AppEntry> {
+        self.apps.get(pkg)
+    }
+
     pub fn is_game(&self, pkg: &str) -> bool {
         self.pkg_set.contains(pkg)
     }
@@ -259,14 +332,14 @@ impl GamesRuntime {
     }
 }
 
-fn sanitize_pkg(s: &str) -> String {
+pub fn sanitize_pkg(s: &str) -> String {
     let s = s.trim();
     s.trim_matches(|c: char| !(c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-'))
         .to_string()
 }
 
 impl GamesFile {
-    /// Normalize entries:
+    /// Normalize entries (games and apps):
     /// - sanitize package names
     /// - drop empty package entries
     /// - de-duplicate by package, keeping the LAST occurrence
@@ -295,6 +368,19 @@ impl GamesFile {
         }
 
         self.games = out;
+
+        // Apps: same rules (last occurrence wins), plus value clamping.
+        let mut apps: Vec<AppEntry> = Vec::new();
+        for mut a in std::mem::take(&mut self.apps) {
+            a.package = sanitize_pkg(&a.package);
+            if a.package.is_empty() {
+                continue;
+            }
+            apps.retain(|x| x.package != a.package);
+            a.normalize();
+            apps.push(a);
+        }
+        self.apps = apps;
     }
 }
 
@@ -343,6 +429,9 @@ fn escape_for_double_quotes(s: &str) -> String {
 /// Apply Android updatable game driver opt-in list.
 /// Uses `settings put global updatable_driver_production_opt_in_apps "<csv>"`.
 pub fn apply_updatable_driver_apps(csv: &str) {
+    if sysfs::is_relocated() {
+        return;
+    }
     // Direct call first (daemon often runs as root).
     let st = Command::new("settings")
         .args([
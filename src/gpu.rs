@@ -56,8 +56,8 @@ pub fn load_gpu_freqs_dynamic(fallback: &'static [u64]) -> (&'static [u64], Stri
     ];
 
     for path in CANDIDATES {
-        let p = Path::new(path);
-        if let Some(s) = sysfs::read_to_string(p) {
+        let p = sysfs::path(path);
+        if let Some(s) = sysfs::read_to_string(&p) {
             if let Some(vals) = parse_gpu_freqs_from_str(&s) {
                 let src = format!("dynamic from {}", path);
                 let leaked: &'static [u64] = Box::leak(vals.into_boxed_slice());
@@ -1,30 +1,31 @@
-use crate::user_config::{
-    ExternalLedColor, ExternalLedMode, ExternalLedSetting, FanLedColor, FanLedMode, FanLedSetting,
+use crate::{
+    device, sysfs,
+    user_config::{
+        ExternalLedColor, ExternalLedMode, ExternalLedSetting, FanLedColor, FanLedMode, FanLedSetting,
+    },
 };
 use std::{
     fs,
     io,
-    path::Path,
+    path::{Path, PathBuf},
     thread,
     sync::Mutex,
     time::Duration,
 };
 
-const BASE: &str = "/sys/class/leds/aw22xxx_led";
-
-fn p(rel: &str) -> String {
-    format!("{}/{}", BASE, rel)
+fn p(rel: &str) -> PathBuf {
+    sysfs::path(format!("{}/{}", device::get().leds.base, rel))
 }
 
-fn write_str(path: &str, val: &str) -> io::Result<()> {
+fn write_str(path: &Path, val: &str) -> io::Result<()> {
     fs::write(path, format!("{}\n", val).as_bytes())
 }
 
-fn read_str(path: &str) -> Option<String> {
+fn read_str(path: &Path) -> Option<String> {
     fs::read_to_string(path).ok()
 }
 
-fn write_u64(path: &str, val: u64) -> io::Result<()> {
+fn write_u64(path: &Path, val: u64) -> io::Result<()> {
     write_str(path, &val.to_string())
 }
 
@@ -35,7 +36,7 @@ fn write_u64(path: &str, val: u64) -> io::Result<()> {
 /// writing it during early boot can lead to inconsistent behavior.
 fn ensure_external_init() {
     let hwen = p("hwen");
-    if Path::new(&hwen).exists() {
+    if hwen.exists() {
         let _ = write_u64(&hwen, 1);
     }
 }
@@ -1,9 +1,16 @@
 
+mod cli;
+mod clock;
 mod config;
 mod config_watch;
+mod control;
 mod cpu;
+mod critical;
+mod device;
 mod domain;
 mod fan;
+mod fan_maintenance;
+mod fan_setpoint;
 mod fmt;
 mod gamemode;
 mod games;
@@ -13,76 +20,75 @@ mod leds;
 mod mem;
 mod notify;
 mod notifications;
+mod pid;
 mod power;
 mod procwatch;
 mod profiles;
 mod screen;
+mod sensors;
 mod services;
+mod shutdown;
+mod sim;
 mod split_charge;
 mod swap;
 mod state;
 mod sysfs;
 mod tempzone;
 mod thermal;
+mod trace;
 mod triggers;
 mod user_config;
+mod watchdog;
 mod web;
 
 use std::{
     collections::HashMap,
-    path::{Path, PathBuf},
+    path::PathBuf,
     sync::{Arc, RwLock},
     time::{Duration, Instant},
 };
 
 use crate::{
     config::*,
-    cpu::{avg_util, cpu_utils_by_core, CpuStatSample},
-    domain::{base_index_from_ratio, mid_freq, Domain},
+    control::{Controller, GameFlags, Inputs},
+    critical::CriticalLevel,
+    cpu::{cpu_utils_by_core, CpuStatSample},
     fan::Fan,
+    fan_maintenance::FanMaintenance,
     fmt::{fmt_c, fmt_hz, fmt_khz},
     gamemode::get_foreground_package,
-    games::{apply_updatable_driver_apps, load_or_init as load_games_or_init},
+    games::{apply_updatable_driver_apps, load_or_init as load_games_or_init, AppEntry},
     gpu::{load_gpu_freqs_dynamic, read_gpu_util_any},
     leds::Leds,
     notify::{ensure_icon_on_disk, post_notification},
-    profiles::{select_active_mode_profile, select_base_led},
+    profiles::{active_schedule, daemon_notifications_allowed, select_active_mode_profile, select_base_led, RuleContext},
     power::ChargeProbe,
     procwatch::ProcWatch,
     screen::{detect_screen_probe, raw_screen_on},
     state::SharedState,
-    services::disable_thermal_services,
+    services::{restore_from_disk as restore_thermal_from_disk, ThermalTakeover},
     split_charge::{DesiredSplitCharge, SplitChargeController},
     sysfs::{write_str_if_needed, write_u64_if_needed},
-    tempzone::{zone_with_hysteresis, TempZone},
-    thermal::{describe_paths, read_avg_temp_mc, read_control_temp_mc, read_soc_temp_mc},
+    sensors::SensorFilter,
+    trace::TraceRecorder,
     triggers::TriggerManager,
-    user_config::{load_or_init as load_config_or_init, CONFIG_PATH, GAMES_PATH},
+    user_config::{load_or_init as load_config_or_init, CONFIG_PATH, GAMES_PATH, TRACE_PATH},
 };
 
 
 fn maybe_post_notification(shared: &Arc<RwLock<SharedState>>, message: &str) {
-    // Read live config so toggling daemon_notifications takes effect immediately.
-    let enabled = { shared.read().unwrap().config.daemon_notifications };
+    // Read live config so toggling daemon_notifications (or a schedule) takes effect immediately.
+    let enabled = {
+        let s = shared.read().unwrap();
+        daemon_notifications_allowed(&s.config, s.info.game_mode)
+    };
     if enabled {
         post_notification(message);
     }
 }
 
 fn cpu_online_path(cpu: usize) -> PathBuf {
-    PathBuf::from(format!("/sys/devices/system/cpu/cpu{}/online", cpu))
-}
-
-fn is_cpu_online(cpu: usize) -> bool {
-    if cpu == 0 {
-        return true;
-    }
-    let p = cpu_online_path(cpu);
-    // Many kernels omit `online` for permanently-online cores; treat missing as online.
-    if !p.exists() {
-        return true;
-    }
-    sysfs::read_u64(&p).unwrap_or(1) == 1
+    sysfs::path(format!("/sys/devices/system/cpu/cpu{}/online", cpu))
 }
 
 fn set_cpu_online(cpu: usize, online: bool, cache_u64: &mut HashMap<PathBuf, u64>) {
@@ -91,37 +97,115 @@ fn set_cpu_online(cpu: usize, online: bool, cache_u64: &mut HashMap<PathBuf, u64
     let _ = write_u64_if_needed(&p, target, cache_u64, true);
 }
 
-fn battery_saver_disable_delay(override_dur: Duration) -> Duration {
-    let s = override_dur.as_secs_f64();
-    if s <= 10.0 {
-        Duration::from_secs(5)
-    } else if s >= 20.0 {
-        Duration::from_secs(30)
-    } else {
-        let t = (s - 10.0) / 10.0;
-        let ms = 5000.0 + t * 25000.0;
-        Duration::from_millis(ms.round() as u64)
-    }
+/// scaling_governor of one cluster: active profile/preset, else the descriptor's game/normal
+/// governor, else whatever the cluster ran at startup.
+struct ClusterGovernor {
+    label: &'static str,
+    path: PathBuf,
+    game: Option<&'static str>,
+    normal: Option<&'static str>,
+    startup: Option<String>,
+    applied: Option<String>,
 }
 
-const SCREEN_OFF_CORE_SAVER_SECS: u64 = 30 * 60;
+impl ClusterGovernor {
+    fn update(&mut self, want: Option<&str>, game_mode: bool, cache: &mut HashMap<PathBuf, String>) {
+        let fallback = if game_mode { self.game } else { self.normal };
+        let Some(want) = want.or(fallback).or(self.startup.as_deref()) else { return };
+        if self.applied.as_deref() == Some(want) {
+            return;
+        }
+        if let Ok(true) = write_str_if_needed(&self.path, want, cache, true) {
+            println!("GOV: {} {}", self.label, want);
+        }
+        self.applied = Some(want.to_string());
+    }
+}
 
 fn main() {
+    let args = cli::parse();
+    if let Some(root) = &args.root {
+        sysfs::set_root(root.clone());
+    }
+
+    // For uninstall/disable scripts: run while the daemon is stopped.
+    if args.restore {
+        if !shutdown::restore_from_disk() {
+            println!("RESTORE: no journal, nothing to restore");
+        }
+        if !restore_thermal_from_disk() {
+            println!("THERM: no thermal snapshot, nothing to restore");
+        }
+        return;
+    }
+    if args.restore_thermal {
+        if !restore_thermal_from_disk() {
+            println!("THERM: no thermal snapshot, nothing to restore");
+        }
+        return;
+    }
+
+    // Device descriptor. Simulation never probes the host's cpufreq, so runs stay reproducible.
+    let dev = match &args.device_file {
+        Some(p) => match device::load_file(p) {
+            Ok(d) => d,
+            Err(e) => {
+                eprintln!("DEVICE: {}: {}", p.display(), e);
+                std::process::exit(1);
+            }
+        },
+        None => device::resolve(args.device.as_deref(), args.simulate.is_none()),
+    };
+    let dev = device::init(dev);
+
+    if let Some(trace) = &args.simulate {
+        if let Err(e) = sim::run(
+            trace,
+            args.sim_config.as_deref(),
+            args.sim_games.as_deref(),
+            args.sim_out.as_deref(),
+        ) {
+            eprintln!("SIM: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    shutdown::install_signal_handlers();
+    shutdown::init_journal();
+
     swap::init_silent();
     println!("mora_perf_daemon starting");
+    if sysfs::is_relocated() {
+        println!("ROOT: {}", sysfs::path("/").display());
+    }
+    println!(
+        "DEVICE: {} ({}) | clusters {}",
+        dev.name,
+        dev.source,
+        dev.clusters
+            .iter()
+            .map(|c| format!("{}{:?}", c.label, c.cpus))
+            .collect::<Vec<_>>()
+            .join(" "),
+    );
 
-    disable_thermal_services();
+    thermal::zones().log();
     ensure_icon_on_disk();
 
-    let (cpu_paths, gpu_paths, bat_path) = describe_paths();
+    let zmap = thermal::zones();
     println!(
         "THERM: CPU avg zones {} | GPU avg zones {} | BAT {}",
-        cpu_paths.len(),
-        gpu_paths.len(),
-        if bat_path.is_some() { "ok" } else { "missing" }
+        zmap.cpu.len(),
+        zmap.gpu.len(),
+        if zmap.battery.is_some() { "ok" } else { "missing" }
     );
+    let mut sensors = SensorFilter::new(zmap);
+    let mut control_trusted = true;
+    let mut critical_level = CriticalLevel::None;
+    let mut stock_thermal_restored = false;
 
-    let games_path = PathBuf::from(GAMES_PATH);
+    let games_path = sysfs::path(GAMES_PATH);
     let (games_rt, games_err) = load_games_or_init(games_path.as_path());
     println!(
         "GAMES: list {} pkgs (driver {})",
@@ -148,8 +232,19 @@ fn main() {
     // ============================================================
     // Extended functionality: config/profiles/notifications/web UI
     // ============================================================
-    let cfg_path = PathBuf::from(CONFIG_PATH);
-    let cfg = load_config_or_init(cfg_path.as_path());
+    let cfg_path = sysfs::path(CONFIG_PATH);
+    let (cfg, cfg_err) = load_config_or_init(cfg_path.as_path());
+
+    // Take over from stock thermal, or hand it back if a previous run left it taken over.
+    let mut takeover = ThermalTakeover::default();
+    if cfg.thermal_takeover {
+        takeover.engage();
+    } else if takeover.release() {
+        println!("THERM: thermal_takeover off, restored stock thermal from snapshot");
+    } else {
+        println!("THERM: thermal_takeover off, stock thermal left alone");
+    }
+
     let shared = Arc::new(RwLock::new(SharedState::new(cfg, games_rt)));
     let leds = Arc::new(Leds::new());
 
@@ -158,6 +253,9 @@ fn main() {
         let mut s = shared.write().unwrap();
         s.games_rev = s.games_rev.wrapping_add(1);
         s.last_games_error = games_err;
+        s.last_config_error = cfg_err;
+        s.info.thermal_takeover_active = takeover.is_active();
+        s.info.thermal_snapshot = takeover.snapshot().cloned();
     }
 
     // Start background workers.
@@ -169,8 +267,8 @@ fn main() {
     // Apply initial LED state (Normal profile, screen assumed ON).
     {
         let cfg = { shared.read().unwrap().config.clone() };
-        let prof = select_active_mode_profile(&cfg, false);
-                let led_sel = select_base_led(&cfg, true, false, false);
+        let (prof, _) = select_active_mode_profile(&cfg, &RuleContext { screen_on: true, ..RuleContext::default() });
+                let led_sel = select_base_led(&cfg, &prof, None, active_schedule(&cfg, clock::minute_of_day(), false), true, false, false);
 
         leds.set_fan_desired(led_sel.fan.clone());
         leds.set_external_desired(led_sel.external.clone());
@@ -187,9 +285,10 @@ let mut s = shared.write().unwrap();
     }
 
     let mut fan = Fan::new();
+    let mut maintenance = FanMaintenance::new();
     if fan.is_some() {
-        let en = sysfs::read_u64(Path::new(FAN_ENABLE)).unwrap_or(0);
-        let lv = sysfs::read_u64(Path::new(FAN_LEVEL)).unwrap_or(0);
+        let en = sysfs::read_u64(&sysfs::path(&dev.fan.enable)).unwrap_or(0);
+        let lv = sysfs::read_u64(&sysfs::path(&dev.fan.level)).unwrap_or(0);
         println!("FAN: sysfs ok (en={} lvl={})", en, lv);
     } else {
         println!("FAN: sysfs not found (skip)");
@@ -208,7 +307,7 @@ let mut s = shared.write().unwrap();
     };
 
     let gpu_busy_percent_path = {
-        let p = PathBuf::from(GPU_BUSY_PERCENT);
+        let p = sysfs::path(&dev.gpu.busy_percent);
         if p.exists() {
             println!("GPUUTIL: gpu_busy_percentage");
             Some(p)
@@ -217,85 +316,54 @@ let mut s = shared.write().unwrap();
             None
         }
     };
-    let gpubusy_path = Path::new(GPU_GPUBUSY);
-
-    let base0 = base_index_from_ratio(CPU0_FREQS, 0.62);
-    let base2 = base_index_from_ratio(CPU2_FREQS, 0.48);
-    let base5 = base_index_from_ratio(CPU5_FREQS, 0.48);
-    let base7 = base_index_from_ratio(CPU7_FREQS, 0.35);
-    let (gpu_freqs, gpu_freqs_source) = load_gpu_freqs_dynamic(GPU_FREQS);
-    let baseg = base_index_from_ratio(gpu_freqs, 0.50);
+    let gpubusy_path = sysfs::path(&dev.gpu.gpubusy);
 
-    let now = Instant::now();
-
-    let mut cpu0 = Domain::new(
-        "CPU0", CPU0_FREQS, POLICY0_MIN, POLICY0_MAX, base0, false, now,
-        UP_UTIL, SPIKE_DELTA2, SPIKE_DELTA4, HIGH_JUMP2, HIGH_JUMP4,
-        60, 50, Duration::from_secs(6), Duration::from_secs(3)
-    );
-    let mut cpu2 = Domain::new(
-        "CPU2", CPU2_FREQS, POLICY2_MIN, POLICY2_MAX, base2, false, now,
-        UP_UTIL, SPIKE_DELTA2, SPIKE_DELTA4, HIGH_JUMP2, HIGH_JUMP4,
-        60, 50, Duration::from_secs(6), Duration::from_secs(3)
-    );
-    let mut cpu5 = Domain::new(
-        "CPU5", CPU5_FREQS, POLICY5_MIN, POLICY5_MAX, base5, false, now,
-        UP_UTIL, SPIKE_DELTA2, SPIKE_DELTA4, HIGH_JUMP2, HIGH_JUMP4,
-        60, 50, Duration::from_secs(6), Duration::from_secs(3)
-    );
-    let mut cpu7 = Domain::new(
-        "CPU7", CPU7_FREQS, POLICY7_MIN, POLICY7_MAX, base7, false, now,
-        UP_UTIL, SPIKE_DELTA2, SPIKE_DELTA4, HIGH_JUMP2, HIGH_JUMP4,
-        60, 50, Duration::from_secs(7), Duration::from_secs(4)
-    );
-    let mut gpu = Domain::new(
-        "GPU", gpu_freqs, GPU_MIN, GPU_MAX, baseg, true, now,
-        UP_UTIL, SPIKE_DELTA2, SPIKE_DELTA4, HIGH_JUMP2, HIGH_JUMP4,
-        60, 50, Duration::from_secs(5), Duration::from_secs(3)
-    );
+    let (gpu_freqs, gpu_freqs_source) = load_gpu_freqs_dynamic(&dev.gpu.freqs);
+    let mut ctrl = match Controller::new(dev, gpu_freqs, Instant::now()) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("DEVICE: {}", e);
+            std::process::exit(1);
+        }
+    };
 
     println!(
         "GPU table: {} ({} steps, {}..{})",
         gpu_freqs_source,
-        gpu.freqs.len(),
-        fmt_hz(gpu.freqs[0]),
-        fmt_hz(*gpu.freqs.last().unwrap()),
-    );
-
-    println!(
-        "BASE: CPU0 {} | CPU2 {} | CPU5 {} | CPU7 {} | GPU {}",
-        fmt_khz(cpu0.freqs[cpu0.base_index]),
-        fmt_khz(cpu2.freqs[cpu2.base_index]),
-        fmt_khz(cpu5.freqs[cpu5.base_index]),
-        fmt_khz(cpu7.freqs[cpu7.base_index]),
-        fmt_hz(gpu.freqs[gpu.base_index]),
+        ctrl.gpu.freqs.len(),
+        fmt_hz(ctrl.gpu.freqs[0]),
+        fmt_hz(*ctrl.gpu.freqs.last().unwrap()),
     );
 
-    // Normal mins
-    let cpu0_min_normal = CPU0_FREQS[0];
-    let cpu2_min_normal = CPU2_FREQS[0];
-    let cpu5_min_normal = CPU5_FREQS[0];
-    let cpu7_min_normal = CPU7_FREQS[0];
-    let gpu_min_normal = gpu.freqs[0];
-
-    // Game mins = mid frequency (~50%)
-    let cpu0_min_game = mid_freq(CPU0_FREQS);
-    let cpu2_min_game = mid_freq(CPU2_FREQS);
-    let cpu5_min_game = mid_freq(CPU5_FREQS);
-    let cpu7_min_game = mid_freq(CPU7_FREQS);
-    let gpu_min_game = mid_freq(gpu.freqs);
-
-    let cluster0 = [0usize, 1usize];
-    let cluster2 = [2usize, 3usize, 4usize];
-    let cluster5 = [5usize, 6usize];
-    let cluster7 = [7usize];
+    {
+        let mut line = String::from("BASE:");
+        for d in ctrl.cpu.iter() {
+            line.push_str(&format!(" {} {} |", d.label, fmt_khz(d.freqs[d.base_index])));
+        }
+        line.push_str(&format!(" GPU {}", fmt_hz(ctrl.gpu.freqs[ctrl.gpu.base_index])));
+        println!("{}", line);
+    }
 
     let mut cache_u64: HashMap<PathBuf, u64> = HashMap::new();
     let mut cache_str: HashMap<PathBuf, String> = HashMap::new();
-    let policy7_gov_path = PathBuf::from(POLICY7_GOV);
+    // Per-cluster governors (the descriptor switches policy7 with game mode on tiro).
+    let mut governors: Vec<ClusterGovernor> = dev
+        .clusters
+        .iter()
+        .map(|c| {
+            let path = sysfs::path(c.governor_path());
+            ClusterGovernor {
+                label: &c.label,
+                startup: sysfs::read_to_string(&path).map(|v| v.trim().to_string()),
+                path,
+                game: c.game_governor.as_deref(),
+                normal: c.normal_governor.as_deref(),
+                applied: None,
+            }
+        })
+        .collect();
 
     let mut prev_cpu: Option<CpuStatSample> = None;
-    let mut last_zone = TempZone::Cool;
 
     let mut last_enforce = Instant::now();
     let enforce_every_active = Duration::from_secs(ENFORCE_ACTIVE);
@@ -328,22 +396,19 @@ let mut s = shared.write().unwrap();
     let batt_check_every = Duration::from_secs(5 * 60);
 
     let mut game_mode = false;
-    // Minimum fan level while current foreground game is active (2..=5). Default matches config::GAME_FAN_BASE.
-    let mut game_fan_min_level: u8 = GAME_FAN_BASE;
-    // Per-game GPU turbo flag for current foreground game.
-    let mut game_gpu_turbo: bool = false;
-    // Per-game thermal limit bypass flag.
-    let mut game_disable_thermal_limit: bool = false;
+    // Per-game flags for the current foreground game (fan minimum, GPU turbo, thermal bypass, split charge).
+    let mut game = GameFlags::default();
+    // App rule (games.json `apps`) for the current foreground non-game package.
+    let mut app: Option<AppEntry> = None;
     let mut last_triggers_cfg: Option<crate::triggers::ActiveConfig> = None;
     let mut last_game_check = Instant::now();
     let game_check_every = Duration::from_secs(GAME_CHECK_EVERY);
     let mut last_game_pkg: Option<String> = None;
-    let mut game_split_charge_cfg = crate::games::SplitChargeConfig::default();
     let mut split_charge = SplitChargeController::new();
     let mut fan_disabled_by_config = false;
+    let mut trace = TraceRecorder::new(sysfs::path(TRACE_PATH));
 
     let mut idle_mode = false;
-    let mut idle_accum = Duration::ZERO;
 
     let mut proc_watch = ProcWatch::new();
     let mut last_proc_check = Instant::now();
@@ -355,7 +420,6 @@ let mut s = shared.write().unwrap();
     let long_off_threshold = Duration::from_secs(LONG_OFF_NOTIFY_SECS);
 
     let mut last_loop = Instant::now();
-    let mut stable_for = Duration::ZERO;
     let mut last_stat_log = Instant::now() - Duration::from_secs(3600);
     let stat_log_every = Duration::from_secs(60);
 
@@ -363,18 +427,36 @@ let mut s = shared.write().unwrap();
     let mut cfg_cache = { shared.read().unwrap().config.clone() };
     let mut cfg_rev_cache = { shared.read().unwrap().config_rev };
 
-    // Smart battery saver runtime state.
-    let mut bs_high_streak: Duration = Duration::ZERO;
-    let mut bs_override = false;
-    let mut bs_override_since: Option<Instant> = None;
-    let mut bs_reapply_at: Option<Instant> = None;
-    let mut bs_disabled_cores: Vec<usize> = Vec::new();
-
-    loop {
+    let safe_caps = ctrl
+        .domains()
+        .map(|d| watchdog::SafeCap {
+            min_path: d.min_path.clone(),
+            min_freq: d.min_freq,
+            max_path: d.max_path.clone(),
+            base_freq: d.freqs[d.base_index],
+        })
+        .collect();
+    watchdog::spawn(shared.clone(), safe_caps);
+    let mut wdog_pushes = watchdog::safe_pushes();
+
+    while !shutdown::requested() {
+        watchdog::beat();
         let now = Instant::now();
         let dt = now.duration_since(last_loop);
         last_loop = now;
 
+        // The watchdog pushed safe values during a stall: forget what we think is written so
+        // the caps go back up this iteration instead of at the next enforce pass.
+        let pushes = watchdog::safe_pushes();
+        if pushes != wdog_pushes {
+            wdog_pushes = pushes;
+            cache_u64.clear();
+            println!("WDOG: resumed after safe values, rewriting caps and fan");
+            if let Some(f) = fan.as_mut() {
+                f.resync(&mut cache_u64);
+            }
+        }
+
         // charging (adaptive interval based on battery percent)
         if now.duration_since(last_chg_check) >= chg_check_every(battery_percent) {
             let new_chg = charge_probe.as_ref().map(|p| p.is_charging()).unwrap_or(false);
@@ -431,32 +513,12 @@ let mut s = shared.write().unwrap();
             last_game_check = now;
 
             let pkg = get_foreground_package();
-            let (is_game, detected_fan_min, detected_gpu_turbo, detected_disable_thermal_limit, detected_split_charge_cfg) = pkg
-                .as_deref()
-                .map(|p| {
-                    let s = shared.read().unwrap();
-                    (
-                        s.games.is_game(p),
-                        s.games.game_fan_min_level(p),
-                        s.games.game_gpu_turbo(p),
-                        s.games.game_disable_thermal_limit(p),
-                        s.games.game_split_charge(p),
-                    )
-                })
-                .unwrap_or((false, GAME_FAN_BASE, false, false, crate::games::SplitChargeConfig::default()));
-
-            // Keep the latest per-game minimum fan level while in game mode.
-            if is_game {
-                game_fan_min_level = detected_fan_min;
-                game_gpu_turbo = detected_gpu_turbo;
-                game_disable_thermal_limit = detected_disable_thermal_limit;
-                game_split_charge_cfg = detected_split_charge_cfg;
-            } else {
-                game_fan_min_level = GAME_FAN_BASE;
-                game_gpu_turbo = false;
-                game_disable_thermal_limit = false;
-                game_split_charge_cfg = crate::games::SplitChargeConfig::default();
-            }
+            // Keep the latest per-game flags while in game mode.
+            (game, app) = {
+                let s = shared.read().unwrap();
+                let app = pkg.as_deref().and_then(|p| s.games.app_for(p)).cloned();
+                (GameFlags::resolve(&s.games, pkg.as_deref()), app)
+            };
 
             // Triggers config (per-game). Active only for foreground game and screen ON.
             if let Some(mgr) = triggers.as_ref() {
@@ -504,53 +566,20 @@ let mut s = shared.write().unwrap();
                 s.info.triggers_pkg = if active { pkg.clone() } else { None };
             }
 
-            if pkg != last_game_pkg {
-                last_game_pkg = pkg.clone();
+            last_game_pkg = pkg.clone();
 
-                // If we switched between games, immediately apply that game's minimum.
-                if is_game {
-                    if let Some(f) = fan.as_mut() {
-                        if f.level() < game_fan_min_level {
-                            f.force_level(&mut cache_u64, game_fan_min_level);
-                        }
-                    }
-                }
-            }
-
-            if is_game != game_mode {
-                game_mode = is_game;
+            if game.active != game_mode {
+                game_mode = game.active;
 
+                // Min freqs, the game fan baseline and governors follow the active profile;
+                // here we only log and notify.
                 if game_mode {
                     let name = pkg.clone().unwrap_or_else(|| "?".to_string());
                     println!("GAME: ON ({})", name);
                     maybe_post_notification(&shared, &format!("Game mode ON: {}", name));
-                    // fan baseline now (per-game minimum)
-                    if let Some(f) = fan.as_mut() {
-                        if f.level() < game_fan_min_level {
-                            f.force_level(&mut cache_u64, game_fan_min_level);
-                        }
-                    }
-
-                    // mins to ~50%
-                    cpu0.min_freq = cpu0_min_game;
-                    cpu2.min_freq = cpu2_min_game;
-                    cpu5.min_freq = cpu5_min_game;
-                    cpu7.min_freq = cpu7_min_game;
-                    gpu.min_freq = gpu_min_game;
-
-                    // policy7 governor
-                    let _ = write_str_if_needed(&policy7_gov_path, GOV_GAME, &mut cache_str, true);
                 } else {
                     println!("GAME: OFF");
                     maybe_post_notification(&shared, "Game mode OFF");
-
-                    cpu0.min_freq = cpu0_min_normal;
-                    cpu2.min_freq = cpu2_min_normal;
-                    cpu5.min_freq = cpu5_min_normal;
-                    cpu7.min_freq = cpu7_min_normal;
-                    gpu.min_freq = gpu_min_normal;
-
-                    let _ = write_str_if_needed(&policy7_gov_path, GOV_NORMAL, &mut cache_str, true);
                 }
             }
         }
@@ -578,37 +607,26 @@ let mut s = shared.write().unwrap();
             }
         }
 
-        // temps (AVG) and battery temperature for thermal control
-        let cpu_avg_mc = read_avg_temp_mc(&cpu_paths);
-        let gpu_avg_mc = read_avg_temp_mc(&gpu_paths);
-        let batt_temp_mc = bat_path.as_ref().and_then(|p| sysfs::read_i32(p));
-        let control_temp_mc = read_control_temp_mc(batt_temp_mc, cpu_avg_mc, gpu_avg_mc);
-
-        let zone = if let Some(t) = control_temp_mc {
-            zone_with_hysteresis(t, last_zone)
-        } else {
-            last_zone
-        };
-
-        if zone != last_zone {
-            let c = cpu_avg_mc.map(fmt_c).unwrap_or_else(|| "?".to_string());
-            let g = gpu_avg_mc.map(fmt_c).unwrap_or_else(|| "?".to_string());
-            let b = batt_temp_mc.map(fmt_c).unwrap_or_else(|| "?".to_string());
-            let u = control_temp_mc.map(fmt_c).unwrap_or_else(|| "?".to_string());
-            println!("TEMP: batt {} | cpu {} | gpu {} | use {} -> {:?} (reduce {}%)", b, c, g, u, zone, zone.reduction_percent());
-            last_zone = zone;
-        }
+        // temps (AVG) and battery temperature for thermal control, trusted sensors only
+        sensors.update(&cfg_cache.sensor_health, now);
+        let cpu_avg_mc = sensors.avg_mc(&zmap.cpu);
+        let gpu_avg_mc = sensors.avg_mc(&zmap.gpu);
+        let batt_temp_mc = sensors.max_mc(zmap.battery.as_slice());
+        // per-domain hotspots (max) for thermal attribution
+        let domain_temps_mc = zmap
+            .domains
+            .iter()
+            .filter_map(|d| sensors.max_mc(&d.zones).map(|t| (d.label.clone(), t)))
+            .collect();
+        // the critical layer also sees suspect (high outlier) sensors
+        let soc_hot_mc = sensors.hotspot_mc(false);
+        let batt_hot_mc = sensors.hotspot_mc(true);
 
         // GPU util
-        let ug = read_gpu_util_any(gpu_busy_percent_path.as_deref(), gpubusy_path);
+        let ug = read_gpu_util_any(gpu_busy_percent_path.as_deref(), &gpubusy_path);
 
         // CPU util
         let cpu_utils = cpu_utils_by_core(&mut prev_cpu).unwrap_or_default();
-        let u0 = avg_util(&cpu_utils, &cluster0);
-        let u2 = avg_util(&cpu_utils, &cluster2);
-        let u5 = avg_util(&cpu_utils, &cluster5);
-        let u7 = avg_util(&cpu_utils, &cluster7);
-        let max_cpu_cluster = u0.max(u2).max(u5).max(u7);
 
         // bg scan (screen OFF)
         let mut bg_over = false;
@@ -636,245 +654,175 @@ let mut s = shared.write().unwrap();
         }
         let cfg = &cfg_cache;
 
-        // charging config switch (defaults to ON)
-        let charging_enabled = cfg.charging.enabled;
-        let charging_effective = charging && charging_enabled;
-        // ------------------------------
-        // Smart battery saver (CPU core hotplug)
-        // ------------------------------
-        let bs_enabled = cfg.battery_saver.enabled;
-        let mut offline_by_battery: Vec<usize> = Vec::new();
-        let mut offline_by_screen_off: Vec<usize> = Vec::new();
-
-        // Battery thresholds:
-        //  - <50%  => disable cpu7
-        //  - <35%  => disable cpu5,cpu6,cpu7
-        // Overrides:
-        //  - charging OR game_mode => always enable all
-        //  - if base cores >=90% for 15s => enable all cores
-        if bs_enabled && !charging && !game_mode {
-            if let Some(pct) = battery_percent {
-                if pct < 35 {
-                    offline_by_battery.extend([5usize, 6, 7]);
-                } else if pct < 50 {
-                    offline_by_battery.push(7);
-                }
+        // Manual fan override: released once its time is up or its game has left. Checked and
+        // cleared under one lock, so an override posted in between is never dropped.
+        let fan_override = {
+            let mut s = shared.write().unwrap();
+            if let Some(reason) = s.fan_override.as_ref().and_then(|o| o.expired(now, game_mode)) {
+                s.fan_override.take();
+                println!("FAN: override released ({})", reason);
             }
-        }
+            s.fan_override.as_ref().map(|o| o.level)
+        };
 
-        let battery_baseline_off = !offline_by_battery.is_empty();
-
-        // Average utilization across base cores is only needed when battery saver
-        // would actually park cores. Avoid extra sysfs reads in the normal >50% path.
-        let base_util: u8 = if battery_baseline_off {
-            let mut sum: u32 = 0;
-            let mut n: u32 = 0;
-            for c in 0usize..=4usize {
-                if is_cpu_online(c) {
-                    if let Some(&u) = cpu_utils.get(c) {
-                        sum += u as u32;
-                        n += 1;
-                    }
-                }
-            }
-            if n == 0 { 0 } else { (sum / n) as u8 }
-        } else {
-            0
+        let inputs = Inputs {
+            cpu_avg_mc,
+            gpu_avg_mc,
+            batt_temp_mc,
+            domain_temps_mc,
+            soc_hot_mc,
+            batt_hot_mc,
+            cpu_utils,
+            ug,
+            screen_on,
+            charging,
+            battery_percent,
+            pkg: last_game_pkg.clone(),
+            bg_over,
+            minute_of_day: clock::minute_of_day(),
+            fan_override,
+            game: game.clone(),
+            app: app.clone(),
         };
+        let d = ctrl.step(&inputs, cfg, now, dt);
+        idle_mode = d.idle_mode;
+        trace.record(&cfg.trace, now, &inputs, &d);
 
-        if !bs_enabled || charging || game_mode || !battery_baseline_off {
-            // Feature disabled / not applicable => reset battery-saver state.
-            bs_high_streak = Duration::ZERO;
-            bs_override = false;
-            bs_override_since = None;
-            bs_reapply_at = None;
-            offline_by_battery.clear();
-        } else {
-            // Override state machine.
-            if bs_override {
-                if base_util >= 90 {
-                    // Still heavy -> cancel pending re-apply.
-                    bs_reapply_at = None;
-                } else {
-                    if bs_reapply_at.is_none() {
-                        let since = bs_override_since.unwrap_or(now);
-                        let delay = battery_saver_disable_delay(now.duration_since(since));
-                        bs_reapply_at = Some(now + delay);
-                    }
-                    if bs_reapply_at.map(|t| now >= t).unwrap_or(false) {
-                        bs_override = false;
-                        bs_override_since = None;
-                        bs_reapply_at = None;
-                        bs_high_streak = Duration::ZERO;
-                    }
-                }
-            }
+        let perf = ctrl.active_perf();
+        for g in governors.iter_mut() {
+            g.update(perf.and_then(|p| p.governor(g.label)), d.game_mode, &mut cache_str);
+        }
 
-            if !bs_override {
-                if base_util >= 90 {
-                    bs_high_streak += dt;
-                    if bs_high_streak >= Duration::from_secs(15) {
-                        bs_override = true;
-                        bs_override_since = Some(now);
-                        bs_reapply_at = None;
-                        bs_high_streak = Duration::ZERO;
+        // Critical safeguard transitions. Notifications here are urgent: posted even with
+        // daemon_notifications off.
+        if d.critical != critical_level {
+            let soc = d.soc_hot_mc.map(fmt_c).unwrap_or_else(|| "?".to_string());
+            let bat = batt_temp_mc.map(fmt_c).unwrap_or_else(|| "?".to_string());
+            println!("CRIT: {:?} -> {:?} (soc {} | batt {})", critical_level, d.critical, soc, bat);
+            match d.critical {
+                CriticalLevel::Shutdown => {
+                    post_notification(&format!("DANGER: soc {} / batt {} still rising, stock thermal restored", soc, bat));
+                    if !stock_thermal_restored {
+                        takeover.release();
+                        stock_thermal_restored = true;
                     }
-                } else {
-                    bs_high_streak = Duration::ZERO;
                 }
+                CriticalLevel::Critical if critical_level < CriticalLevel::Critical => {
+                    post_notification(&format!("CRITICAL temperature (soc {} / batt {}): minimum caps, fan max", soc, bat));
+                }
+                CriticalLevel::None => post_notification("Temperature back below critical"),
+                _ => {}
             }
-
-            if bs_override {
-                offline_by_battery.clear();
-            }
+            critical_level = d.critical;
         }
 
-        // Additional saver: if the screen stays OFF for 30 minutes, temporarily park cpu5..cpu7.
-        // Battery-based saver remains the priority; when the screen turns back ON, only the
-        // extra screen-off restriction is removed and the battery policy keeps whatever cores
-        // should still stay offline for the current battery %.
-        if !charging && !game_mode {
-            if let Some(since) = screen_off_since {
-                if !screen_on_state && now.duration_since(since) >= Duration::from_secs(SCREEN_OFF_CORE_SAVER_SECS) {
-                    offline_by_screen_off.extend([5usize, 6, 7]);
-                }
+        // Follow `thermal_takeover` (config edit, /api/thermal/takeover or /api/thermal/restore),
+        // unless the critical safeguard has already handed thermal back for this run.
+        if cfg.thermal_takeover != takeover.is_active() && !stock_thermal_restored {
+            if cfg.thermal_takeover {
+                takeover.engage();
+            } else {
+                takeover.release();
             }
+            let mut st = shared.write().unwrap();
+            st.info.thermal_takeover_active = takeover.is_active();
+            st.info.thermal_snapshot = takeover.snapshot().cloned();
         }
 
-        let mut desired_offline: Vec<usize> = offline_by_battery.clone();
-        for c in offline_by_screen_off.iter().copied() {
-            if !desired_offline.contains(&c) {
-                desired_offline.push(c);
+        // The control temperature is only as good as the sensors its formula reads.
+        let formula = cfg.control_temp.by_name(d.control_formula);
+        let batt_ok = zmap.battery.is_none() || sensors.any_trusted(zmap.battery.as_slice());
+        let soc_ok = sensors.any_trusted(&zmap.cpu) || sensors.any_trusted(&zmap.gpu);
+        let trusted = (!formula.uses_battery() || batt_ok) && (!formula.uses_soc() || soc_ok);
+        if trusted != control_trusted {
+            control_trusted = trusted;
+            if trusted {
+                println!("SENSOR: control temperature sensors trusted again");
+                maybe_post_notification(&shared, "Temperature sensors OK again");
+            } else {
+                let bad = sensors
+                    .health()
+                    .iter()
+                    .filter(|h| !h.trusted)
+                    .map(|h| format!("{} {}", h.ty, h.status.as_str()))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                println!("SENSOR: control temperature untrusted ({})", bad);
+                maybe_post_notification(&shared, &format!("Temperature sensor untrusted ({}), using fallback", bad));
             }
         }
-        desired_offline.sort_unstable();
 
-        // Apply desired core states (manage cpu5..cpu7 only).
-        for &c in &[5usize, 6, 7] {
-            set_cpu_online(c, !desired_offline.contains(&c), &mut cache_u64);
+        if d.zone_changed {
+            let c = cpu_avg_mc.map(fmt_c).unwrap_or_else(|| "?".to_string());
+            let g = gpu_avg_mc.map(fmt_c).unwrap_or_else(|| "?".to_string());
+            let b = batt_temp_mc.map(fmt_c).unwrap_or_else(|| "?".to_string());
+            let u = d.control_temp_mc.map(fmt_c).unwrap_or_else(|| "?".to_string());
+            let pred = match (d.zone_temp_mc, d.control_temp_mc) {
+                (Some(z), Some(t)) if z != t => format!(" | pred {} ({:+.1}C/min)", fmt_c(z), d.temp_slope_c_per_min.unwrap_or(0.0)),
+                _ => String::new(),
+            };
+            println!("TEMP: batt {} | cpu {} | gpu {} | use {}{} -> {} (reduce {}%)", b, c, g, u, pred, d.zone, d.zone.reduction_percent());
+        }
+        if d.idle_changed {
+            println!("IDLE: {}", if idle_mode { "enter" } else { "exit" });
         }
 
-        bs_disabled_cores = offline_by_battery.clone();
-        let bs_active = bs_enabled && !charging && !game_mode && !bs_override && !bs_disabled_cores.is_empty();
-        let bs_reapply_in = bs_reapply_at.map(|t| if t > now { (t - now).as_secs() } else { 0 });
-        let screen_off_saver_active = !offline_by_screen_off.is_empty();
+        // Apply desired core states (parkable clusters only).
+        for &c in &ctrl.parkable_cpus {
+            set_cpu_online(c, !d.offline_cores.contains(&c), &mut cache_u64);
+        }
 
-        let split_charge_should_enable = if game_mode
-            && game_split_charge_cfg.enabled
+        let split_charge_should_enable = game_mode
+            && game.split_charge.enabled
             && charging
-            && battery_percent.map(|p| p > game_split_charge_cfg.stop_battery_percent).unwrap_or(false)
-        {
-            true
-        } else {
-            false
-        };
+            && battery_percent.map(|p| p > game.split_charge.stop_battery_percent).unwrap_or(false);
         let desired_split_charge = DesiredSplitCharge {
             should_enable: split_charge_should_enable,
             package: if split_charge_should_enable { last_game_pkg.clone() } else { None },
-            stop_battery_percent: game_split_charge_cfg.stop_battery_percent,
+            stop_battery_percent: game.split_charge.stop_battery_percent,
         };
         split_charge.sync(desired_split_charge, now);
         let split_charge_status = split_charge.status();
 
-        // ------------------------------
-        // Profile/LED selection
-        // ------------------------------
-        let active_prof = select_active_mode_profile(cfg, game_mode);
-                let led_sel = select_base_led(cfg, screen_on, charging_effective, game_mode);
-        leds.set_fan_desired(led_sel.fan.clone());
-        leds.set_external_desired(led_sel.external.clone());
+        // LEDs
+        leds.set_fan_desired(d.led.fan.clone());
+        leds.set_external_desired(d.led.external.clone());
 
         let (fan_des, fan_last) = leds.get_fan_state();
         let (ext_des, ext_last) = leds.get_external_state();
-// fan
+
+        // fan
         if let Some(f) = fan.as_mut() {
-            if cfg.use_phone_cooler {
+            let busy = game_mode || d.critical.active() || d.fan_override.is_some();
+            let blocker = fan_maintenance::blocker(&cfg.fan_maintenance, charging, screen_on, batt_temp_mc, busy);
+            if let Some(level) = maintenance.step(&cfg.fan_maintenance, blocker, now) {
+                if f.level() != level {
+                    f.force_level(&mut cache_u64, level);
+                }
+                // Put the configured state back once the run ends.
+                fan_disabled_by_config = false;
+            } else if cfg.use_phone_cooler || d.critical.active() || d.fan_override.is_some() {
                 fan_disabled_by_config = false;
-                let soc = read_soc_temp_mc(cpu_avg_mc, gpu_avg_mc).unwrap_or(-1);
-                // At 100% battery, don't keep the cooler running just because charging is connected.
-                // Game mode is excluded so per-game fan rules still work while playing.
-                let cooler_charging_effective = charging_effective && !(battery_percent == Some(100) && !game_mode);
-                f.apply(
-                    &mut cache_u64,
-                    soc,
-                    batt_temp_mc,
-                    screen_on,
-                    cooler_charging_effective,
-                    game_mode,
-                    game_fan_min_level,
-                );
+                f.apply(&mut cache_u64, d.fan_level);
+                f.verify(&mut cache_u64, now);
+                if let Some(err) = f.take_fault_notice() {
+                    maybe_post_notification(&shared, &format!("Fan not responding ({}): cooling may be off", err));
+                }
             } else if !fan_disabled_by_config || f.level() != 0 {
                 f.force_level(&mut cache_u64, 0);
                 fan_disabled_by_config = true;
             }
         }
 
-        // idle mode
-        let idle_cond = !screen_on && !bg_over && max_cpu_cluster < IDLE_CPU_MAX && ug < IDLE_GPU_MAX;
-
-        if idle_cond { idle_accum += dt; } else { idle_accum = Duration::ZERO; }
-
-        if !idle_mode && idle_accum >= Duration::from_secs(IDLE_ENTER_SECS) {
-            idle_mode = true;
-            println!("IDLE: enter");
-
-            if !charging_effective && !game_mode {
-                cpu0.idx = cpu0.base_index;
-                cpu2.idx = cpu2.base_index;
-                cpu5.idx = cpu5.base_index;
-                cpu7.idx = cpu7.base_index;
-                gpu.idx = gpu.base_index;
-            }
-        }
-
-        if idle_mode && !idle_cond {
-            idle_mode = false;
-            println!("IDLE: exit");
-        }
-
         // enforce
         let enforce_every = if idle_mode { enforce_every_idle } else { enforce_every_active };
         let force_check = now.duration_since(last_enforce) >= enforce_every;
         if force_check { last_enforce = now; }
 
-        
-        // GPU turbo: pin min/max to maximum while foreground game requests it.
-        let gpu_turbo_active = game_mode && screen_on && game_gpu_turbo;
-        if game_mode {
-            if gpu_turbo_active {
-                gpu.min_freq = gpu.max_freq;
-            } else {
-                gpu.min_freq = gpu_min_game;
-            }
-        } else {
-            gpu.min_freq = gpu_min_normal;
-        }
-
-// desired idx update
-        let mut any_step = false;
-        any_step |= cpu0.desired_step_update(u0, now, dt);
-        any_step |= cpu2.desired_step_update(u2, now, dt);
-        any_step |= cpu5.desired_step_update(u5, now, dt);
-        any_step |= cpu7.desired_step_update(u7, now, dt);
-        if !gpu_turbo_active {
-            any_step |= gpu.desired_step_update(ug, now, dt);
-        } else {
-            // Force GPU domain to max immediately while turbo is active.
-            gpu.force_idx(gpu.freqs.len() - 1, now);
-        }
-
         // apply caps
-        let effective_zone = if game_mode && game_disable_thermal_limit {
-            TempZone::Cool
-        } else {
-            zone
-        };
-        let mut any_write = false;
-        any_write |= cpu0.apply(effective_zone, &mut cache_u64, force_check).unwrap_or(false);
-        any_write |= cpu2.apply(effective_zone, &mut cache_u64, force_check).unwrap_or(false);
-        any_write |= cpu5.apply(effective_zone, &mut cache_u64, force_check).unwrap_or(false);
-        any_write |= cpu7.apply(effective_zone, &mut cache_u64, force_check).unwrap_or(false);
-        any_write |= gpu.apply(effective_zone, &mut cache_u64, force_check).unwrap_or(false);
+        for dom in ctrl.domains() {
+            let _ = dom.write_cap(&mut cache_u64, force_check);
+        }
 
         // STAT: human-readable status is useful but logcat I/O is not free.
         // Keep it slow and independent from the cap enforcement interval.
@@ -883,7 +831,7 @@ let mut s = shared.write().unwrap();
             last_stat_log = now;
             let c = cpu_avg_mc.map(|v| v as f32 / 1000.0);
             let g = gpu_avg_mc.map(|v| v as f32 / 1000.0);
-            let u = control_temp_mc.map(|v| v as f32 / 1000.0);
+            let u = d.control_temp_mc.map(|v| v as f32 / 1000.0);
             let b = batt_temp_mc.map(|v| v as f32 / 1000.0);
 
             let c = c.map(|x| format!("{:.1}C", x)).unwrap_or_else(|| "?".to_string());
@@ -891,10 +839,11 @@ let mut s = shared.write().unwrap();
             let u = u.map(|x| format!("{:.1}C", x)).unwrap_or_else(|| "?".to_string());
             let b = b.map(|x| format!("{:.1}C", x)).unwrap_or_else(|| "?".to_string());
 
+            let cpu_u = d.caps[..ctrl.cpu.len()].iter().map(|c| c.util.to_string()).collect::<Vec<_>>().join(" ");
             println!(
-                "STAT: cpu {} | gpu {} | use {} | bat {} | CPU[{} {} {} {}]% | GPU {}% | scr {} | chg {}{}{}",
+                "STAT: cpu {} | gpu {} | use {} | bat {} | CPU[{}]% | GPU {}% | scr {} | chg {}{}{}",
                 c, g, u, b,
-                u0, u2, u5, u7,
+                cpu_u,
                 ug,
                 if screen_on { "ON" } else { "OFF" },
                 if charging { "ON" } else { "OFF" },
@@ -906,12 +855,12 @@ let mut s = shared.write().unwrap();
                 let mut st = shared.write().unwrap();
                 st.info.cpu_avg_mc = cpu_avg_mc;
                 st.info.gpu_avg_mc = gpu_avg_mc;
-                st.info.soc_mc = control_temp_mc;
+                st.info.soc_mc = d.control_temp_mc;
                 st.info.batt_mc = batt_temp_mc;
-                st.info.temp_zone = format!("{:?}", zone);
+                st.info.temp_zone = d.zone.to_string();
                 // mora's reduction percent is returned as u32, UI stores it as u8.
                 // Clamp to avoid accidental overflow if implementation changes.
-                let rp = zone.reduction_percent();
+                let rp = d.zone.reduction_percent();
                 st.info.reduce_percent = rp.min(u8::MAX as u32) as u8;
             }
         }
@@ -921,58 +870,75 @@ let mut s = shared.write().unwrap();
             let mut st = shared.write().unwrap();
             st.info.screen_on = screen_on;
             st.info.charging = charging;
-            st.info.charging_enabled = charging_enabled;
-            st.info.charging_effective = charging_effective;
+            st.info.charging_enabled = cfg.charging.enabled;
+            st.info.charging_effective = d.charging_effective;
             st.info.game_mode = game_mode;
             st.info.idle_mode = idle_mode;
 
+            // PID output moves every iteration; the zone block above only runs on band changes.
+            st.info.control_formula = d.control_formula;
+            st.info.control_sensor_trusted = control_trusted;
+            st.info.critical_level = d.critical;
+            st.info.soc_hot_mc = d.soc_hot_mc;
+            st.info.stock_thermal_restored = stock_thermal_restored;
+            st.info.thermal_takeover_active = takeover.is_active();
+            st.info.sensors = sensors.health();
+            st.info.soc_mc = d.control_temp_mc;
+            st.info.thermal_pid = d.pid;
+            st.info.fan_setpoint = d.fan_setpoint;
+            st.info.fan = fan.as_ref().map(|f| f.status(now));
+            st.info.fan_maintenance = maintenance.status(&cfg.fan_maintenance);
+            st.info.temp_slope_c_per_min = d.temp_slope_c_per_min;
+            st.info.zone_temp_mc = d.zone_temp_mc;
+            st.info.thermal_hotspot = d.hotspot;
+            st.info.thermal_domains = d.thermal_domains.clone();
+            st.info.domain_tuning = ctrl.domains().map(|dom| (dom.label, *dom.tuning())).collect();
+            if let Some(p) = &d.pid {
+                st.info.reduce_percent = p.reduce_percent.round() as u8;
+            }
+
             // Battery saver runtime (updated continuously)
             st.info.battery_percent = battery_percent;
-            st.info.battery_saver_active = bs_active;
-            st.info.battery_saver_override = bs_override;
-            st.info.battery_saver_disabled_cores = bs_disabled_cores.iter().map(|&x| x as u8).collect();
-            st.info.battery_saver_reapply_in_sec = bs_reapply_in;
-            st.info.screen_off_core_saver_active = screen_off_saver_active;
-            st.info.screen_off_core_saver_disabled_cores = offline_by_screen_off.iter().map(|&x| x as u8).collect();
+            st.info.battery_saver_active = d.battery_saver.active;
+            st.info.battery_saver_override = d.battery_saver.override_on;
+            st.info.battery_saver_disabled_cores = d.battery_saver.disabled_cores.iter().map(|&x| x as u8).collect();
+            st.info.battery_saver_reapply_in_sec = d.battery_saver.reapply_in_sec;
+            st.info.screen_off_core_saver_active = !d.screen_off_saver_cores.is_empty();
+            st.info.screen_off_core_saver_disabled_cores = d.screen_off_saver_cores.iter().map(|&x| x as u8).collect();
             st.info.split_charge_active = split_charge_status.active;
             st.info.split_charge_package = split_charge_status.package.clone();
             st.info.split_charge_node = split_charge_status.node.clone();
             st.info.split_charge_stop_battery_percent = split_charge_status.target_stop_battery_percent;
             st.info.split_charge_last_error = split_charge_status.last_error.clone();
 
+            // Trace recorder
+            st.info.trace_active = trace.is_active();
+            st.info.trace_bytes = trace.written();
+            st.info.trace_last_error = trace.last_error();
+
             // Profiles / LED state (updated continuously)
-            st.info.active_profile = active_prof.name.clone();
-            st.info.led_profile = led_sel.source.clone();
+            st.info.active_profile = d.profile.clone();
+            st.info.profile_rule = d.profile_rule.clone();
+            st.info.app_rule = d.app_rule.clone();
+            st.info.schedule = d.schedule.clone();
+            st.info.led_profile = d.led.source.clone();
             st.leds.base_external_desired = ext_des.clone();
             st.leds.base_external_last_applied = ext_last.clone();
             st.leds.fan_desired = fan_des;
             st.leds.fan_last_applied = fan_last;
         }
 
-        // sleep selection
-        if any_write || any_step { stable_for = Duration::ZERO; } else { stable_for += dt; }
-
-        let sleep_ms = match zone {
-            TempZone::B56 | TempZone::B57 | TempZone::B58 => 450,
-            _ => {
-                if idle_mode {
-                    15000
-                } else if any_write || any_step {
-                    750
-                } else if game_mode || charging_effective {
-                    if stable_for >= Duration::from_secs(30) { 3000 } else { 1500 }
-                } else if !screen_on {
-                    12000
-                } else if stable_for >= Duration::from_secs(60) {
-                    8000
-                } else if stable_for >= Duration::from_secs(30) {
-                    5000
-                } else {
-                    2000
-                }
-            }
-        };
-
-        std::thread::sleep(Duration::from_millis(sleep_ms));
+        // A maintenance run steps levels every few seconds; screen-off sleeps are far longer.
+        let sleep_ms = if maintenance.running() { d.sleep_ms.min(1000) } else { d.sleep_ms };
+        shutdown::sleep(Duration::from_millis(sleep_ms));
     }
+
+    // SIGTERM/SIGINT: give native touch and charging back first, then every node we wrote,
+    // then stock thermal.
+    println!("mora_perf_daemon stopping");
+    drop(triggers);
+    drop(split_charge);
+    shutdown::restore_journal();
+    takeover.release();
+    println!("mora_perf_daemon stopped");
 }
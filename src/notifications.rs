@@ -1,4 +1,4 @@
-use crate::{leds::Leds, state::SharedState, user_config::NotificationsStopKind};
+use crate::{clock, leds::Leds, profiles::active_schedule, state::SharedState, sysfs, user_config::NotificationsStopKind};
 use std::{
     collections::HashSet,
     fs,
@@ -36,6 +36,10 @@ fn pkg_from_line(line: &str) -> Option<&str> {
 }
 
 fn run_notification_list(cmd: &str, su: &str) -> Result<String, String> {
+    // No framework on a relocated (fake) device tree: nothing ever gets posted.
+    if sysfs::is_relocated() {
+        return Ok(String::new());
+    }
 
     // 1) Preferred (tested): shell UID 2000
     let out = Command::new(su)
@@ -128,8 +132,10 @@ pub fn spawn(shared: Arc<RwLock<SharedState>>, leds: Arc<Leds>) {
             let (enabled, stop_kind_cfg, for_seconds, ext_setting, screen_on) = {
                 let s = shared.read().unwrap();
                 let n = &s.config.notifications;
+                // Quiet hours with LEDs off: treated as disabled.
+                let quiet = active_schedule(&s.config, clock::minute_of_day(), s.info.game_mode).is_some_and(|q| q.leds_off);
                 (
-                    n.enabled,
+                    n.enabled && !quiet,
                     n.stop_condition.kind,
                     n.for_seconds.max(1),
                     n.external_led.clone(),
@@ -2,11 +2,10 @@
 use std::{
     fs,
     os::unix::fs::PermissionsExt,
-    path::Path,
     process::Command,
 };
 
-use crate::config::{ICON_DST, ICON_URI};
+use crate::{config::{ICON_DST, ICON_URI}, sysfs};
 
 const ICON_BYTES: &[u8] = include_bytes!("assets/mora.png");
 
@@ -15,24 +14,28 @@ fn sh_escape_single_quotes(s: &str) -> String {
 }
 
 pub fn ensure_icon_on_disk() {
-    let dst = Path::new(ICON_DST);
+    let dst = sysfs::path(ICON_DST);
 
-    let need_write = match fs::metadata(dst) {
+    let need_write = match fs::metadata(&dst) {
         Ok(m) => m.len() != ICON_BYTES.len() as u64,
         Err(_) => true,
     };
 
     if need_write {
-        if let Err(e) = fs::write(dst, ICON_BYTES) {
+        if let Err(e) = fs::write(&dst, ICON_BYTES) {
             println!("NOTIFY: icon write fail ({})", e);
             return;
         }
-        let _ = fs::set_permissions(dst, fs::Permissions::from_mode(0o644));
+        let _ = fs::set_permissions(&dst, fs::Permissions::from_mode(0o644));
         println!("NOTIFY: icon ready ({})", ICON_DST);
     }
 }
 
 pub fn post_notification(message: &str) {
+    if sysfs::is_relocated() {
+        println!("NOTIFY: {}", message);
+        return;
+    }
     let msg = sh_escape_single_quotes(message);
 
     let cmd = format!(
@@ -0,0 +1,196 @@
+//! Closed-loop thermal controller (`thermal_control.mode = "pid"`).
+//!
+//! Instead of jumping between curve bands, the PID holds the control temperature at a
+//! setpoint and outputs a continuous reduction of every domain's max frequency.
+
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::{domain::ThermalLimit, user_config::PidConfig};
+
+/// Derivative low-pass factor (sysfs temps are quantized, raw dT/dt is noisy).
+const D_FILTER_ALPHA: f32 = 0.3;
+/// Gaps longer than this (idle/suspend) restart the derivative from scratch.
+const MAX_DT: Duration = Duration::from_secs(30);
+
+/// One PID update, exposed in decisions / `/api/state`.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct PidStatus {
+    pub setpoint_mc: i32,
+    pub error_mc: i32,
+    pub p: f32,
+    pub i: f32,
+    pub d: f32,
+    /// Reduction applied to every domain's max frequency (0..max_reduce_percent).
+    pub reduce_percent: f32,
+    /// Output pinned at 0 or at the configured maximum.
+    pub saturated: bool,
+    #[serde(skip)]
+    pub max_step_down: u8,
+}
+
+impl PidStatus {
+    pub fn limit(&self) -> ThermalLimit {
+        ThermalLimit {
+            keep_permille: (1000.0 - self.reduce_percent * 10.0).round().clamp(0.0, 1000.0) as u32,
+            max_step_down: self.max_step_down.max(1) as usize,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ThermalPid {
+    /// Accumulated error (°C·s), clamped so the I term stays within the output range.
+    integral: f32,
+    last_temp_c: Option<f32>,
+    d_filtered: f32,
+    last: Option<PidStatus>,
+}
+
+/// Lowest setpoint among the states that apply (charging while gaming uses the stricter one).
+pub fn setpoint_mc(cfg: &PidConfig, game_mode: bool, charging: bool) -> i32 {
+    let mut sp = cfg.setpoint_normal_c;
+    if game_mode {
+        sp = cfg.setpoint_gaming_c;
+    }
+    if charging {
+        sp = if game_mode { sp.min(cfg.setpoint_charging_c) } else { cfg.setpoint_charging_c };
+    }
+    (sp * 1000.0).round() as i32
+}
+
+impl ThermalPid {
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Last output, used when the control temperature can't be read this iteration.
+    pub fn last(&self) -> Option<PidStatus> {
+        self.last
+    }
+
+    pub fn update(&mut self, temp_mc: i32, setpoint_mc: i32, cfg: &PidConfig, dt: Duration) -> PidStatus {
+        let max_out = cfg.max_reduce_percent as f32 / 100.0;
+        let temp_c = temp_mc as f32 / 1000.0;
+        let err = (temp_mc - setpoint_mc) as f32 / 1000.0;
+        let dt_s = dt.as_secs_f32();
+
+        // Derivative on measurement: a setpoint switch (game/charging) doesn't kick the output.
+        if dt > MAX_DT {
+            self.last_temp_c = None;
+            self.d_filtered = 0.0;
+        }
+        if let Some(prev) = self.last_temp_c {
+            if dt_s > 0.0 {
+                let rate = (temp_c - prev) / dt_s;
+                self.d_filtered += D_FILTER_ALPHA * (rate - self.d_filtered);
+            }
+        }
+        self.last_temp_c = Some(temp_c);
+
+        let p = cfg.kp * err;
+        let d = cfg.kd * self.d_filtered;
+
+        // Anti-windup: integrate only while that doesn't push further into saturation,
+        // and keep the I term itself within [0, max_out].
+        let unsat = p + cfg.ki * self.integral + d;
+        let pushing_high = unsat >= max_out && err > 0.0;
+        let pushing_low = unsat <= 0.0 && err < 0.0;
+        if !pushing_high && !pushing_low && dt <= MAX_DT {
+            self.integral += err * dt_s;
+        }
+        if cfg.ki > 0.0 {
+            self.integral = self.integral.clamp(0.0, max_out / cfg.ki);
+        } else {
+            self.integral = 0.0;
+        }
+        let i = cfg.ki * self.integral;
+
+        let raw = p + i + d;
+        let out = raw.clamp(0.0, max_out);
+        let st = PidStatus {
+            setpoint_mc,
+            error_mc: temp_mc - setpoint_mc,
+            p,
+            i,
+            d,
+            reduce_percent: out * 100.0,
+            saturated: raw <= 0.0 || raw >= max_out,
+            max_step_down: cfg.max_step_down,
+        };
+        self.last = Some(st);
+        st
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP: Duration = Duration::from_secs(1);
+
+    fn cfg() -> PidConfig {
+        PidConfig { kd: 0.0, ..PidConfig::default() }
+    }
+
+    #[test]
+    fn integral_stops_at_saturation() {
+        // 8 °C over: P alone gives 32 %, the I term fills the rest up to 75 % and stops there.
+        let cfg = cfg();
+        let mut pid = ThermalPid::default();
+        let mut st = PidStatus::default();
+        for _ in 0..1000 {
+            st = pid.update(60_000, 52_000, &cfg, STEP);
+        }
+        assert!(st.saturated);
+        assert_eq!(st.reduce_percent, cfg.max_reduce_percent as f32);
+        let i = st.i;
+        assert!(st.p + i < 0.75 + cfg.ki * 8.0 + 1e-4, "I term overshot the ceiling: {}", i);
+        for _ in 0..3600 {
+            st = pid.update(60_000, 52_000, &cfg, STEP);
+        }
+        assert_eq!(st.i, i, "integral kept growing while saturated");
+    }
+
+    #[test]
+    fn releases_after_long_saturation() {
+        let cfg = cfg();
+        let mut pid = ThermalPid::default();
+        for _ in 0..3600 {
+            pid.update(60_000, 52_000, &cfg, STEP);
+        }
+        // Only the I term built up to the ceiling is left: without anti-windup it would sit at
+        // max_out / ki and hold ~71 % here.
+        let st = pid.update(51_000, 52_000, &cfg, STEP);
+        assert!(!st.saturated);
+        assert!(st.reduce_percent < 45.0, "{}%", st.reduce_percent);
+    }
+
+    #[test]
+    fn integral_does_not_go_negative() {
+        let cfg = cfg();
+        let mut pid = ThermalPid::default();
+        for _ in 0..600 {
+            pid.update(40_000, 52_000, &cfg, STEP);
+        }
+        let st = pid.update(53_000, 52_000, &cfg, STEP);
+        assert!(st.reduce_percent > 0.0, "wound up below the setpoint");
+    }
+
+    #[test]
+    fn long_gap_is_not_integrated() {
+        let cfg = cfg();
+        let mut pid = ThermalPid::default();
+        let st = pid.update(60_000, 52_000, &cfg, Duration::from_secs(600));
+        assert_eq!(st.i, 0.0);
+    }
+
+    #[test]
+    fn charging_in_game_takes_lower_setpoint() {
+        let cfg = PidConfig::default();
+        assert_eq!(setpoint_mc(&cfg, true, false), 55_000);
+        assert_eq!(setpoint_mc(&cfg, true, true), 50_000);
+        assert_eq!(setpoint_mc(&cfg, false, false), 52_000);
+    }
+}
@@ -1,5 +1,5 @@
 
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 
 use crate::sysfs;
 
@@ -12,12 +12,12 @@ pub struct ChargeProbe {
 
 impl ChargeProbe {
     pub fn detect() -> Option<Self> {
-        let base = Path::new("/sys/class/power_supply");
+        let base = sysfs::path("/sys/class/power_supply");
         let mut online_paths = Vec::new();
         let mut battery_status_path = None;
         let mut battery_capacity_path = None;
 
-        let entries = std::fs::read_dir(base).ok()?;
+        let entries = std::fs::read_dir(&base).ok()?;
         for e in entries.flatten() {
             let p = e.path();
             if !p.is_dir() {
@@ -1,8 +1,10 @@
 
 use std::collections::HashMap;
 
+use crate::sysfs;
+
 fn read_total_cpu_jiffies() -> Option<u64> {
-    let s = std::fs::read_to_string("/proc/stat").ok()?;
+    let s = std::fs::read_to_string(sysfs::path("/proc/stat")).ok()?;
     for line in s.lines() {
         if line.starts_with("cpu ") {
             let mut sum = 0u64;
@@ -61,7 +63,7 @@ impl ProcWatch {
         let mut new_map: HashMap<u32, u64> = HashMap::new();
         let mut best: Option<(u32, String, u64)> = None; // pid, comm, dticks
 
-        if let Ok(entries) = std::fs::read_dir("/proc") {
+        if let Ok(entries) = std::fs::read_dir(sysfs::path("/proc")) {
             for e in entries.flatten() {
                 let name = match e.file_name().into_string() {
                     Ok(s) => s,
@@ -72,7 +74,7 @@ impl ProcWatch {
                     Err(_) => continue,
                 };
 
-                let stat_path = format!("/proc/{}/stat", pid);
+                let stat_path = sysfs::path(format!("/proc/{}/stat", pid));
                 let line = match std::fs::read_to_string(&stat_path) {
                     Ok(s) => s,
                     Err(_) => continue,
@@ -1,8 +1,18 @@
-use crate::user_config::{ExternalLedSetting, FanLedSetting, ProfileConfig, ProfileType, UserConfig};
+use serde::Serialize;
 
-#[derive(Clone, Debug)]
+use crate::{
+    clock,
+    games::AppEntry,
+    user_config::{
+        BatterySaverConfig, DomainTunables, DomainTuningConfig, ExternalLedSetting, FanLedSetting, PerfPreset,
+        ProfileConditions, ProfileConfig, ProfileFanConfig, ProfileType, ScheduleConfig, ThermalCurveConfig,
+        UserConfig,
+    },
+};
+
+#[derive(Clone, Debug, Serialize)]
 pub struct BaseLedSelection {
-    /// Human readable source: "Charging" / "Gaming" / "Normal" / "Off"
+    /// Human readable source: "Schedule: <name>" / "Charging" / "App: <pkg>" / custom profile name / "Gaming" / "Normal" / "Off"
     pub source: String,
     pub fan: Option<FanLedSetting>,
     pub external: Option<ExternalLedSetting>,
@@ -31,14 +41,240 @@ pub fn get_gaming_profile(cfg: &UserConfig) -> ProfileConfig {
         .unwrap_or_else(ProfileConfig::gaming_default)
 }
 
-pub fn select_active_mode_profile(cfg: &UserConfig, game_mode: bool) -> ProfileConfig {
-    if game_mode {
-        let g = get_gaming_profile(cfg);
-        if g.enabled {
-            return g;
+/// State custom-profile rules are matched against.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RuleContext<'a> {
+    pub pkg: Option<&'a str>,
+    pub game_mode: bool,
+    /// Hardware charging state.
+    pub charging: bool,
+    pub battery_percent: Option<u8>,
+    pub screen_on: bool,
+    pub minute_of_day: Option<u16>,
+    /// Thermal band of the previous iteration (the curve itself may come from the profile).
+    pub band: u8,
+}
+
+/// `*` matches any run of characters, everything else literally.
+fn wildcard_match(pattern: &str, s: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = s.strip_prefix(parts.next().unwrap_or("")) else { return false };
+    let mut parts: Vec<&str> = parts.collect();
+    let Some(last) = parts.pop() else { return rest.is_empty() };
+    for p in parts {
+        match rest.find(p) {
+            Some(i) => rest = &rest[i + p.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+fn range_contains(min: Option<u8>, max: Option<u8>, v: u8) -> bool {
+    min.is_none_or(|m| v >= m) && max.is_none_or(|m| v <= m)
+}
+
+pub fn conditions_match(w: &ProfileConditions, ctx: &RuleContext) -> bool {
+    let pkg_ok = w.packages.is_empty() || ctx.pkg.is_some_and(|pkg| w.packages.iter().any(|p| wildcard_match(p.trim(), pkg)));
+    let battery_ok = (w.battery_min.is_none() && w.battery_max.is_none())
+        || ctx.battery_percent.is_some_and(|v| range_contains(w.battery_min, w.battery_max, v));
+    let time_ok = match &w.time {
+        None => true,
+        Some(t) => ctx.minute_of_day.is_some_and(|m| t.contains(m)),
+    };
+    pkg_ok
+        && battery_ok
+        && time_ok
+        && w.game_mode.is_none_or(|v| v == ctx.game_mode)
+        && w.charging.is_none_or(|v| v == ctx.charging)
+        && w.screen_on.is_none_or(|v| v == ctx.screen_on)
+        && range_contains(w.band_min, w.band_max, ctx.band)
+}
+
+/// Short human readable form of a rule, e.g. `pkg org.telegram.*, charging, battery 20..50%`.
+pub fn describe_conditions(w: &ProfileConditions) -> String {
+    let range = |min: Option<u8>, max: Option<u8>| {
+        format!("{}..{}", min.map(|v| v.to_string()).unwrap_or_default(), max.map(|v| v.to_string()).unwrap_or_default())
+    };
+    let flag = |v: Option<bool>, on: &str, off: &str| v.map(|v| if v { on.to_string() } else { off.to_string() });
+    let parts: Vec<String> = [
+        (!w.packages.is_empty()).then(|| format!("pkg {}", w.packages.join("|"))),
+        flag(w.game_mode, "game", "no game"),
+        flag(w.charging, "charging", "not charging"),
+        (w.battery_min.is_some() || w.battery_max.is_some()).then(|| format!("battery {}%", range(w.battery_min, w.battery_max))),
+        flag(w.screen_on, "screen on", "screen off"),
+        w.time.as_ref().map(|t| format!("{}..{}", t.from, t.to)),
+        (w.band_min.is_some() || w.band_max.is_some()).then(|| format!("band {}", range(w.band_min, w.band_max))),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    if parts.is_empty() { "always".to_string() } else { parts.join(", ") }
+}
+
+/// Gaming (in game, if enabled) or Normal, unless an enabled custom profile whose rule matches
+/// has a higher `priority`. Ties keep the built-in profile, then the first custom one listed.
+/// Returns the profile and, for a custom one, its rule.
+pub fn select_active_mode_profile(cfg: &UserConfig, ctx: &RuleContext) -> (ProfileConfig, Option<String>) {
+    let builtin = match get_gaming_profile(cfg) {
+        g if ctx.game_mode && g.enabled => g,
+        _ => get_normal_profile(cfg),
+    };
+    let custom = cfg
+        .profiles
+        .iter()
+        .filter(|p| p.enabled && p.profile_type == ProfileType::Custom && p.priority > builtin.priority)
+        .filter_map(|p| p.when.as_ref().filter(|w| conditions_match(w, ctx)).map(|w| (p, w)))
+        .fold(None::<(&ProfileConfig, &ProfileConditions)>, |best, c| match best {
+            Some(b) if b.0.priority >= c.0.priority => Some(b),
+            _ => Some(c),
+        });
+    match custom {
+        Some((p, w)) => (p.clone(), Some(describe_conditions(w))),
+        None => (builtin, None),
+    }
+}
+
+/// First enabled schedule whose window contains `minute_of_day`.
+pub fn active_schedule(cfg: &UserConfig, minute_of_day: Option<u16>, game_mode: bool) -> Option<&ScheduleConfig> {
+    let m = minute_of_day?;
+    cfg.schedules
+        .iter()
+        .find(|s| s.enabled && !(game_mode && s.skip_in_games) && s.time.contains(m))
+}
+
+/// `daemon_notifications` is on and no schedule mutes them right now.
+pub fn daemon_notifications_allowed(cfg: &UserConfig, game_mode: bool) -> bool {
+    cfg.daemon_notifications
+        && !active_schedule(cfg, clock::minute_of_day(), game_mode).is_some_and(|s| s.mute_notifications)
+}
+
+/// Built-in overrides of a preset; Balanced has none.
+pub fn preset_profile(preset: PerfPreset) -> Option<ProfileConfig> {
+    let (name, all, fan_offset_c, battery_saver, sleep_scale) = match preset {
+        PerfPreset::Balanced => return None,
+        PerfPreset::Battery => (
+            "Battery",
+            DomainTunables {
+                up_util: Some(80),
+                spike_delta2: Some(30),
+                spike_delta4: Some(45),
+                down_after_ms: Some([3000, 1500]),
+                max_ratio: Some(0.8),
+                ..DomainTunables::default()
+            },
+            -3.0,
+            true,
+            1.5,
+        ),
+        PerfPreset::Performance => (
+            "Performance",
+            DomainTunables {
+                up_util: Some(60),
+                spike_delta2: Some(15),
+                spike_delta4: Some(25),
+                down_after_ms: Some([9000, 5000]),
+                ..DomainTunables::default()
+            },
+            5.0,
+            false,
+            0.75,
+        ),
+    };
+    Some(ProfileConfig {
+        domain_tuning: Some(DomainTuningConfig { all, ..DomainTuningConfig::default() }),
+        fan: Some(ProfileFanConfig { temp_offset_c: fan_offset_c, ..ProfileFanConfig::default() }),
+        battery_saver: Some(BatterySaverConfig { enabled: battery_saver, ..BatterySaverConfig::default() }),
+        sleep_scale: Some(sleep_scale),
+        ..ProfileConfig::empty(name, ProfileType::Custom)
+    })
+}
+
+/// Overrides of an app rule as a profile layer named after the package.
+pub fn app_profile(app: &AppEntry) -> ProfileConfig {
+    ProfileConfig {
+        domain_tuning: app.domain_tuning.clone(),
+        governors: app.governors.clone(),
+        thermal_curve: app.thermal_curve.clone(),
+        fan: app.fan.clone(),
+        fan_led: app.fan_led.clone(),
+        external_led: app.external_led.clone(),
+        sleep_scale: app.sleep_scale,
+        ..ProfileConfig::empty(&app.package, ProfileType::Custom)
+    }
+}
+
+/// Performance knobs in effect: the active mode profile, the foreground app's rule, then the
+/// preset.
+#[derive(Clone, Debug)]
+pub struct ActivePerf {
+    pub profile: ProfileConfig,
+    /// Rule that selected a custom `profile`.
+    pub rule: Option<String>,
+    pub app: Option<ProfileConfig>,
+    pub preset: Option<ProfileConfig>,
+}
+
+impl ActivePerf {
+    /// Profile first, preset last; for replaced values the last one set wins.
+    fn layers(&self) -> impl Iterator<Item = &ProfileConfig> {
+        std::iter::once(&self.profile).chain(self.app.as_ref()).chain(self.preset.as_ref())
+    }
+
+    /// `domain_tuning` layers to stack over the global one.
+    pub fn domain_tuning(&self) -> impl Iterator<Item = &DomainTuningConfig> {
+        self.layers().filter_map(|p| p.domain_tuning.as_ref())
+    }
+
+    pub fn governor(&self, label: &str) -> Option<&str> {
+        self.layers().filter_map(|p| p.governors.get(label)).last().map(String::as_str)
+    }
+
+    pub fn thermal_curve<'a>(&'a self, cfg: &'a UserConfig) -> &'a ThermalCurveConfig {
+        self.layers()
+            .filter_map(|p| p.thermal_curve.as_ref())
+            .last()
+            .unwrap_or(&cfg.thermal_curve)
+    }
+
+    pub fn battery_saver_enabled(&self, cfg: &UserConfig) -> bool {
+        self.layers()
+            .filter_map(|p| p.battery_saver.as_ref())
+            .last()
+            .unwrap_or(&cfg.battery_saver)
+            .enabled
+    }
+
+    /// Offsets add up; the higher minimum and the lower maximum win; curves are replaced.
+    pub fn fan(&self) -> ProfileFanConfig {
+        let mut out = ProfileFanConfig::default();
+        for f in self.layers().filter_map(|p| p.fan.as_ref()) {
+            out.temp_offset_c += f.temp_offset_c;
+            out.min_level = out.min_level.max(f.min_level);
+            out.max_level = match (out.max_level, f.max_level) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+            out.soc = f.soc.clone().or(out.soc);
+            out.battery = f.battery.clone().or(out.battery);
+            out.game = f.game.clone().or(out.game);
         }
+        out
+    }
+
+    pub fn sleep_scale(&self) -> f32 {
+        self.layers().filter_map(|p| p.sleep_scale).product()
+    }
+}
+
+pub fn select_active_perf(cfg: &UserConfig, ctx: &RuleContext, app: Option<&AppEntry>) -> ActivePerf {
+    let (profile, rule) = select_active_mode_profile(cfg, ctx);
+    ActivePerf {
+        profile,
+        rule,
+        app: app.map(app_profile),
+        preset: preset_profile(cfg.perf_preset),
     }
-    get_normal_profile(cfg)
 }
 
 /// LED selection following full_rgb.sh behavior:
@@ -48,10 +284,22 @@ pub fn select_active_mode_profile(cfg: &UserConfig, game_mode: bool) -> ProfileC
 /// - When applying OUT, the daemon must re-apply FAN (handled in leds.rs).
 pub fn select_base_led(
     cfg: &UserConfig,
+    active: &ProfileConfig,
+    app: Option<&ProfileConfig>,
+    schedule: Option<&ScheduleConfig>,
     _screen_on: bool,
     charging_effective: bool,
     game_mode: bool,
 ) -> BaseLedSelection {
+    // 0) Quiet hours: everything off, charging included.
+    if let Some(s) = schedule.filter(|s| s.leds_off) {
+        return BaseLedSelection {
+            source: format!("Schedule: {}", s.name),
+            fan: None,
+            external: None,
+        };
+    }
+
     // 1) Charging overrides LED output (but must not disable game mode itself).
     if charging_effective {
         let fan = cfg.charging.fan_led.clone();
@@ -66,7 +314,25 @@ pub fn select_base_led(
         // Charging enabled but no LED settings -> fall through.
     }
 
-    // 2) Gaming
+    // 2) Foreground app rule, if it sets LEDs
+    if let Some(a) = app.filter(|a| a.fan_led.is_some() || a.external_led.is_some()) {
+        return BaseLedSelection {
+            source: format!("App: {}", a.name),
+            fan: a.fan_led.clone(),
+            external: a.external_led.clone(),
+        };
+    }
+
+    // 3) Custom profile selected by its rule, if it sets LEDs
+    if active.profile_type == ProfileType::Custom && (active.fan_led.is_some() || active.external_led.is_some()) {
+        return BaseLedSelection {
+            source: active.name.clone(),
+            fan: active.fan_led.clone(),
+            external: active.external_led.clone(),
+        };
+    }
+
+    // 4) Gaming
     let g = get_gaming_profile(cfg);
     if game_mode && g.enabled {
         let fan = g.fan_led.clone();
@@ -80,7 +346,7 @@ pub fn select_base_led(
         }
     }
 
-    // 3) Normal
+    // 5) Normal
     // NOTE: On this device the LED driver can be slow and the daemon may overwrite
     // manual changes. Also, tying LEDs to `screen_on` caused confusing behavior
     // ("nothing works" when the screen state toggles or is detected incorrectly).
@@ -104,3 +370,101 @@ pub fn select_base_led(
         external: None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user_config::TimeWindow;
+    use serde_json::json;
+
+    #[test]
+    fn wildcard() {
+        assert!(wildcard_match("com.android.chrome", "com.android.chrome"));
+        assert!(!wildcard_match("com.android.chrome", "com.android.chrome.beta"));
+        assert!(wildcard_match("org.telegram.*", "org.telegram.messenger"));
+        assert!(!wildcard_match("org.telegram.*", "org.telegramx"));
+        assert!(wildcard_match("*", "anything"));
+        assert!(wildcard_match("*.game", "com.example.game"));
+        assert!(wildcard_match("com.*.game*", "com.example.game.beta"));
+        // Parts can't overlap: two `x` are needed.
+        assert!(!wildcard_match("*x*x", "x"));
+        assert!(wildcard_match("a*b*b", "abb"));
+        assert!(!wildcard_match("a*bc*c", "abc"));
+    }
+
+    fn custom(name: &str, priority: i32, when: serde_json::Value) -> ProfileConfig {
+        let mut v = serde_json::to_value(get_normal_profile(&UserConfig::default())).unwrap();
+        v["name"] = json!(name);
+        v["type"] = json!("custom");
+        v["priority"] = json!(priority);
+        v["enabled"] = json!(true);
+        v["when"] = when;
+        serde_json::from_value(v).unwrap()
+    }
+
+    fn ctx(pkg: Option<&str>, minute_of_day: Option<u16>) -> RuleContext<'_> {
+        RuleContext { pkg, screen_on: true, minute_of_day, ..RuleContext::default() }
+    }
+
+    #[test]
+    fn time_rule_wraps_past_midnight() {
+        let mut cfg = UserConfig::default();
+        cfg.profiles.push(custom("Night", 5, json!({ "time": { "from": "22:00", "to": "07:00" } })));
+        let name = |m| select_active_mode_profile(&cfg, &ctx(None, m)).0.name;
+        assert_eq!(name(Some(23 * 60)), "Night");
+        assert_eq!(name(Some(3 * 60)), "Night");
+        assert_eq!(name(Some(7 * 60)), "Normal");
+        assert_eq!(name(Some(12 * 60)), "Normal");
+        // Unknown clock: a time rule never matches.
+        assert_eq!(name(None), "Normal");
+    }
+
+    #[test]
+    fn highest_priority_match_wins() {
+        let mut cfg = UserConfig::default();
+        cfg.profiles.push(custom("Reading", 5, json!({ "packages": ["org.telegram.*"] })));
+        cfg.profiles.push(custom("Chat", 7, json!({ "packages": ["org.telegram.messenger"] })));
+        cfg.profiles.push(custom("Low", 0, json!({ "packages": ["*"] })));
+        let pick = |pkg| select_active_mode_profile(&cfg, &ctx(Some(pkg), None));
+        let (p, rule) = pick("org.telegram.messenger");
+        assert_eq!(p.name, "Chat");
+        assert!(rule.is_some());
+        assert_eq!(pick("org.telegram.plus").0.name, "Reading");
+        // Below Normal's priority: never picked.
+        assert_eq!(pick("com.other").0.name, "Normal");
+    }
+
+    fn schedule(name: &str, from: &str, to: &str) -> ScheduleConfig {
+        ScheduleConfig {
+            name: name.to_string(),
+            time: TimeWindow { from: from.to_string(), to: to.to_string() },
+            ..ScheduleConfig::default()
+        }
+    }
+
+    #[test]
+    fn schedule_across_midnight() {
+        let cfg = UserConfig {
+            schedules: vec![schedule("Night", "23:00", "07:00"), schedule("Lunch", "12:00", "13:00")],
+            ..UserConfig::default()
+        };
+        let active = |m, game| active_schedule(&cfg, Some(m), game).map(|s| s.name.clone());
+        assert_eq!(active(23 * 60 + 30, false).as_deref(), Some("Night"));
+        assert_eq!(active(6 * 60 + 59, false).as_deref(), Some("Night"));
+        assert_eq!(active(7 * 60, false), None);
+        assert_eq!(active(12 * 60 + 30, false).as_deref(), Some("Lunch"));
+        // skip_in_games (default on) pauses it in a game.
+        assert_eq!(active(0, true), None);
+        assert_eq!(active_schedule(&cfg, None, false).map(|s| s.name.as_str()), None);
+    }
+
+    #[test]
+    fn first_enabled_schedule_wins() {
+        let off = ScheduleConfig { enabled: false, ..schedule("Off", "00:00", "00:00") };
+        let cfg = UserConfig {
+            schedules: vec![off, schedule("All day", "00:00", "00:00"), schedule("Night", "23:00", "07:00")],
+            ..UserConfig::default()
+        };
+        assert_eq!(active_schedule(&cfg, Some(60), false).map(|s| s.name.as_str()), Some("All day"));
+    }
+}
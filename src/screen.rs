@@ -11,13 +11,13 @@ pub enum ScreenProbe {
 }
 
 pub fn detect_screen_probe() -> Option<ScreenProbe> {
-    let fb_blank = PathBuf::from("/sys/class/graphics/fb0/blank");
+    let fb_blank = sysfs::path("/sys/class/graphics/fb0/blank");
     if fb_blank.exists() {
         return Some(ScreenProbe::FbBlank(fb_blank));
     }
 
-    let bl_dir = std::path::Path::new("/sys/class/backlight");
-    if let Ok(entries) = std::fs::read_dir(bl_dir) {
+    let bl_dir = sysfs::path("/sys/class/backlight");
+    if let Ok(entries) = std::fs::read_dir(&bl_dir) {
         for e in entries.flatten() {
             let p = e.path();
             let bright = p.join("brightness");
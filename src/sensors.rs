@@ -0,0 +1,333 @@
+//! Thermal sensor sanity filtering.
+//!
+//! Every zone the daemon reads is tracked individually: missing reads, implausible values,
+//! values stuck while the rest of the SoC moves, and outliers against their group's median
+//! are excluded from the averages/hotspots, and re-included after a clean streak.
+//! A high outlier may be a real runaway zone, so it stays `suspect`: out of the control
+//! temperature, but still seen by the critical layer (`hotspot_mc`).
+
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::{
+    sysfs,
+    thermal::{zone_temp_path, Zone, ZoneMap},
+    user_config::SensorHealthConfig,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SensorStatus {
+    Ok,
+    Missing,
+    OutOfRange,
+    Stuck,
+    Outlier,
+    /// Passing checks again, waiting out `recover_s` before re-inclusion.
+    Recovering,
+}
+
+impl SensorStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SensorStatus::Ok => "ok",
+            SensorStatus::Missing => "missing",
+            SensorStatus::OutOfRange => "out_of_range",
+            SensorStatus::Stuck => "stuck",
+            SensorStatus::Outlier => "outlier",
+            SensorStatus::Recovering => "recovering",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SensorHealth {
+    pub id: u32,
+    #[serde(rename = "type")]
+    pub ty: String,
+    /// `cpu`, `gpu`, `battery` or a domain label for domain-only zones.
+    pub group: String,
+    pub value_mc: Option<i32>,
+    pub status: SensorStatus,
+    pub trusted: bool,
+    /// Excluded high outlier, still fed to the critical layer.
+    pub suspect: bool,
+}
+
+struct Track {
+    health: SensorHealth,
+    flat_value: Option<i32>,
+    flat_since: Option<Instant>,
+    /// Range of the SoC median seen while this sensor stayed flat.
+    peer_lo: i32,
+    peer_hi: i32,
+    good_since: Option<Instant>,
+}
+
+impl Track {
+    fn new(z: &Zone, group: &str) -> Self {
+        Self {
+            health: SensorHealth {
+                id: z.id,
+                ty: z.ty.clone(),
+                group: group.to_string(),
+                value_mc: None,
+                status: SensorStatus::Ok,
+                trusted: true,
+                suspect: false,
+            },
+            flat_value: None,
+            flat_since: None,
+            peer_lo: 0,
+            peer_hi: 0,
+            good_since: None,
+        }
+    }
+
+    fn flat_for(&mut self, v: i32, soc_median: Option<i32>, now: Instant) -> (Duration, i32) {
+        if self.flat_value != Some(v) {
+            self.flat_value = Some(v);
+            self.flat_since = Some(now);
+            self.peer_lo = soc_median.unwrap_or(0);
+            self.peer_hi = self.peer_lo;
+        } else if let Some(m) = soc_median {
+            self.peer_lo = self.peer_lo.min(m);
+            self.peer_hi = self.peer_hi.max(m);
+        }
+        let since = self.flat_since.unwrap_or(now);
+        (now.duration_since(since), self.peer_hi - self.peer_lo)
+    }
+}
+
+fn median(mut v: Vec<i32>) -> Option<i32> {
+    if v.is_empty() {
+        return None;
+    }
+    v.sort_unstable();
+    let n = v.len();
+    Some(if n % 2 == 1 { v[n / 2] } else { (v[n / 2 - 1] + v[n / 2]) / 2 })
+}
+
+pub struct SensorFilter {
+    tracks: Vec<Track>,
+}
+
+impl SensorFilter {
+    /// Track every zone referenced by the map (each id once, first group wins).
+    pub fn new(map: &ZoneMap) -> Self {
+        let mut tracks: Vec<Track> = Vec::new();
+        let mut add = |z: &Zone, group: &str| {
+            if !tracks.iter().any(|t| t.health.id == z.id) {
+                tracks.push(Track::new(z, group));
+            }
+        };
+        map.cpu.iter().for_each(|z| add(z, "cpu"));
+        map.gpu.iter().for_each(|z| add(z, "gpu"));
+        map.battery.iter().for_each(|z| add(z, "battery"));
+        for d in &map.domains {
+            d.zones.iter().for_each(|z| add(z, &d.label));
+        }
+        Self { tracks }
+    }
+
+    /// Read all sensors and update their health.
+    pub fn update(&mut self, cfg: &SensorHealthConfig, now: Instant) {
+        for t in &mut self.tracks {
+            t.health.value_mc = sysfs::read_i32(&zone_temp_path(t.health.id));
+        }
+        self.evaluate(cfg, now);
+    }
+
+    fn evaluate(&mut self, cfg: &SensorHealthConfig, now: Instant) {
+        let (min_mc, max_mc) = ((cfg.min_c * 1000.0) as i32, (cfg.max_c * 1000.0) as i32);
+        let in_range = |v: Option<i32>| v.filter(|&v| (min_mc..=max_mc).contains(&v));
+        let soc_median = median(
+            self.tracks
+                .iter()
+                .filter(|t| t.health.group != "battery")
+                .filter_map(|t| in_range(t.health.value_mc))
+                .collect(),
+        );
+        let group_values = |group: &str| -> Vec<i32> {
+            self.tracks
+                .iter()
+                .filter(|t| t.health.group == group)
+                .filter_map(|t| in_range(t.health.value_mc))
+                .collect()
+        };
+        let group_medians: Vec<Option<i32>> = self
+            .tracks
+            .iter()
+            .map(|t| {
+                let v = group_values(&t.health.group);
+                if v.len() >= 3 { median(v) } else { None }
+            })
+            .collect();
+
+        let outlier_mc = (cfg.outlier_c * 1000.0) as i32;
+        let peer_delta_mc = (cfg.stuck_peer_delta_c * 1000.0) as i32;
+        let stuck_after = Duration::from_secs(cfg.stuck_after_s as u64);
+        let recover = Duration::from_secs(cfg.recover_s as u64);
+
+        for (t, group_median) in self.tracks.iter_mut().zip(group_medians) {
+            let mut suspect = false;
+            let verdict = match t.health.value_mc {
+                None => SensorStatus::Missing,
+                Some(_) if !cfg.enabled => SensorStatus::Ok,
+                Some(v) if in_range(Some(v)).is_none() => SensorStatus::OutOfRange,
+                Some(v) if group_median.is_some_and(|m| (v - m).abs() > outlier_mc) => {
+                    suspect = group_median.is_some_and(|m| v > m);
+                    SensorStatus::Outlier
+                }
+                Some(v) => {
+                    let (flat, peer_moved) = t.flat_for(v, soc_median, now);
+                    if flat >= stuck_after && peer_moved >= peer_delta_mc {
+                        SensorStatus::Stuck
+                    } else {
+                        SensorStatus::Ok
+                    }
+                }
+            };
+
+            let h = &mut t.health;
+            if verdict != SensorStatus::Ok {
+                if h.trusted || h.status != verdict || h.suspect != suspect {
+                    let how = if suspect { "suspect" } else { "excluded" };
+                    println!("SENSOR: zone {} ({}) {} -> {}", h.id, h.ty, verdict.as_str(), how);
+                }
+                h.trusted = false;
+                h.suspect = suspect;
+                h.status = verdict;
+                t.good_since = None;
+            } else if !h.trusted {
+                h.suspect = false;
+                let since = *t.good_since.get_or_insert(now);
+                if now.duration_since(since) >= recover || !cfg.enabled {
+                    println!("SENSOR: zone {} ({}) ok -> re-included", h.id, h.ty);
+                    h.trusted = true;
+                    h.status = SensorStatus::Ok;
+                    t.good_since = None;
+                } else {
+                    h.status = SensorStatus::Recovering;
+                }
+            }
+        }
+    }
+
+    fn trusted_values<'a>(&'a self, zones: &'a [Zone]) -> impl Iterator<Item = i32> + 'a {
+        zones.iter().filter_map(move |z| {
+            self.tracks
+                .iter()
+                .find(|t| t.health.id == z.id && t.health.trusted)
+                .and_then(|t| t.health.value_mc)
+        })
+    }
+
+    /// Average of the trusted sensors among `zones`.
+    pub fn avg_mc(&self, zones: &[Zone]) -> Option<i32> {
+        let (sum, n) = self.trusted_values(zones).fold((0i64, 0i64), |(s, n), v| (s + v as i64, n + 1));
+        if n == 0 { None } else { Some((sum / n) as i32) }
+    }
+
+    /// Hottest trusted sensor among `zones`.
+    pub fn max_mc(&self, zones: &[Zone]) -> Option<i32> {
+        self.trusted_values(zones).max()
+    }
+
+    /// Hottest plausible reading of the SoC (`battery = false`) or battery sensors for the
+    /// critical layer: trusted, recovering and suspect sensors count, so a runaway zone does.
+    /// Missing, out-of-range, stuck and low-outlier sensors don't.
+    pub fn hotspot_mc(&self, battery: bool) -> Option<i32> {
+        self.tracks
+            .iter()
+            .filter(|t| (t.health.group == "battery") == battery)
+            .filter(|t| {
+                let h = &t.health;
+                h.trusted || h.suspect || h.status == SensorStatus::Recovering
+            })
+            .filter_map(|t| t.health.value_mc)
+            .max()
+    }
+
+    /// True while at least one of `zones` is trusted.
+    pub fn any_trusted(&self, zones: &[Zone]) -> bool {
+        self.trusted_values(zones).next().is_some()
+    }
+
+    pub fn health(&self) -> Vec<SensorHealth> {
+        self.tracks.iter().map(|t| t.health.clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        critical::{CriticalGuard, CriticalLevel},
+        user_config::CriticalConfig,
+    };
+
+    fn cpu_zones(n: u32) -> Vec<Zone> {
+        (0..n).map(|id| Zone { id, ty: format!("cpu-{}", id) }).collect()
+    }
+
+    fn filter(cpu: u32) -> SensorFilter {
+        SensorFilter::new(&ZoneMap {
+            cpu: cpu_zones(cpu),
+            gpu: Vec::new(),
+            battery: Some(Zone { id: 99, ty: "battery".to_string() }),
+            domains: Vec::new(),
+        })
+    }
+
+    fn set(f: &mut SensorFilter, cpu_c: &[i32], batt_c: i32) {
+        for t in &mut f.tracks {
+            let c = if t.health.id == 99 { batt_c } else { cpu_c[t.health.id as usize] };
+            t.health.value_mc = Some(c * 1000);
+        }
+    }
+
+    #[test]
+    fn runaway_zone_still_reaches_critical() {
+        let cfg = SensorHealthConfig::default();
+        let mut f = filter(4);
+        let now = Instant::now();
+        set(&mut f, &[45, 46, 44, 45], 35);
+        f.evaluate(&cfg, now);
+
+        // One zone runs away while the rest stay cool: it's an outlier for control ...
+        set(&mut f, &[45, 46, 44, 102], 35);
+        f.evaluate(&cfg, now + Duration::from_secs(1));
+        assert_eq!(f.max_mc(&cpu_zones(4)), Some(46_000));
+        assert_eq!(f.hotspot_mc(false), Some(102_000));
+
+        // ... but the critical layer sees it.
+        assert!(f.health().iter().any(|h| h.id == 3 && h.suspect));
+        let mut g = CriticalGuard::default();
+        let level = g.update(f.hotspot_mc(false), f.hotspot_mc(true), &CriticalConfig::default());
+        assert_eq!(level, CriticalLevel::Critical);
+    }
+
+    #[test]
+    fn low_and_frozen_readings_are_dropped() {
+        let cfg = SensorHealthConfig::default();
+        let mut f = filter(4);
+        let mut now = Instant::now();
+        set(&mut f, &[60, 61, 20, 60], 35);
+        f.evaluate(&cfg, now);
+        let low = &f.health()[2];
+        assert_eq!((low.status, low.trusted, low.suspect), (SensorStatus::Outlier, false, false));
+
+        // Zone 0 frozen at 70 °C while the rest of the SoC heats up: stuck, not suspect.
+        for step in 0..=(cfg.stuck_after_s / 10) as i32 {
+            now += Duration::from_secs(10);
+            let c = 56 + step.min(10);
+            set(&mut f, &[70, c, c, c], 35);
+            f.evaluate(&cfg, now);
+        }
+        let frozen = &f.health()[0];
+        assert_eq!((frozen.status, frozen.suspect), (SensorStatus::Stuck, false));
+        assert_eq!(f.hotspot_mc(false), Some(66_000));
+    }
+}
@@ -1,145 +1,324 @@
 
-use std::{fs, process::Command, thread, time::Duration};
+use std::{
+    fs,
+    path::Path,
+    process::Command,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
-use crate::config::{CPU_ZONE_IDS, GPU_ZONE_IDS};
+use serde::{Deserialize, Serialize};
 
-pub fn disable_thermal_services() {
-    println!("Disabling thermal services...");
+use crate::{sysfs, thermal, user_config::{ensure_parent_dir, THERMAL_SNAPSHOT_PATH}};
 
-    let stop_services = vec![
-        "android.thermal-hal",
-        "vendor.thermal-engine",
-        "vendor.thermal_manager",
-        "vendor.thermal-manager",
-        "vendor.thermal-hal-2-0",
-        "vendor.thermal-symlinks",
-        "thermal_mnt_hal_service",
-        "thermal",
-        "mi_thermald",
-        "thermald",
-        "thermalloadalgod",
-        "thermalservice",
-        "sec-thermal-1-0",
-        "debug_pid.sec-thermal-1-0",
-        "thermal-engine",
-        "vendor.thermal-hal-1-0",
-        "vendor-thermal-1-0",
-        "thermal-hal",
-        "vendor.qti.hardware.perf2-hal-service",
-        "qti-msdaemon_vendor-0",
-        "qti-msdaemon_vendor-1",
-        "qti-ssdaemon_vendor",
-    ];
-
-    for service in stop_services {
-        let output = Command::new("stop").arg(service).output();
-        match output {
-            Ok(output) => {
-                if output.status.success() {
-                    println!("Stopped: {}", service);
-                } else {
-                    println!("Stop failed {}: {}", service, output.status);
-                }
-            }
-            Err(e) => println!("Stop error {}: {}", service, e),
+/// Vendor thermal services stopped on takeover (and started again on restore).
+const STOCK_THERMAL_SERVICES: &[&str] = &[
+    "android.thermal-hal",
+    "vendor.thermal-engine",
+    "vendor.thermal_manager",
+    "vendor.thermal-manager",
+    "vendor.thermal-hal-2-0",
+    "vendor.thermal-symlinks",
+    "thermal_mnt_hal_service",
+    "thermal",
+    "mi_thermald",
+    "thermald",
+    "thermalloadalgod",
+    "thermalservice",
+    "sec-thermal-1-0",
+    "debug_pid.sec-thermal-1-0",
+    "thermal-engine",
+    "vendor.thermal-hal-1-0",
+    "vendor-thermal-1-0",
+    "thermal-hal",
+    "vendor.qti.hardware.perf2-hal-service",
+    "qti-msdaemon_vendor-0",
+    "qti-msdaemon_vendor-1",
+    "qti-ssdaemon_vendor",
+];
+
+const STOCK_THERMAL_PROPS: &[(&str, &str)] = &[
+    ("init.svc.thermal", "stopped"),
+    ("init.svc.thermal-managers", "stopped"),
+    ("init.svc.thermal_manager", "stopped"),
+    ("init.svc.thermal_mnt_hal_service", "stopped"),
+    ("init.svc.thermal-engine", "stopped"),
+    ("init.svc.mi-thermald", "stopped"),
+    ("init.svc.thermalloadalgod", "stopped"),
+    ("init.svc.thermalservice", "stopped"),
+    ("init.svc.thermal-hal", "stopped"),
+    ("init.svc.vendor.thermal-symlinks", ""),
+    ("init.svc.android.thermal-hal", "stopped"),
+    ("init.svc.vendor.thermal-hal", "stopped"),
+    ("init.svc.thermal-manager", "stopped"),
+    ("init.svc.vendor-thermal-hal-1-0", "stopped"),
+    ("init.svc.vendor.thermal-hal-1-0", "stopped"),
+    ("init.svc.vendor.thermal-hal-2-0.mtk", "stopped"),
+    ("init.svc.vendor.thermal-hal-2-0", "stopped"),
+];
+
+// Thermal HAL/engine processes that survive `stop`, e.g. android.hardware.thermal-service.qti
+// lingers with init.svc=stopped yet keeps applying mitigations through cooling devices.
+const KILL_PATTERNS: &[&str] = &[
+    "thermal-service.qti",
+    "android.hardware.thermal",
+    "thermal-engine",
+    "thermald",
+];
+
+const THERMAL_DIR: &str = "/sys/class/thermal";
+const LMH_DRIVER_DIR: &str = "/sys/bus/platform/drivers/msm_lmh_dcvs";
+const LMH_DEVICE: &str = "soc:qcom,limits-dcvs";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ZoneModeState {
+    pub id: u32,
+    #[serde(rename = "type")]
+    pub ty: String,
+    pub mode: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CoolingState {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+    pub cur_state: u64,
+}
+
+/// Everything the takeover changed, with the values from before. Persisted to
+/// `THERMAL_SNAPSHOT_PATH` so a killed daemon or `--restore-thermal` can still undo it.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ThermalSnapshot {
+    pub taken_ms: u64,
+    /// Services that were running and got stopped.
+    pub services: Vec<String>,
+    /// Properties overwritten, with their previous values.
+    pub props: Vec<(String, String)>,
+    pub zones: Vec<ZoneModeState>,
+    pub cooling: Vec<CoolingState>,
+    /// msm_lmh_dcvs was bound and got unbound.
+    pub lmh_unbound: bool,
+}
+
+fn getprop(name: &str) -> Option<String> {
+    let out = Command::new("getprop").arg(name).output().ok()?;
+    Some(String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+fn run(cmd: &str, args: &[&str]) {
+    match Command::new(cmd).args(args).output() {
+        Ok(o) if o.status.success() => println!("{} {}", cmd, args.join(" ")),
+        Ok(o) => println!("{} failed {}: {}", cmd, args.join(" "), o.status),
+        Err(e) => println!("{} error {}: {}", cmd, args.join(" "), e),
+    }
+    thread::sleep(Duration::from_millis(50));
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+/// CPU/GPU cooling devices that cap frequency under the kernel governors.
+fn is_freq_cooling(ty: &str) -> bool {
+    ty.starts_with("cpufreq-") || ty.starts_with("cpu-cluster") || ty.starts_with("thermal-cluster") || ty == "gpu"
+}
+
+fn cooling_devices() -> Vec<CoolingState> {
+    let mut out = Vec::new();
+    let Ok(entries) = fs::read_dir(sysfs::path(THERMAL_DIR)) else { return out };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !name.starts_with("cooling_device") {
+            continue;
+        }
+        let p = entry.path();
+        let ty = sysfs::read_to_string(&p.join("type")).unwrap_or_default().trim().to_string();
+        if !is_freq_cooling(&ty) {
+            continue;
         }
-        thread::sleep(Duration::from_millis(50));
-    }
-
-    let setprop_commands = vec![
-        ("init.svc.thermal", "stopped"),
-        ("init.svc.thermal-managers", "stopped"),
-        ("init.svc.thermal_manager", "stopped"),
-        ("init.svc.thermal_mnt_hal_service", "stopped"),
-        ("init.svc.thermal-engine", "stopped"),
-        ("init.svc.mi-thermald", "stopped"),
-        ("init.svc.thermalloadalgod", "stopped"),
-        ("init.svc.thermalservice", "stopped"),
-        ("init.svc.thermal-hal", "stopped"),
-        ("init.svc.vendor.thermal-symlinks", ""),
-        ("init.svc.android.thermal-hal", "stopped"),
-        ("init.svc.vendor.thermal-hal", "stopped"),
-        ("init.svc.thermal-manager", "stopped"),
-        ("init.svc.vendor-thermal-hal-1-0", "stopped"),
-        ("init.svc.vendor.thermal-hal-1-0", "stopped"),
-        ("init.svc.vendor.thermal-hal-2-0.mtk", "stopped"),
-        ("init.svc.vendor.thermal-hal-2-0", "stopped"),
-    ];
-
-    for (prop, value) in setprop_commands {
-        let output = Command::new("setprop").arg(prop).arg(value).output();
-        match output {
-            Ok(output) => {
-                if output.status.success() {
-                    println!("setprop {}={}", prop, value);
-                } else {
-                    println!("setprop failed {}={}: {}", prop, value, output.status);
-                }
+        let cur_state = sysfs::read_u64(&p.join("cur_state")).unwrap_or(0);
+        out.push(CoolingState { name, ty, cur_state });
+    }
+    out.sort_by(|a, b| a.name.cmp(&b.name));
+    out
+}
+
+fn zone_mode_path(id: u32) -> std::path::PathBuf {
+    sysfs::path(format!("{}/thermal_zone{}/mode", THERMAL_DIR, id))
+}
+
+/// Record the current stock state of everything `apply_takeover` touches.
+fn capture() -> ThermalSnapshot {
+    let relocated = sysfs::is_relocated();
+    let mut snap = ThermalSnapshot { taken_ms: now_ms(), ..Default::default() };
+
+    if !relocated {
+        for svc in STOCK_THERMAL_SERVICES {
+            if getprop(&format!("init.svc.{}", svc)).as_deref() == Some("running") {
+                snap.services.push(svc.to_string());
             }
-            Err(e) => println!("setprop error {}={}: {}", prop, value, e),
         }
-        thread::sleep(Duration::from_millis(50));
-    }
-
-    // --- Burn mode additions (port of manual diag script) ---
-
-    // 1) Hard-kill thermal HAL/engine processes that survive `stop`.
-    //    e.g. android.hardware.thermal-service.qti lingers with init.svc=stopped
-    //    yet keeps applying mitigations through cooling devices.
-    let kill_patterns = vec![
-        "thermal-service.qti",
-        "android.hardware.thermal",
-        "thermal-engine",
-        "thermald",
-    ];
-    for pat in kill_patterns {
-        match Command::new("pkill").arg("-f").arg(pat).output() {
-            Ok(o) => println!("pkill -f {} -> {}", pat, o.status),
-            Err(e) => println!("pkill error {}: {}", pat, e),
+        for (prop, _) in STOCK_THERMAL_PROPS {
+            snap.props.push((prop.to_string(), getprop(prop).unwrap_or_default()));
         }
-        thread::sleep(Duration::from_millis(50));
     }
 
-    // 2) Disable in-kernel thermal (step_wise) on CPU/GPU zones so cooling
-    //    devices (cpufreq-cpuN / cpu-clusterN / gpu) stop capping frequency.
-    //    Battery/BCL zones are intentionally left untouched.
-    for &id in CPU_ZONE_IDS.iter().chain(GPU_ZONE_IDS.iter()) {
-        let path = format!("/sys/class/thermal/thermal_zone{}/mode", id);
-        match fs::write(&path, "disabled") {
-            Ok(_) => println!("zone {} mode=disabled", id),
-            Err(e) => println!("zone {} disable failed: {}", id, e),
+    // Exactly the zones resolved from the device descriptor; battery/BCL zones are left alone.
+    let z = thermal::zones();
+    for zone in z.cpu.iter().chain(z.gpu.iter()) {
+        if let Some(mode) = sysfs::read_to_string(&zone_mode_path(zone.id)) {
+            snap.zones.push(ZoneModeState { id: zone.id, ty: zone.ty.clone(), mode: mode.trim().to_string() });
         }
     }
+    snap.cooling = cooling_devices();
+    snap.lmh_unbound = sysfs::path(LMH_DRIVER_DIR).join(LMH_DEVICE).exists();
+    snap
+}
 
-    // 3) Reset any already-engaged CPU/GPU cooling devices back to 0.
-    if let Ok(entries) = fs::read_dir("/sys/class/thermal") {
-        for entry in entries.flatten() {
-            let name = entry.file_name().to_string_lossy().to_string();
-            if !name.starts_with("cooling_device") {
-                continue;
-            }
-            let p = entry.path();
-            let ty = fs::read_to_string(p.join("type")).unwrap_or_default();
-            let ty = ty.trim();
-            if ty.starts_with("cpufreq-")
-                || ty.starts_with("cpu-cluster")
-                || ty.starts_with("thermal-cluster")
-                || ty == "gpu"
-            {
-                let _ = fs::write(p.join("cur_state"), "0");
-            }
+/// Stop the vendor stack and detach kernel thermal from CPU/GPU.
+fn apply_takeover(snap: &ThermalSnapshot) {
+    println!("Disabling thermal services...");
+    if !sysfs::is_relocated() {
+        for svc in STOCK_THERMAL_SERVICES {
+            run("stop", &[svc]);
+        }
+        for (prop, value) in STOCK_THERMAL_PROPS {
+            run("setprop", &[prop, value]);
+        }
+        for pat in KILL_PATTERNS {
+            run("pkill", &["-f", pat]);
         }
     }
 
-    // 4) Unbind the userspace LMh driver. The real enforcer lives in CPUCP
-    //    firmware (hardware), but we detach what we can from userspace.
-    match fs::write(
-        "/sys/bus/platform/drivers/msm_lmh_dcvs/unbind",
-        "soc:qcom,limits-dcvs",
-    ) {
-        Ok(_) => println!("lmh: unbound msm_lmh_dcvs"),
-        Err(e) => println!("lmh: unbind skipped ({})", e),
+    // Disable in-kernel thermal (step_wise) on CPU/GPU zones so cooling
+    // devices (cpufreq-cpuN / cpu-clusterN / gpu) stop capping frequency.
+    for z in &snap.zones {
+        match fs::write(zone_mode_path(z.id), "disabled") {
+            Ok(_) => println!("zone {} ({}) mode=disabled", z.id, z.ty),
+            Err(e) => println!("zone {} ({}) disable failed: {}", z.id, z.ty, e),
+        }
+    }
+
+    // Reset any already-engaged CPU/GPU cooling devices back to 0.
+    for c in cooling_devices() {
+        let _ = fs::write(sysfs::path(THERMAL_DIR).join(&c.name).join("cur_state"), "0");
+    }
+
+    // Unbind the userspace LMh driver. The real enforcer lives in CPUCP
+    // firmware (hardware), but we detach what we can from userspace.
+    if snap.lmh_unbound {
+        match fs::write(sysfs::path(LMH_DRIVER_DIR).join("unbind"), LMH_DEVICE) {
+            Ok(_) => println!("lmh: unbound msm_lmh_dcvs"),
+            Err(e) => println!("lmh: unbind skipped ({})", e),
+        }
     }
 
     println!("Thermal service disable finished");
 }
+
+/// Put back everything recorded in `snap`.
+fn apply_restore(snap: &ThermalSnapshot) {
+    println!("Restoring stock thermal...");
+
+    if snap.lmh_unbound {
+        match fs::write(sysfs::path(LMH_DRIVER_DIR).join("bind"), LMH_DEVICE) {
+            Ok(_) => println!("lmh: rebound msm_lmh_dcvs"),
+            Err(e) => println!("lmh: rebind failed ({})", e),
+        }
+    }
+    for z in &snap.zones {
+        match fs::write(zone_mode_path(z.id), &z.mode) {
+            Ok(_) => println!("zone {} ({}) mode={}", z.id, z.ty, z.mode),
+            Err(e) => println!("zone {} ({}) restore failed: {}", z.id, z.ty, e),
+        }
+    }
+    for c in &snap.cooling {
+        let _ = fs::write(sysfs::path(THERMAL_DIR).join(&c.name).join("cur_state"), c.cur_state.to_string());
+    }
+    if !sysfs::is_relocated() {
+        for (prop, value) in &snap.props {
+            run("setprop", &[prop, value]);
+        }
+        for svc in &snap.services {
+            run("start", &[svc]);
+        }
+    }
+
+    println!("Stock thermal restore finished");
+}
+
+fn snapshot_path() -> std::path::PathBuf {
+    sysfs::path(THERMAL_SNAPSHOT_PATH)
+}
+
+fn load_snapshot(path: &Path) -> Option<ThermalSnapshot> {
+    let s = fs::read_to_string(path).ok()?;
+    match serde_json::from_str(&s) {
+        Ok(v) => Some(v),
+        Err(e) => {
+            println!("THERM: snapshot {} unreadable ({})", path.display(), e);
+            None
+        }
+    }
+}
+
+fn save_snapshot(path: &Path, snap: &ThermalSnapshot) {
+    let res = ensure_parent_dir(path).and_then(|_| {
+        let data = serde_json::to_vec_pretty(snap).map_err(std::io::Error::other)?;
+        fs::write(path, data)
+    });
+    if let Err(e) = res {
+        println!("THERM: snapshot save failed ({})", e);
+    }
+}
+
+/// Owner of the stock-thermal takeover. At most one snapshot exists: taking over again
+/// (e.g. after a crash) reuses the one on disk instead of recording the already-disabled state.
+#[derive(Default)]
+pub struct ThermalTakeover {
+    snapshot: Option<ThermalSnapshot>,
+}
+
+impl ThermalTakeover {
+    pub fn is_active(&self) -> bool {
+        self.snapshot.is_some()
+    }
+
+    pub fn snapshot(&self) -> Option<&ThermalSnapshot> {
+        self.snapshot.as_ref()
+    }
+
+    pub fn engage(&mut self) {
+        let path = snapshot_path();
+        let snap = match load_snapshot(&path) {
+            Some(s) => {
+                println!("THERM: reusing snapshot from a previous run");
+                s
+            }
+            None => {
+                let s = capture();
+                save_snapshot(&path, &s);
+                s
+            }
+        };
+        apply_takeover(&snap);
+        self.snapshot = Some(snap);
+    }
+
+    /// Restore the stock state from the in-memory snapshot, or from disk when a previous
+    /// run left one behind. Returns false when there was nothing to restore.
+    pub fn release(&mut self) -> bool {
+        let Some(snap) = self.snapshot.take().or_else(|| load_snapshot(&snapshot_path())) else {
+            return false;
+        };
+        apply_restore(&snap);
+        let _ = fs::remove_file(snapshot_path());
+        true
+    }
+}
+
+/// `--restore-thermal`: undo a takeover recorded on disk (daemon stopped or crashed).
+pub fn restore_from_disk() -> bool {
+    ThermalTakeover::default().release()
+}
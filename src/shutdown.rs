@@ -0,0 +1,159 @@
+//! Graceful shutdown.
+//!
+//! SIGTERM/SIGINT only raise a flag; the main loop notices it, leaves, and puts back every
+//! device node it wrote. The first value seen for each node is journaled to disk before the
+//! first write, so a killed (or aborted) daemon can still be undone by the next start or by
+//! `perf_daemon --restore`.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    sysfs,
+    user_config::{ensure_parent_dir, RESTORE_JOURNAL_PATH},
+};
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+static JOURNAL: Mutex<Option<Journal>> = Mutex::new(None);
+
+extern "C" fn on_signal(_sig: libc::c_int) {
+    REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// Route SIGTERM/SIGINT to the shutdown flag.
+pub fn install_signal_handlers() {
+    let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    unsafe {
+        libc::signal(libc::SIGTERM, handler);
+        libc::signal(libc::SIGINT, handler);
+    }
+}
+
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Sleep that returns early once shutdown is requested.
+pub fn sleep(d: Duration) {
+    let end = Instant::now() + d;
+    while !requested() {
+        let left = end.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            break;
+        }
+        thread::sleep(left.min(Duration::from_millis(100)));
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Entry {
+    path: PathBuf,
+    value: String,
+}
+
+struct Journal {
+    file: PathBuf,
+    /// In order of first write; restored in reverse.
+    entries: Vec<Entry>,
+}
+
+impl Journal {
+    fn save(&self) {
+        let res = ensure_parent_dir(&self.file).and_then(|_| {
+            let data = serde_json::to_vec_pretty(&self.entries).map_err(std::io::Error::other)?;
+            fs::write(&self.file, data)
+        });
+        if let Err(e) = res {
+            println!("RESTORE: journal save failed ({})", e);
+        }
+    }
+}
+
+fn journal_file() -> PathBuf {
+    sysfs::path(RESTORE_JOURNAL_PATH)
+}
+
+fn load_entries(file: &Path) -> Vec<Entry> {
+    let Ok(s) = fs::read_to_string(file) else { return Vec::new() };
+    serde_json::from_str(&s).unwrap_or_else(|e| {
+        println!("RESTORE: journal {} unreadable ({})", file.display(), e);
+        Vec::new()
+    })
+}
+
+/// Start journaling. Entries left by a previous run are kept: they hold the values from
+/// before that run, not the ones it left behind.
+pub fn init_journal() {
+    let file = journal_file();
+    let entries = load_entries(&file);
+    if !entries.is_empty() {
+        println!("RESTORE: {} original values kept from a previous run", entries.len());
+    }
+    *JOURNAL.lock().unwrap() = Some(Journal { file, entries });
+}
+
+fn is_recorded(path: &Path) -> Option<bool> {
+    let guard = JOURNAL.lock().unwrap();
+    guard.as_ref().map(|j| j.entries.iter().any(|e| e.path == path))
+}
+
+/// Remember the current value of `path` before its first write. No-op before `init_journal`
+/// (simulation) or for nodes that can't be read. The node is read without holding the lock,
+/// so a hung node only blocks its writer (the watchdog still gets through).
+pub fn record(path: &Path) {
+    if is_recorded(path) != Some(false) {
+        return;
+    }
+    let Some(value) = sysfs::read_to_string(path) else { return };
+    let mut guard = JOURNAL.lock().unwrap();
+    let Some(j) = guard.as_mut() else { return };
+    if j.entries.iter().any(|e| e.path == path) {
+        return;
+    }
+    j.entries.push(Entry { path: path.to_path_buf(), value: value.trim().to_string() });
+    j.save();
+}
+
+fn restore_entries(entries: &[Entry]) -> usize {
+    let mut restored = 0;
+    for e in entries.iter().rev() {
+        match fs::write(&e.path, format!("{}\n", e.value)) {
+            Ok(_) => restored += 1,
+            Err(err) => println!("RESTORE: {} = {} failed: {}", e.path.display(), e.value, err),
+        }
+    }
+    restored
+}
+
+/// Write every journaled value back, newest first, and drop the journal.
+/// Journaling stops: later writes are not recorded.
+pub fn restore_journal() -> usize {
+    let Some(j) = JOURNAL.lock().unwrap().take() else { return 0 };
+    let n = restore_entries(&j.entries);
+    let _ = fs::remove_file(&j.file);
+    println!("RESTORE: {}/{} device values restored", n, j.entries.len());
+    n
+}
+
+/// `--restore`: undo a journal left on disk by a daemon that is no longer running.
+pub fn restore_from_disk() -> bool {
+    let file = journal_file();
+    let entries = load_entries(&file);
+    if entries.is_empty() {
+        return false;
+    }
+    let n = restore_entries(&entries);
+    let _ = fs::remove_file(&file);
+    println!("RESTORE: {}/{} device values restored", n, entries.len());
+    true
+}
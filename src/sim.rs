@@ -0,0 +1,127 @@
+//! Offline simulation: replay a sensor trace through `control::Controller`.
+//!
+//! Trace format (JSONL, one frame per line):
+//!   {"t_ms":0,"cpu_avg_mc":52000,"batt_temp_mc":36000,"cpu_utils":[40,40,20,20,20,5,5,0],"ug":30,"pkg":"com.foo"}
+//! Missing fields fall back to `Inputs` defaults (screen ON, not charging, no temps).
+//!
+//! The clock is virtual: each step advances by the controller's own `sleep_ms`, and the
+//! latest frame with `t_ms <= now` is held until the next one. The run ends once the
+//! clock passes the last frame. Output is one JSONL line per step (`t_ms` + decisions),
+//! so two runs over the same trace/config are byte-identical and diffable.
+
+use std::{
+    fs,
+    io::{self, BufWriter, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    control::{Controller, Decisions, GameFlags, Inputs},
+    games::{read_games, GamesFile, GamesRuntime},
+    device,
+    user_config::UserConfig,
+};
+
+#[derive(Debug, Deserialize)]
+struct Frame {
+    #[serde(default)]
+    t_ms: u64,
+    #[serde(flatten)]
+    inputs: Inputs,
+}
+
+#[derive(Serialize)]
+struct Step<'a> {
+    t_ms: u64,
+    #[serde(flatten)]
+    d: &'a Decisions,
+}
+
+fn read_frames(path: &Path) -> io::Result<Vec<Frame>> {
+    let s = fs::read_to_string(path)?;
+    let mut frames = Vec::new();
+    for (i, line) in s.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let f: Frame = serde_json::from_str(line).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", i + 1, e))
+        })?;
+        frames.push(f);
+    }
+    // Frames must be in time order for sample-and-hold.
+    frames.sort_by_key(|f| f.t_ms);
+    Ok(frames)
+}
+
+fn read_config(path: Option<&Path>) -> io::Result<UserConfig> {
+    let Some(path) = path else { return Ok(UserConfig::default()) };
+    let s = fs::read_to_string(path)?;
+    let mut cfg: UserConfig =
+        serde_json::from_str(&s).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let resets = cfg.validate_and_normalize();
+    if !resets.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, resets.join("; ")));
+    }
+    Ok(cfg)
+}
+
+/// Run the simulation. Writes decisions to `out` (stdout if None).
+pub fn run(trace: &Path, config: Option<&Path>, games: Option<&Path>, out: Option<&Path>) -> io::Result<()> {
+    let frames = read_frames(trace)?;
+    let cfg = read_config(config)?;
+    let games = match games {
+        Some(p) => GamesRuntime::from_file(read_games(p)?),
+        None => GamesRuntime::from_file(GamesFile::default()),
+    };
+
+    let mut w: Box<dyn Write> = match out {
+        Some(p) => Box::new(BufWriter::new(fs::File::create(p)?)),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+
+    let Some(last_t) = frames.last().map(|f| f.t_ms) else {
+        eprintln!("SIM: empty trace");
+        return Ok(());
+    };
+
+    let dev = device::get();
+    // The descriptor's table, not the host's: a replay must not depend on where it runs.
+    let base = Instant::now();
+    let mut ctrl = Controller::new(dev, &dev.gpu.freqs, base)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    let mut t_ms: u64 = 0;
+    let mut prev_t_ms: u64 = 0;
+    let mut next = 0usize;
+    let mut steps = 0u64;
+
+    while t_ms <= last_t {
+        while next + 1 < frames.len() && frames[next + 1].t_ms <= t_ms {
+            next += 1;
+        }
+        let mut inputs = frames[next].inputs.clone();
+        inputs.game = GameFlags::resolve(&games, inputs.pkg.as_deref());
+        inputs.app = inputs.pkg.as_deref().and_then(|p| games.app_for(p)).cloned();
+
+        let now = base + Duration::from_millis(t_ms);
+        let dt = Duration::from_millis(t_ms - prev_t_ms);
+        let d = ctrl.step(&inputs, &cfg, now, dt);
+
+        serde_json::to_writer(&mut w, &Step { t_ms, d: &d })
+            .map_err(io::Error::other)?;
+        w.write_all(b"\n")?;
+        steps += 1;
+
+        prev_t_ms = t_ms;
+        t_ms += d.sleep_ms;
+    }
+    w.flush()?;
+
+    eprintln!("SIM: {} frames, {} steps, {} ms simulated", frames.len(), steps, prev_t_ms);
+    Ok(())
+}
@@ -1,5 +1,7 @@
 use std::{fs, io, path::PathBuf, time::{Duration, Instant}};
 
+use crate::{shutdown, sysfs};
+
 const SPLIT_CHARGE_RECHECK_EVERY: Duration = Duration::from_secs(90);
 const PREFERRED_NODE: &str = "/sys/class/qcom-battery/battery_charging_enabled";
 const CANDIDATE_NODES: &[&str] = &[
@@ -127,7 +129,7 @@ pub struct DesiredSplitCharge {
 
 fn detect_writable_node() -> Option<PathBuf> {
     for cand in CANDIDATE_NODES {
-        let p = PathBuf::from(cand);
+        let p = sysfs::path(cand);
         if !p.exists() { continue; }
         if fs::OpenOptions::new().write(true).open(&p).is_ok() {
             return Some(p);
@@ -137,9 +139,11 @@ fn detect_writable_node() -> Option<PathBuf> {
 }
 
 fn write_zero(path: &PathBuf) -> io::Result<()> {
+    shutdown::record(path);
     fs::write(path, b"0\n")
 }
 
 fn write_one(path: &PathBuf) -> io::Result<()> {
+    shutdown::record(path);
     fs::write(path, b"1\n")
 }
@@ -1,7 +1,40 @@
+use crate::control::DomainThermal;
+use crate::critical::CriticalLevel;
+use crate::domain::DomainTuning;
 use crate::games::GamesRuntime;
+use crate::{fan::FanStatus, fan_maintenance::FanMaintenanceStatus, fan_setpoint::FanSetpointStatus, pid::PidStatus};
+use crate::sensors::SensorHealth;
+use crate::services::ThermalSnapshot;
 use crate::user_config::{ExternalLedSetting, FanLedSetting, NotificationsStopKind, UserConfig};
 use std::time::Instant;
 
+/// Manual fan level set through `/api/fan/override`. Not persisted.
+#[derive(Clone, Debug)]
+pub struct FanOverride {
+    pub level: u8,
+    pub since: Instant,
+    pub ends_at: Option<Instant>,
+    /// Released when the foreground game exits.
+    pub until_game_exit: bool,
+}
+
+impl FanOverride {
+    pub fn remaining_s(&self, now: Instant) -> Option<u64> {
+        self.ends_at.map(|t| t.saturating_duration_since(now).as_secs())
+    }
+
+    /// Why the override should end now, if it should.
+    pub fn expired(&self, now: Instant, game_mode: bool) -> Option<&'static str> {
+        if self.ends_at.is_some_and(|t| now >= t) {
+            Some("expired")
+        } else if self.until_game_exit && !game_mode {
+            Some("game exited")
+        } else {
+            None
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct LedRuntimeState {
     pub base_external_desired: Option<ExternalLedSetting>,
@@ -40,8 +73,36 @@ pub struct InfoState {
     pub soc_mc: Option<i32>,
     pub batt_mc: Option<i32>,
 
+    // control_temp formula in use (normal / charging / gaming)
+    pub control_formula: &'static str,
+    // Critical safeguard
+    pub critical_level: CriticalLevel,
+    pub soc_hot_mc: Option<i32>,
+    pub stock_thermal_restored: bool,
+    // Stock thermal takeover and what it replaced
+    pub thermal_takeover_active: bool,
+    pub thermal_snapshot: Option<ThermalSnapshot>,
+    // Thermal sensor sanity filtering
+    pub control_sensor_trusted: bool,
+    pub sensors: Vec<SensorHealth>,
     pub temp_zone: String,
     pub reduce_percent: u8,
+    // PID thermal controller output (pid mode only)
+    pub thermal_pid: Option<PidStatus>,
+    // Fan setpoint controller output (setpoint mode only)
+    pub fan_setpoint: Option<FanSetpointStatus>,
+    // Fan actuation health (None without fan sysfs)
+    pub fan: Option<FanStatus>,
+    // Dust-clearing cycle
+    pub fan_maintenance: FanMaintenanceStatus,
+    // Predictive throttling: control temp slope and the temperature the curve saw
+    pub temp_slope_c_per_min: Option<f32>,
+    pub zone_temp_mc: Option<i32>,
+    // Per-domain thermal attribution (which domain is cut, by how much, why)
+    pub thermal_hotspot: Option<&'static str>,
+    pub thermal_domains: Vec<DomainThermal>,
+    // Effective domain tunables (config + active profile), by label
+    pub domain_tuning: Vec<(&'static str, DomainTuning)>,
 
     pub screen_on: bool,
     // Hardware charging state (as detected by mora)
@@ -54,6 +115,12 @@ pub struct InfoState {
     pub idle_mode: bool,
 
     pub active_profile: String,
+    // Rule that selected a custom active_profile
+    pub profile_rule: Option<String>,
+    // Foreground package whose app rule is applied
+    pub app_rule: Option<String>,
+    // Active time-of-day schedule (quiet hours)
+    pub schedule: Option<String>,
     pub led_profile: String,
 
     // Battery percentage (0..100), if available
@@ -80,6 +147,11 @@ pub struct InfoState {
     pub triggers_left: bool,
     pub triggers_right: bool,
     pub triggers_pkg: Option<String>,
+
+    // Trace recorder
+    pub trace_active: bool,
+    pub trace_bytes: u64,
+    pub trace_last_error: Option<String>,
 }
 
 impl Default for InfoState {
@@ -89,8 +161,25 @@ impl Default for InfoState {
             gpu_avg_mc: None,
             soc_mc: None,
             batt_mc: None,
+            control_formula: "normal",
+            critical_level: CriticalLevel::None,
+            soc_hot_mc: None,
+            stock_thermal_restored: false,
+            thermal_takeover_active: false,
+            thermal_snapshot: None,
+            control_sensor_trusted: true,
+            sensors: Vec::new(),
             temp_zone: String::new(),
             reduce_percent: 0,
+            thermal_pid: None,
+            fan_setpoint: None,
+            fan: None,
+            fan_maintenance: FanMaintenanceStatus::default(),
+            temp_slope_c_per_min: None,
+            zone_temp_mc: None,
+            thermal_hotspot: None,
+            thermal_domains: Vec::new(),
+            domain_tuning: Vec::new(),
             screen_on: true,
             charging: false,
             charging_enabled: true,
@@ -98,6 +187,9 @@ impl Default for InfoState {
             game_mode: false,
             idle_mode: false,
             active_profile: String::new(),
+            profile_rule: None,
+            app_rule: None,
+            schedule: None,
             led_profile: String::new(),
 
             battery_percent: None,
@@ -117,6 +209,10 @@ impl Default for InfoState {
             triggers_left: false,
             triggers_right: false,
             triggers_pkg: None,
+
+            trace_active: false,
+            trace_bytes: 0,
+            trace_last_error: None,
         }
     }
 }
@@ -133,6 +229,7 @@ pub struct SharedState {
 
     pub info: InfoState,
     pub leds: LedRuntimeState,
+    pub fan_override: Option<FanOverride>,
 }
 
 impl SharedState {
@@ -148,6 +245,7 @@ impl SharedState {
 
             info: InfoState::default(),
             leds: LedRuntimeState::default(),
+            fan_override: None,
         }
     }
 }
@@ -18,6 +18,10 @@ const ZRAM_BLOCK: &str = "/dev/block/zram0";
 const ZRAM_DEV: &str = "/dev/zram0";
 
 pub fn init_silent() {
+    // Swap/zram setup targets the real /data and block devices; never run it against a fake tree.
+    if crate::sysfs::is_relocated() {
+        return;
+    }
     let _ = setup_swap_silent();
 }
 
@@ -4,8 +4,36 @@ use std::{
     fs,
     io,
     path::{Path, PathBuf},
+    sync::OnceLock,
 };
 
+use crate::shutdown;
+
+/// Root that every device path (sysfs/procfs/devfs/module config) is resolved against.
+/// Unset on the phone. Set via `--root <dir>` (or `MORA_ROOT`) to run the daemon on a
+/// normal Linux box against a directory tree that mimics the device.
+static ROOT: OnceLock<PathBuf> = OnceLock::new();
+
+/// Relocate all device paths under `root`. Only the first call has an effect.
+pub fn set_root(root: PathBuf) {
+    let _ = ROOT.set(root);
+}
+
+/// True when running against a relocated (fake) device tree.
+/// Android-only side effects (`settings`, `stop`, `su`, swap setup, ...) are skipped then.
+pub fn is_relocated() -> bool {
+    ROOT.get().is_some()
+}
+
+/// Resolve an absolute device path against the configured root.
+pub fn path<P: AsRef<Path>>(abs: P) -> PathBuf {
+    let abs = abs.as_ref();
+    match ROOT.get() {
+        Some(root) => root.join(abs.strip_prefix("/").unwrap_or(abs)),
+        None => abs.to_path_buf(),
+    }
+}
+
 pub fn read_to_string(path: &Path) -> Option<String> {
     fs::read_to_string(path).ok()
 }
@@ -21,6 +49,7 @@ pub fn read_i32(path: &Path) -> Option<i32> {
 }
 
 pub fn write_num(path: &Path, val: u64) -> io::Result<()> {
+    shutdown::record(path);
     fs::write(path, format!("{}\n", val).as_bytes())
 }
 
@@ -79,6 +108,7 @@ pub fn write_str_if_needed(
         }
     }
 
+    shutdown::record(path);
     fs::write(path, format!("{}\n", target).as_bytes())?;
     cache.insert(path.to_path_buf(), target.to_string());
     Ok(true)
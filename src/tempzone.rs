@@ -1,131 +1,256 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    time::{Duration, Instant},
+};
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum TempZone {
-    Cool,
-    B50,
-    B51,
-    B52,
-    B53,
-    B54,
-    B55,
-    B56,
-    B57,
-    B58,
+use serde::{Serialize, Serializer};
+
+use crate::user_config::{ThermalCurveConfig, ThermalPredictConfig};
+
+/// Current band of the thermal curve (`config.json` -> `thermal_curve.bands`).
+/// `band == 0` is Cool (no reduction); band N is `bands[N - 1]`.
+/// The band's parameters are copied in so caps don't need the config.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TempZone {
+    pub band: u8,
+    /// Entry threshold (m°C); 0 for Cool.
+    pub threshold_mc: i32,
+    pub reduce_percent: u8,
+    /// Max table steps a cap may drop per iteration while in this band.
+    pub max_step_down: u8,
+    /// Poll faster while in this band.
+    pub fast_poll: bool,
 }
 
 impl TempZone {
+    pub const COOL: TempZone = TempZone {
+        band: 0,
+        threshold_mc: 0,
+        reduce_percent: 0,
+        max_step_down: 1,
+        fast_poll: false,
+    };
+
+    pub fn is_cool(self) -> bool {
+        self.band == 0
+    }
+
     pub fn reduction_percent(self) -> u32 {
-        match self {
-            TempZone::Cool => 0,
-            TempZone::B50 => 10,
-            TempZone::B51 => 15,
-            TempZone::B52 => 20,
-            TempZone::B53 => 25,
-            TempZone::B54 => 30,
-            TempZone::B55 => 35,
-            TempZone::B56 => 45,
-            TempZone::B57 => 55,
-            TempZone::B58 => 75,
+        self.reduce_percent as u32
+    }
+
+    fn from_curve(curve: &ThermalCurveConfig, band: usize) -> TempZone {
+        match band.checked_sub(1).and_then(|i| curve.bands.get(i)) {
+            None => TempZone::COOL,
+            Some(b) => TempZone {
+                band: band as u8,
+                threshold_mc: b.threshold_mc(),
+                reduce_percent: b.reduce_percent,
+                max_step_down: b.max_step_down,
+                fast_poll: b.fast_poll,
+            },
         }
     }
 }
 
-pub fn zone_with_hysteresis(temp_mc: i32, prev: TempZone) -> TempZone {
-    let t50 = 50_000;
-    let t51 = 51_000;
-    let t52 = 52_000;
-    let t53 = 53_000;
-    let t54 = 54_000;
-    let t55 = 55_000;
-    let t56 = 56_000;
-    let t57 = 57_000;
-    let t58 = 58_000;
-    let h = 500;
-
-    match prev {
-        TempZone::Cool => {
-            if temp_mc >= t58 { TempZone::B58 }
-            else if temp_mc >= t57 { TempZone::B57 }
-            else if temp_mc >= t56 { TempZone::B56 }
-            else if temp_mc >= t55 { TempZone::B55 }
-            else if temp_mc >= t54 { TempZone::B54 }
-            else if temp_mc >= t53 { TempZone::B53 }
-            else if temp_mc >= t52 { TempZone::B52 }
-            else if temp_mc >= t51 { TempZone::B51 }
-            else if temp_mc >= t50 { TempZone::B50 }
-            else { TempZone::Cool }
-        }
-        TempZone::B50 => {
-            if temp_mc >= t58 { TempZone::B58 }
-            else if temp_mc >= t57 { TempZone::B57 }
-            else if temp_mc >= t56 { TempZone::B56 }
-            else if temp_mc >= t55 { TempZone::B55 }
-            else if temp_mc >= t54 { TempZone::B54 }
-            else if temp_mc >= t53 { TempZone::B53 }
-            else if temp_mc >= t52 { TempZone::B52 }
-            else if temp_mc >= t51 { TempZone::B51 }
-            else if temp_mc < t50 - h { TempZone::Cool }
-            else { TempZone::B50 }
-        }
-        TempZone::B51 => {
-            if temp_mc >= t58 { TempZone::B58 }
-            else if temp_mc >= t57 { TempZone::B57 }
-            else if temp_mc >= t56 { TempZone::B56 }
-            else if temp_mc >= t55 { TempZone::B55 }
-            else if temp_mc >= t54 { TempZone::B54 }
-            else if temp_mc >= t53 { TempZone::B53 }
-            else if temp_mc >= t52 { TempZone::B52 }
-            else if temp_mc < t51 - h { TempZone::B50 }
-            else { TempZone::B51 }
+/// Zone name as shown in the API/logs: `Cool`, `B50`, `B52.5`, ...
+impl fmt::Display for TempZone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_cool() {
+            return f.write_str("Cool");
         }
-        TempZone::B52 => {
-            if temp_mc >= t58 { TempZone::B58 }
-            else if temp_mc >= t57 { TempZone::B57 }
-            else if temp_mc >= t56 { TempZone::B56 }
-            else if temp_mc >= t55 { TempZone::B55 }
-            else if temp_mc >= t54 { TempZone::B54 }
-            else if temp_mc >= t53 { TempZone::B53 }
-            else if temp_mc < t52 - h { TempZone::B51 }
-            else { TempZone::B52 }
+        let t = self.threshold_mc;
+        if t % 1000 == 0 {
+            write!(f, "B{}", t / 1000)
+        } else {
+            write!(f, "B{}", t as f32 / 1000.0)
         }
-        TempZone::B53 => {
-            if temp_mc >= t58 { TempZone::B58 }
-            else if temp_mc >= t57 { TempZone::B57 }
-            else if temp_mc >= t56 { TempZone::B56 }
-            else if temp_mc >= t55 { TempZone::B55 }
-            else if temp_mc >= t54 { TempZone::B54 }
-            else if temp_mc < t53 - h { TempZone::B52 }
-            else { TempZone::B53 }
+    }
+}
+
+impl Serialize for TempZone {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_str(self)
+    }
+}
+
+/// Climb straight to the hottest band whose threshold is reached; fall back one band at a time
+/// once the temperature drops below the current band's threshold minus its hysteresis.
+pub fn zone_with_hysteresis(temp_mc: i32, prev: TempZone, curve: &ThermalCurveConfig) -> TempZone {
+    let n = curve.bands.len();
+    let hot = curve.bands.iter().take_while(|b| temp_mc >= b.threshold_mc()).count();
+    // The curve may have been edited since `prev` was computed.
+    let cur = (prev.band as usize).min(n);
+
+    if hot > cur {
+        return TempZone::from_curve(curve, hot);
+    }
+    if cur > 0 {
+        let b = &curve.bands[cur - 1];
+        if temp_mc < b.threshold_mc() - b.hysteresis_mc() {
+            return TempZone::from_curve(curve, cur - 1);
         }
-        TempZone::B54 => {
-            if temp_mc >= t58 { TempZone::B58 }
-            else if temp_mc >= t57 { TempZone::B57 }
-            else if temp_mc >= t56 { TempZone::B56 }
-            else if temp_mc >= t55 { TempZone::B55 }
-            else if temp_mc < t54 - h { TempZone::B53 }
-            else { TempZone::B54 }
+    }
+    TempZone::from_curve(curve, cur)
+}
+
+/// Least-squares dT/dt over the recent control-temperature history.
+#[derive(Default)]
+pub struct SlopeEstimator {
+    samples: VecDeque<(Instant, i32)>,
+}
+
+impl SlopeEstimator {
+    /// Record a sample and return the slope in m°C/s once the window holds enough history.
+    pub fn update(&mut self, now: Instant, temp_mc: i32, window: Duration) -> Option<f32> {
+        self.samples.push_back((now, temp_mc));
+        while let Some(&(t, _)) = self.samples.front() {
+            if now.duration_since(t) > window {
+                self.samples.pop_front();
+            } else {
+                break;
+            }
         }
-        TempZone::B55 => {
-            if temp_mc >= t58 { TempZone::B58 }
-            else if temp_mc >= t57 { TempZone::B57 }
-            else if temp_mc >= t56 { TempZone::B56 }
-            else if temp_mc < t55 - h { TempZone::B54 }
-            else { TempZone::B55 }
+        let (t0, _) = *self.samples.front()?;
+        // Need a few points spread over a meaningful span, or quantization dominates.
+        if self.samples.len() < 3 || now.duration_since(t0) < window / 4 {
+            return None;
         }
-        TempZone::B56 => {
-            if temp_mc >= t58 { TempZone::B58 }
-            else if temp_mc >= t57 { TempZone::B57 }
-            else if temp_mc < t56 - h { TempZone::B55 }
-            else { TempZone::B56 }
+
+        let n = self.samples.len() as f32;
+        let xs = || self.samples.iter().map(|&(t, v)| (t.duration_since(t0).as_secs_f32(), v as f32));
+        let mx = xs().map(|(x, _)| x).sum::<f32>() / n;
+        let my = xs().map(|(_, y)| y).sum::<f32>() / n;
+        let (num, den) = xs().fold((0.0, 0.0), |(num, den), (x, y)| {
+            (num + (x - mx) * (y - my), den + (x - mx) * (x - mx))
+        });
+        if den <= f32::EPSILON { None } else { Some(num / den) }
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+}
+
+/// Temperature the curve should see: the reading pushed ahead along a steep slope
+/// (earlier throttling on a rise, earlier release on a fast cool-down).
+pub fn predicted_temp_mc(temp_mc: i32, slope_mc_s: Option<f32>, cfg: &ThermalPredictConfig) -> i32 {
+    let Some(slope) = slope_mc_s else { return temp_mc };
+    let per_min = slope * 60.0 / 1000.0;
+    let lead = if per_min >= cfg.rise_c_per_min || per_min <= -cfg.fall_c_per_min {
+        slope * cfg.lead_s
+    } else {
+        return temp_mc;
+    };
+    let max_lead = cfg.max_lead_c * 1000.0;
+    temp_mc + lead.clamp(-max_lead, max_lead).round() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Band (0 = Cool) the hardcoded B50..B58 ladder picked before the curve was configurable.
+    fn old_ladder(temp_mc: i32, prev: usize) -> usize {
+        const T: [i32; 9] = [50_000, 51_000, 52_000, 53_000, 54_000, 55_000, 56_000, 57_000, 58_000];
+        const H: i32 = 500;
+        // Every band first checks the hotter ones from B58 down, then its own exit.
+        if let Some(i) = (prev..T.len()).rev().find(|&i| temp_mc >= T[i]) {
+            return i + 1;
         }
-        TempZone::B57 => {
-            if temp_mc >= t58 { TempZone::B58 }
-            else if temp_mc < t57 - h { TempZone::B56 }
-            else { TempZone::B57 }
+        if prev > 0 && temp_mc < T[prev - 1] - H { prev - 1 } else { prev }
+    }
+
+    const OLD_REDUCTION: [u32; 10] = [0, 10, 15, 20, 25, 30, 35, 45, 55, 75];
+
+    #[test]
+    fn default_curve_matches_old_ladder() {
+        let curve = ThermalCurveConfig::default();
+        for prev in 0..=9usize {
+            let prev_zone = TempZone::from_curve(&curve, prev);
+            for temp_mc in (45_000..=60_000).step_by(50) {
+                let z = zone_with_hysteresis(temp_mc, prev_zone, &curve);
+                let want = old_ladder(temp_mc, prev);
+                assert_eq!(z.band as usize, want, "{} m°C from band {}", temp_mc, prev);
+                assert_eq!(z.reduction_percent(), OLD_REDUCTION[want]);
+            }
         }
-        TempZone::B58 => {
-            if temp_mc < t58 - h { TempZone::B57 }
-            else { TempZone::B58 }
+    }
+
+    #[test]
+    fn climbs_at_once_and_falls_one_band_at_a_time() {
+        let curve = ThermalCurveConfig::default();
+        let z = zone_with_hysteresis(57_200, TempZone::COOL, &curve);
+        assert_eq!(z.to_string(), "B57");
+        // Inside the hysteresis: stays.
+        let z = zone_with_hysteresis(56_600, z, &curve);
+        assert_eq!(z.to_string(), "B57");
+        // Far below: still only one band per step.
+        let z = zone_with_hysteresis(40_000, z, &curve);
+        assert_eq!(z.to_string(), "B56");
+    }
+
+    #[test]
+    fn shrunk_curve_clamps_previous_band() {
+        let mut curve = ThermalCurveConfig::default();
+        let prev = zone_with_hysteresis(58_000, TempZone::COOL, &curve);
+        curve.bands.truncate(3);
+        let z = zone_with_hysteresis(52_200, prev, &curve);
+        assert_eq!(z.band, 3);
+    }
+
+    fn feed(est: &mut SlopeEstimator, t0: Instant, secs: u64, temp: impl Fn(u64) -> i32) -> Option<f32> {
+        let window = Duration::from_secs(12);
+        let mut out = None;
+        for s in 0..=secs {
+            out = est.update(t0 + Duration::from_secs(s), temp(s), window);
         }
+        out
+    }
+
+    #[test]
+    fn slope_needs_history() {
+        let mut est = SlopeEstimator::default();
+        let t0 = Instant::now();
+        assert_eq!(est.update(t0, 50_000, Duration::from_secs(12)), None);
+        assert_eq!(est.update(t0 + Duration::from_secs(1), 50_100, Duration::from_secs(12)), None);
+        // Three points but only 2 s of a 12 s window.
+        assert_eq!(est.update(t0 + Duration::from_secs(2), 50_200, Duration::from_secs(12)), None);
+        assert!(est.update(t0 + Duration::from_secs(3), 50_300, Duration::from_secs(12)).is_some());
+    }
+
+    #[test]
+    fn slope_fits_a_ramp_and_forgets_old_samples() {
+        let mut est = SlopeEstimator::default();
+        let t0 = Instant::now();
+        // 100 m°C/s ramp.
+        let s = feed(&mut est, t0, 20, |s| 50_000 + 100 * s as i32).unwrap();
+        assert!((s - 100.0).abs() < 0.01, "{}", s);
+        // Flat afterwards: once the ramp leaves the window the slope is 0.
+        let s = feed(&mut est, t0 + Duration::from_secs(21), 20, |_| 52_000).unwrap();
+        assert!(s.abs() < 0.01, "{}", s);
+    }
+
+    #[test]
+    fn slope_tolerates_quantized_noise() {
+        let mut est = SlopeEstimator::default();
+        let t0 = Instant::now();
+        // 1 °C sensor steps on a 100 m°C/s rise: about one step per window still reads as the rise.
+        let s = feed(&mut est, t0, 30, |s| (50_000 + 100 * s as i32) / 1000 * 1000).unwrap();
+        assert!((50.0..150.0).contains(&s), "{}", s);
+    }
+
+    #[test]
+    fn prediction_leads_only_steep_slopes() {
+        let cfg = ThermalPredictConfig::default();
+        // 4 °C/min = 66.7 m°C/s is the default threshold.
+        assert_eq!(predicted_temp_mc(50_000, Some(50.0), &cfg), 50_000);
+        assert_eq!(predicted_temp_mc(50_000, None, &cfg), 50_000);
+        // 100 m°C/s over 20 s = 2 °C ahead.
+        assert_eq!(predicted_temp_mc(50_000, Some(100.0), &cfg), 52_000);
+        // Capped at max_lead_c either way.
+        assert_eq!(predicted_temp_mc(50_000, Some(500.0), &cfg), 53_000);
+        assert_eq!(predicted_temp_mc(50_000, Some(-500.0), &cfg), 47_000);
     }
 }
@@ -1,38 +1,141 @@
 
-use std::path::PathBuf;
+use std::{fs, path::PathBuf, sync::OnceLock};
 
-use crate::{config::{BAT_ZONE_ID, CPU_ZONE_IDS, GPU_ZONE_IDS}, sysfs};
+use serde::Serialize;
 
-pub fn zone_temp_path(id: u32) -> PathBuf {
-    PathBuf::from(format!("/sys/class/thermal/thermal_zone{}/temp", id))
+use crate::{
+    device::{self, DeviceDescriptor},
+    sysfs,
+    user_config::{ControlTempFormula, ControlTempSource},
+};
+
+const THERMAL_DIR: &str = "/sys/class/thermal";
+
+static ZONES: OnceLock<ZoneMap> = OnceLock::new();
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Zone {
+    pub id: u32,
+    #[serde(rename = "type")]
+    pub ty: String,
+}
+
+/// Sensors attributed to one CPU cluster or the GPU.
+#[derive(Clone, Debug, Serialize)]
+pub struct DomainZones {
+    pub label: String,
+    pub zones: Vec<Zone>,
+}
+
+/// Thermal zones resolved from the device descriptor (by type pattern or explicit id).
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ZoneMap {
+    pub cpu: Vec<Zone>,
+    pub gpu: Vec<Zone>,
+    pub battery: Option<Zone>,
+    /// Per-domain sensors, in domain order (CPU clusters, then GPU).
+    pub domains: Vec<DomainZones>,
 }
 
-pub fn build_paths(ids: &[u32]) -> Vec<PathBuf> {
-    let mut v = Vec::new();
-    for &id in ids {
-        let p = zone_temp_path(id);
-        if p.exists() {
-            v.push(p);
+impl ZoneMap {
+    fn ids_str(zones: &[Zone]) -> String {
+        zones.iter().map(|z| format!("{}:{}", z.id, z.ty)).collect::<Vec<_>>().join(" ")
+    }
+
+    pub fn log(&self) {
+        println!("THERM: cpu [{}]", Self::ids_str(&self.cpu));
+        println!("THERM: gpu [{}]", Self::ids_str(&self.gpu));
+        println!("THERM: bat [{}]", Self::ids_str(self.battery.as_slice()));
+        for d in &self.domains {
+            println!("THERM: {} [{}]", d.label, Self::ids_str(&d.zones));
         }
     }
-    v
 }
 
-pub fn battery_path() -> Option<PathBuf> {
-    let p = zone_temp_path(BAT_ZONE_ID);
-    if p.exists() { Some(p) } else { None }
+pub fn zone_temp_path(id: u32) -> PathBuf {
+    sysfs::path(format!("{}/thermal_zone{}/temp", THERMAL_DIR, id))
 }
 
-pub fn read_avg_temp_mc(paths: &[PathBuf]) -> Option<i32> {
-    let mut sum: i64 = 0;
-    let mut n: i64 = 0;
-    for p in paths {
-        if let Some(v) = sysfs::read_i32(p) {
-            sum += v as i64;
-            n += 1;
+/// Minimal glob: `*` matches any run of characters, `?` exactly one.
+pub fn glob_match(pat: &str, s: &str) -> bool {
+    let p: Vec<char> = pat.chars().collect();
+    let t: Vec<char> = s.chars().collect();
+    let (mut pi, mut ti) = (0usize, 0usize);
+    let mut star: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if let Some((sp, st)) = star {
+            pi = sp + 1;
+            ti = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
         }
     }
-    if n == 0 { None } else { Some((sum / n) as i32) }
+    while pi < p.len() && p[pi] == '*' {
+        pi += 1;
+    }
+    pi == p.len()
+}
+
+/// All thermal zones with a readable temperature, sorted by id.
+fn scan_zones() -> Vec<Zone> {
+    let mut out = Vec::new();
+    let Ok(rd) = fs::read_dir(sysfs::path(THERMAL_DIR)) else { return out };
+    for ent in rd.flatten() {
+        let name = ent.file_name().to_string_lossy().to_string();
+        let Some(id) = name.strip_prefix("thermal_zone").and_then(|n| n.parse::<u32>().ok()) else { continue };
+        if !ent.path().join("temp").exists() {
+            continue;
+        }
+        let ty = sysfs::read_to_string(&ent.path().join("type")).unwrap_or_default().trim().to_string();
+        out.push(Zone { id, ty });
+    }
+    out.sort_by_key(|z| z.id);
+    out
+}
+
+fn pick(all: &[Zone], patterns: &[String], ids: &[u32]) -> Vec<Zone> {
+    if patterns.is_empty() {
+        return all.iter().filter(|z| ids.contains(&z.id)).cloned().collect();
+    }
+    all.iter()
+        .filter(|z| patterns.iter().any(|p| glob_match(p, &z.ty)))
+        .cloned()
+        .collect()
+}
+
+pub fn resolve_zones(dev: &DeviceDescriptor) -> ZoneMap {
+    let t = &dev.thermal;
+    let all = scan_zones();
+    let battery_ids: Vec<u32> = t.battery_zone_id.into_iter().collect();
+    let cpu = pick(&all, &t.cpu_types, &t.cpu_zone_ids);
+    let gpu = pick(&all, &t.gpu_types, &t.gpu_zone_ids);
+
+    // A domain without its own patterns (or whose patterns match nothing) falls back to its group.
+    let domain = |label: &str, types: &[String], group: &[Zone]| {
+        let own = if types.is_empty() { Vec::new() } else { pick(&all, types, &[]) };
+        DomainZones { label: label.to_string(), zones: if own.is_empty() { group.to_vec() } else { own } }
+    };
+    let mut domains: Vec<DomainZones> = dev.clusters.iter().map(|c| domain(&c.label, &c.thermal_types, &cpu)).collect();
+    domains.push(domain("GPU", &dev.gpu.thermal_types, &gpu));
+
+    ZoneMap {
+        cpu,
+        gpu,
+        battery: pick(&all, &t.battery_types, &battery_ids).into_iter().next(),
+        domains,
+    }
+}
+
+/// Zones for the active device, resolved once.
+pub fn zones() -> &'static ZoneMap {
+    ZONES.get_or_init(|| resolve_zones(device::get()))
 }
 
 pub fn read_soc_temp_mc(cpu_avg: Option<i32>, gpu_avg: Option<i32>) -> Option<i32> {
@@ -44,10 +147,21 @@ pub fn read_soc_temp_mc(cpu_avg: Option<i32>, gpu_avg: Option<i32>) -> Option<i3
     }
 }
 
-pub fn read_control_temp_mc(batt_mc: Option<i32>, cpu_avg: Option<i32>, gpu_avg: Option<i32>) -> Option<i32> {
-    batt_mc.or_else(|| read_soc_temp_mc(cpu_avg, gpu_avg))
-}
-
-pub fn describe_paths() -> (Vec<PathBuf>, Vec<PathBuf>, Option<PathBuf>) {
-    (build_paths(CPU_ZONE_IDS), build_paths(GPU_ZONE_IDS), battery_path())
+/// Control temperature per `config.json` -> `control_temp` (default: battery, SoC fallback).
+pub fn read_control_temp_mc(f: &ControlTempFormula, batt_mc: Option<i32>, cpu_avg: Option<i32>, gpu_avg: Option<i32>) -> Option<i32> {
+    let soc_mc = read_soc_temp_mc(cpu_avg, gpu_avg);
+    let base = match f.source {
+        ControlTempSource::Battery => batt_mc.or(soc_mc),
+        ControlTempSource::Soc => soc_mc.or(batt_mc),
+        ControlTempSource::Max => batt_mc.max(soc_mc),
+        ControlTempSource::Blend => {
+            let b = batt_mc.or(soc_mc);
+            let s = soc_mc.or(batt_mc);
+            match (b, s) {
+                (Some(b), Some(s)) => Some((f.batt_weight * b as f32 + f.soc_weight * s as f32).round() as i32),
+                _ => None,
+            }
+        }
+    }?;
+    Some(base + (f.offset_c * 1000.0).round() as i32)
 }
@@ -0,0 +1,168 @@
+//! Loop trace recorder.
+//!
+//! When `trace.enabled` is set, every loop iteration is appended to trace.jsonl as one line:
+//! the sampled `Inputs` (flattened, same keys as a `--simulate` frame) plus an `out` object with
+//! what the daemon decided. A recorded file can therefore be replayed as-is with
+//! `perf_daemon --simulate trace.jsonl` and the `out` values compared to the replay.
+//!
+//! Each recording session starts a fresh file (`t_ms` from 0); the previous one is kept as
+//! `trace.jsonl.1`. The same rotation happens when the file exceeds `trace.max_kb`.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+
+use crate::{
+    control::{Decisions, Inputs},
+    tempzone::TempZone,
+    user_config::TraceConfig,
+};
+
+#[derive(Serialize)]
+struct OutCap {
+    label: &'static str,
+    max: u64,
+}
+
+#[derive(Serialize)]
+struct Out<'a> {
+    zone: TempZone,
+    effective_zone: TempZone,
+    caps: Vec<OutCap>,
+    fan_level: u8,
+    offline_cores: &'a [usize],
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    t_ms: u64,
+    wall_ms: u64,
+    #[serde(flatten)]
+    inputs: &'a Inputs,
+    out: Out<'a>,
+}
+
+pub struct TraceRecorder {
+    path: PathBuf,
+    file: Option<File>,
+    started: Instant,
+    written: u64,
+    last_error: Option<String>,
+}
+
+fn rotated(path: &Path) -> PathBuf {
+    let mut s = path.as_os_str().to_owned();
+    s.push(".1");
+    PathBuf::from(s)
+}
+
+fn wall_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+impl TraceRecorder {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path, file: None, started: Instant::now(), written: 0, last_error: None }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.file.is_some()
+    }
+
+    /// Bytes in the active trace file.
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    pub fn last_error(&self) -> Option<String> {
+        self.last_error.clone()
+    }
+
+    /// Move the current file to `.1` and start an empty one.
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file = None;
+        if self.path.exists() {
+            fs::rename(&self.path, rotated(&self.path))?;
+        }
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let f = OpenOptions::new().create(true).write(true).truncate(true).open(&self.path)?;
+        self.file = Some(f);
+        self.written = 0;
+        Ok(())
+    }
+
+    /// Append one iteration (or close the file when tracing is disabled).
+    pub fn record(&mut self, cfg: &TraceConfig, now: Instant, inputs: &Inputs, d: &Decisions) {
+        if !cfg.enabled {
+            if self.file.take().is_some() {
+                println!("TRACE: stop ({} bytes)", self.written);
+            }
+            return;
+        }
+
+        if self.file.is_none() {
+            match self.rotate() {
+                Ok(()) => {
+                    self.started = now;
+                    self.last_error = None;
+                    println!("TRACE: start {}", self.path.display());
+                }
+                Err(e) => {
+                    // Report once; retry on the next iteration.
+                    if self.last_error.is_none() {
+                        println!("TRACE: open failed: {}", e);
+                    }
+                    self.last_error = Some(e.to_string());
+                    return;
+                }
+            }
+        }
+
+        let rec = Record {
+            t_ms: now.duration_since(self.started).as_millis() as u64,
+            wall_ms: wall_ms(),
+            inputs,
+            out: Out {
+                zone: d.zone,
+                effective_zone: d.effective_zone,
+                caps: d.caps.iter().map(|c| OutCap { label: c.label, max: c.max }).collect(),
+                fan_level: d.fan_level,
+                offline_cores: &d.offline_cores,
+            },
+        };
+        let mut line = match serde_json::to_vec(&rec) {
+            Ok(v) => v,
+            Err(_) => return,
+        };
+        line.push(b'\n');
+
+        let cap = cfg.max_kb as u64 * 1024;
+        if self.written + line.len() as u64 > cap {
+            if let Err(e) = self.rotate() {
+                self.last_error = Some(e.to_string());
+                return;
+            }
+        }
+
+        if let Some(f) = self.file.as_mut() {
+            match f.write_all(&line) {
+                Ok(()) => self.written += line.len() as u64,
+                Err(e) => {
+                    println!("TRACE: write failed: {}", e);
+                    self.last_error = Some(e.to_string());
+                    self.file = None;
+                }
+            }
+        }
+    }
+}
@@ -51,6 +51,8 @@ use std::{
 
 use libc::c_int;
 
+use crate::{device, shutdown, sysfs};
+
 // ----------------- Linux input constants -----------------
 const EV_SYN: u16 = 0x00;
 const EV_KEY: u16 = 0x01;
@@ -114,8 +116,6 @@ const EVIOCGRAB: u32 = iow(b'E', 0x90, 4);
 const BUS_VIRTUAL: u16 = 0x06;
 
 const TRIGGER_ENABLE_SETTING: &str = "nubia_parts_trigger_enable";
-const TRIGGER_LEFT_MODE_PATH: &str = "/proc/nubia_key/sar0/mode_operation";
-const TRIGGER_RIGHT_MODE_PATH: &str = "/proc/nubia_key/sar1/mode_operation";
 const TRIGGER_MODE_ON: &str = "1";
 const TRIGGER_MODE_OFF: &str = "0";
 
@@ -165,6 +165,9 @@ fn read_to_string(path: &Path) -> io::Result<String> {
 }
 
 fn set_nubia_parts_trigger_enable(enable: bool) {
+    if sysfs::is_relocated() {
+        return;
+    }
     let val = if enable { "1" } else { "0" };
 
     let st = Command::new("settings")
@@ -184,26 +187,28 @@ fn set_nubia_parts_trigger_enable(enable: bool) {
 }
 
 fn write_trigger_mode(path: &str, mode: &str) {
-    let p = Path::new(path);
+    let p = sysfs::path(path);
     if !p.exists() {
         eprintln!("TRIGSYS: {} missing", path);
         return;
     }
-    if let Err(e) = fs::write(p, format!("{}\n", mode).as_bytes()) {
+    shutdown::record(&p);
+    if let Err(e) = fs::write(&p, format!("{}\n", mode).as_bytes()) {
         eprintln!("TRIGSYS: write {}={} failed: {}", path, mode, e);
     }
 }
 
 fn set_system_triggers_enabled(enable: bool) {
+    let dev = &device::get().triggers;
     if enable {
         set_nubia_parts_trigger_enable(true);
-        write_trigger_mode(TRIGGER_LEFT_MODE_PATH, TRIGGER_MODE_ON);
-        write_trigger_mode(TRIGGER_RIGHT_MODE_PATH, TRIGGER_MODE_ON);
+        write_trigger_mode(&dev.left_mode, TRIGGER_MODE_ON);
+        write_trigger_mode(&dev.right_mode, TRIGGER_MODE_ON);
         println!("TRIGSYS: enabled");
     } else {
         set_nubia_parts_trigger_enable(false);
-        write_trigger_mode(TRIGGER_LEFT_MODE_PATH, TRIGGER_MODE_OFF);
-        write_trigger_mode(TRIGGER_RIGHT_MODE_PATH, TRIGGER_MODE_OFF);
+        write_trigger_mode(&dev.left_mode, TRIGGER_MODE_OFF);
+        write_trigger_mode(&dev.right_mode, TRIGGER_MODE_OFF);
         println!("TRIGSYS: disabled");
     }
 }
@@ -231,10 +236,10 @@ fn cap_has(words: &[u64], bit: u32) -> bool {
 }
 
 fn sys_event_dir(ev: &str) -> PathBuf {
-    PathBuf::from("/sys/class/input").join(ev).join("device")
+    sysfs::path("/sys/class/input").join(ev).join("device")
 }
 fn devnode(ev: &str) -> PathBuf {
-    PathBuf::from("/dev/input").join(ev)
+    sysfs::path("/dev/input").join(ev)
 }
 
 #[derive(Clone, Debug)]
@@ -250,8 +255,8 @@ struct EventDevInfo {
 
 fn scan_input_devices() -> io::Result<Vec<EventDevInfo>> {
     let mut out = Vec::new();
-    let dir = Path::new("/sys/class/input");
-    for ent in fs::read_dir(dir)? {
+    let dir = sysfs::path("/sys/class/input");
+    for ent in fs::read_dir(&dir)? {
         let ent = ent?;
         let ev = ent.file_name().to_string_lossy().to_string();
         if !ev.starts_with("event") {
@@ -347,8 +352,8 @@ fn read_abs_range(sysdir: &Path, abs_name: &str) -> Option<(i32, i32)> {
 }
 
 fn discover_screen_size() -> (i32, i32) {
-    let p = Path::new("/sys/class/graphics/fb0/virtual_size");
-    if let Ok(s) = read_to_string(p) {
+    let p = sysfs::path("/sys/class/graphics/fb0/virtual_size");
+    if let Ok(s) = read_to_string(&p) {
         let t = s.trim();
         if let Some((a, b)) = t.split_once(',') {
             if let (Ok(w), Ok(h)) = (a.trim().parse::<i32>(), b.trim().parse::<i32>()) {
@@ -415,11 +420,11 @@ fn open_uinput() -> io::Result<fs::File> {
         .read(true)
         .write(true)
         .custom_flags(libc::O_CLOEXEC | libc::O_NONBLOCK)
-        .open("/dev/uinput")
+        .open(sysfs::path("/dev/uinput"))
 }
 
 fn xioctl(fd: RawFd, req: u32, arg: c_int) -> io::Result<()> {
-    let r = unsafe { libc::ioctl(fd, req as c_int, arg) };
+    let r = unsafe { libc::ioctl(fd, req as _, arg) };
     if r < 0 {
         Err(io::Error::last_os_error())
     } else {
@@ -428,7 +433,7 @@ fn xioctl(fd: RawFd, req: u32, arg: c_int) -> io::Result<()> {
 }
 
 fn xioctl_void(fd: RawFd, req: u32) -> io::Result<()> {
-    let r = unsafe { libc::ioctl(fd, req as c_int) };
+    let r = unsafe { libc::ioctl(fd, req as _) };
     if r < 0 {
         Err(io::Error::last_os_error())
     } else {
@@ -834,7 +839,7 @@ impl Drop for TriggerManager {
         {
             let mut uif = self.inner.uif.lock().unwrap();
             force_release_all(&mut uif, self.ranges.slot_max + 2);
-            let _ = unsafe { libc::ioctl(uif.as_raw_fd(), UI_DEV_DESTROY as c_int) };
+            let _ = unsafe { libc::ioctl(uif.as_raw_fd(), UI_DEV_DESTROY as _) };
         }
     }
 }
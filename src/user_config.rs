@@ -12,6 +12,14 @@ pub const CONFIG_PATH: &str = "/data/adb/modules/mora_perf_deamon/config/config.
 
 pub const GAMES_PATH: &str = "/data/adb/modules/mora_perf_deamon/config/games.json";
 
+pub const TRACE_PATH: &str = "/data/adb/modules/mora_perf_deamon/config/trace.jsonl";
+
+pub const RESTORE_JOURNAL_PATH: &str = "/data/adb/modules/mora_perf_deamon/config/restore_journal.json";
+
+pub const THERMAL_SNAPSHOT_PATH: &str = "/data/adb/modules/mora_perf_deamon/config/thermal_snapshot.json";
+
+pub const FAN_MAINTENANCE_PATH: &str = "/data/adb/modules/mora_perf_deamon/config/fan_maintenance.json";
+
 
 fn default_true() -> bool { true }
 
@@ -34,12 +42,78 @@ pub struct UserConfig {
     #[serde(default = "default_true")]
     pub use_phone_cooler: bool,
 
+    /// Stop the vendor thermal stack and detach kernel thermal from CPU/GPU.
+    /// If false, stock thermal is left alone (and restored if the daemon had taken it over).
+    #[serde(default = "default_true")]
+    pub thermal_takeover: bool,
+
     /// Smart battery saver: disables CPU cores based on battery %, but never during charging or in games.
     #[serde(default)]
     pub battery_saver: BatterySaverConfig,
 
     #[serde(default)]
     pub charging: ChargingConfig,
+
+    /// Loop trace recorder (inputs + decisions as JSONL, replayable with `--simulate`).
+    #[serde(default)]
+    pub trace: TraceConfig,
+
+    /// Thermal throttling curve: bands of (threshold, reduction, hysteresis, step-down rate).
+    #[serde(default)]
+    pub thermal_curve: ThermalCurveConfig,
+
+    /// Thermal controller: step zones from `thermal_curve`, or a PID holding a setpoint.
+    #[serde(default)]
+    pub thermal_control: ThermalControlConfig,
+
+    /// Scale each domain's thermal cut by how close it runs to the hotspot domain.
+    #[serde(default)]
+    pub thermal_attribution: ThermalAttributionConfig,
+
+    /// Feed the curve a temperature extrapolated along a steep dT/dt.
+    #[serde(default)]
+    pub thermal_predict: ThermalPredictConfig,
+
+    /// How the control temperature is derived from battery/SoC sensors, per state.
+    #[serde(default)]
+    pub control_temp: ControlTempConfig,
+
+    /// Thermal sensor sanity checks (range, stuck, outlier) and re-inclusion.
+    #[serde(default)]
+    pub sensor_health: SensorHealthConfig,
+
+    /// Critical / shutdown-warning temperatures; not bypassable by any game setting.
+    #[serde(default)]
+    pub critical: CriticalConfig,
+
+    /// Control-loop stall detection: safe fan/caps and optional re-exec.
+    #[serde(default)]
+    pub watchdog: WatchdogConfig,
+
+    /// Step-up/step-down thresholds and base frequency of the CPU/GPU domains.
+    #[serde(default)]
+    pub domain_tuning: DomainTuningConfig,
+
+    /// Battery / Balanced / Performance preset, applied over the active profile.
+    #[serde(default)]
+    pub perf_preset: PerfPreset,
+
+    /// Fan level curves: SoC, battery while charging, and an optional game curve.
+    #[serde(default)]
+    pub fan_curves: FanCurvesConfig,
+
+    /// Fan mode: static curves, or a closed loop holding a temperature setpoint.
+    #[serde(default)]
+    pub fan_control: FanControlConfig,
+
+    /// Periodic dust-clearing run of the cooler while charging with the screen off.
+    #[serde(default)]
+    pub fan_maintenance: FanMaintenanceConfig,
+
+    /// Time-of-day overrides (quiet hours); the first enabled window containing now applies.
+    #[serde(default)]
+    pub schedules: Vec<ScheduleConfig>,
+
     pub notifications: NotificationsConfig,
     pub fan_led: FanLedDefaults,
     pub profiles: Vec<ProfileConfig>,
@@ -54,8 +128,24 @@ impl Default for UserConfig {
             api_token: String::new(),
             daemon_notifications: true,
             use_phone_cooler: true,
+            thermal_takeover: true,
             battery_saver: BatterySaverConfig::default(),
             charging: ChargingConfig::default(),
+            trace: TraceConfig::default(),
+            thermal_curve: ThermalCurveConfig::default(),
+            thermal_control: ThermalControlConfig::default(),
+            thermal_attribution: ThermalAttributionConfig::default(),
+            thermal_predict: ThermalPredictConfig::default(),
+            control_temp: ControlTempConfig::default(),
+            sensor_health: SensorHealthConfig::default(),
+            critical: CriticalConfig::default(),
+            watchdog: WatchdogConfig::default(),
+            domain_tuning: DomainTuningConfig::default(),
+            perf_preset: PerfPreset::default(),
+            fan_curves: FanCurvesConfig::default(),
+            fan_control: FanControlConfig::default(),
+            fan_maintenance: FanMaintenanceConfig::default(),
+            schedules: Vec::new(),
             notifications: NotificationsConfig::default(),
             fan_led: FanLedDefaults::default(),
             profiles: vec![
@@ -96,10 +186,28 @@ impl Default for ChargingConfig {
     }
 }
 
+/// Run a section's validator; a section it still rejects goes back to its default.
+fn checked<T: Default>(resets: &mut Vec<String>, section: &mut T, validate: impl FnOnce(&mut T) -> Result<(), String>) {
+    if let Err(e) = validate(section) {
+        resets.push(format!("{} (reset to default)", e));
+        *section = T::default();
+    }
+}
+
+/// A NaN/inf from a hand-edited file falls back to the default.
+fn finite_or(v: &mut f32, def: f32) {
+    if !v.is_finite() {
+        *v = def;
+    }
+}
+
 impl UserConfig {
-    pub fn validate_and_normalize(&mut self) -> Result<(), String> {
+    /// Normalize in place. Nothing is rejected as a whole: a section that is still unusable
+    /// goes back to its default (a profile or schedule is disabled instead). Returns what was
+    /// reset, empty if nothing was.
+    pub fn validate_and_normalize(&mut self) -> Vec<String> {
         // Keep validation minimal for stability: do not enforce unique priorities/names.
-        // UI manages only Normal/Gaming, and custom profiles are ignored.
+        // UI manages only Normal/Gaming; custom profiles are activated by their `when` rules.
 
         // Ensure at least one Normal profile exists.
         let has_normal = self
@@ -123,7 +231,50 @@ impl UserConfig {
         // (UI only exposes supported values, but config files may contain older ones.)
         self.normalize_leds();
 
-        Ok(())
+        self.trace.max_kb = self.trace.max_kb.clamp(TRACE_MIN_KB, TRACE_MAX_KB);
+
+        let mut resets = Vec::new();
+        checked(&mut resets, &mut self.thermal_curve, ThermalCurveConfig::validate_and_normalize);
+        self.thermal_control.pid.validate_and_normalize();
+        finite_or(&mut self.thermal_attribution.spread_c, ThermalAttributionConfig::default().spread_c);
+        self.thermal_attribution.spread_c = self.thermal_attribution.spread_c.clamp(1.0, 30.0);
+        self.thermal_attribution.min_share_percent = self.thermal_attribution.min_share_percent.min(100);
+        self.thermal_predict.validate_and_normalize();
+        self.control_temp.normal.validate_and_normalize();
+        if let Some(f) = &mut self.control_temp.charging {
+            f.validate_and_normalize();
+        }
+        if let Some(f) = &mut self.control_temp.gaming {
+            f.validate_and_normalize();
+        }
+        self.sensor_health.validate_and_normalize();
+        self.critical.validate_and_normalize();
+        self.watchdog.validate_and_normalize();
+        self.domain_tuning.validate_and_normalize();
+        // A profile whose rule can't be read is switched off; an already disabled one is only
+        // normalized, so it doesn't keep the rest of the config from being saved.
+        for p in &mut self.profiles {
+            if let Err(e) = p.validate_and_normalize() {
+                if p.enabled {
+                    resets.push(format!("{} (profile disabled)", e));
+                    p.enabled = false;
+                }
+            }
+        }
+        checked(&mut resets, &mut self.fan_curves, FanCurvesConfig::validate_and_normalize);
+        self.fan_control.setpoint.validate_and_normalize();
+        self.fan_maintenance.validate_and_normalize();
+        // Same for schedules.
+        for s in &mut self.schedules {
+            if let Err(e) = s.validate_and_normalize() {
+                if s.enabled {
+                    resets.push(format!("{} (schedule disabled)", e));
+                    s.enabled = false;
+                }
+            }
+        }
+
+        resets
     }
 
     fn normalize_leds(&mut self) {
@@ -183,6 +334,619 @@ impl Default for BatterySaverConfig {
     }
 }
 
+fn default_band_hysteresis_c() -> f32 { 0.5 }
+fn default_band_max_step_down() -> u8 { 1 }
+
+pub const THERMAL_CURVE_MAX_BANDS: usize = 32;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ThermalBand {
+    /// Band is entered at or above this control temperature (°C).
+    pub temp_c: f32,
+    /// Max-frequency reduction applied to every domain in this band (0..95 %).
+    pub reduce_percent: u8,
+    /// Band is left (one band down) below `temp_c - hysteresis_c`.
+    #[serde(default = "default_band_hysteresis_c")]
+    pub hysteresis_c: f32,
+    /// Max table steps a cap may drop per loop iteration in this band (1..10).
+    #[serde(default = "default_band_max_step_down")]
+    pub max_step_down: u8,
+    /// Poll at the fastest interval while in this band.
+    #[serde(default)]
+    pub fast_poll: bool,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+impl ThermalBand {
+    fn new(temp_c: f32, reduce_percent: u8, max_step_down: u8, fast_poll: bool) -> Self {
+        Self {
+            temp_c,
+            reduce_percent,
+            hysteresis_c: default_band_hysteresis_c(),
+            max_step_down,
+            fast_poll,
+            extra: BTreeMap::new(),
+        }
+    }
+
+    pub fn threshold_mc(&self) -> i32 {
+        (self.temp_c * 1000.0).round() as i32
+    }
+
+    pub fn hysteresis_mc(&self) -> i32 {
+        (self.hysteresis_c * 1000.0).round() as i32
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ThermalCurveConfig {
+    pub bands: Vec<ThermalBand>,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+impl Default for ThermalCurveConfig {
+    /// The original B50..B58 ladder.
+    fn default() -> Self {
+        Self {
+            bands: vec![
+                ThermalBand::new(50.0, 10, 1, false),
+                ThermalBand::new(51.0, 15, 1, false),
+                ThermalBand::new(52.0, 20, 1, false),
+                ThermalBand::new(53.0, 25, 1, false),
+                ThermalBand::new(54.0, 30, 1, false),
+                ThermalBand::new(55.0, 35, 2, false),
+                ThermalBand::new(56.0, 45, 2, true),
+                ThermalBand::new(57.0, 55, 3, true),
+                ThermalBand::new(58.0, 75, 4, true),
+            ],
+            extra: BTreeMap::new(),
+        }
+    }
+}
+
+impl ThermalCurveConfig {
+    /// Sort by threshold, clamp per-band values, merge duplicate thresholds (the stronger band
+    /// stays) and keep `reduce_percent` from decreasing. Only a curve without bands is unusable.
+    pub fn validate_and_normalize(&mut self) -> Result<(), String> {
+        self.bands.retain(|b| b.temp_c.is_finite());
+        if self.bands.is_empty() {
+            return Err("thermal_curve: at least one band is required".to_string());
+        }
+        for b in &mut self.bands {
+            b.temp_c = b.temp_c.clamp(20.0, 100.0);
+            if !b.hysteresis_c.is_finite() {
+                b.hysteresis_c = default_band_hysteresis_c();
+            }
+            b.hysteresis_c = b.hysteresis_c.clamp(0.0, 5.0);
+            b.reduce_percent = b.reduce_percent.min(95);
+            b.max_step_down = b.max_step_down.clamp(1, 10);
+        }
+        self.bands.sort_by_key(|b| b.threshold_mc());
+        self.bands.dedup_by(|next, kept| {
+            let same = next.threshold_mc() == kept.threshold_mc();
+            if same && next.reduce_percent > kept.reduce_percent {
+                std::mem::swap(next, kept);
+            }
+            same
+        });
+        // Too many bands: the hottest ones are kept.
+        let extra = self.bands.len().saturating_sub(THERMAL_CURVE_MAX_BANDS);
+        self.bands.drain(..extra);
+        let mut floor = 0;
+        for b in &mut self.bands {
+            b.reduce_percent = b.reduce_percent.max(floor);
+            floor = b.reduce_percent;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ThermalControlMode {
+    /// Band ladder from `thermal_curve`.
+    #[default]
+    Zones,
+    /// Continuous cap from `thermal_control.pid`.
+    Pid,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PidConfig {
+    /// Target control temperature (°C) per state; charging while gaming uses the lower one.
+    pub setpoint_normal_c: f32,
+    pub setpoint_gaming_c: f32,
+    pub setpoint_charging_c: f32,
+
+    /// Reduction fraction per °C above the setpoint.
+    pub kp: f32,
+    /// Reduction fraction per °C·s of accumulated error.
+    pub ki: f32,
+    /// Reduction fraction per °C/s of temperature rise.
+    pub kd: f32,
+
+    /// Upper bound of the output (0..95 %).
+    pub max_reduce_percent: u8,
+    /// Max table steps a cap may drop per loop iteration (1..10).
+    pub max_step_down: u8,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+impl Default for PidConfig {
+    fn default() -> Self {
+        Self {
+            setpoint_normal_c: 52.0,
+            setpoint_gaming_c: 55.0,
+            setpoint_charging_c: 50.0,
+            kp: 0.04,
+            ki: 0.002,
+            kd: 0.05,
+            max_reduce_percent: 75,
+            max_step_down: 2,
+            extra: BTreeMap::new(),
+        }
+    }
+}
+
+impl PidConfig {
+    pub fn validate_and_normalize(&mut self) {
+        let d = Self::default();
+        finite_or(&mut self.setpoint_normal_c, d.setpoint_normal_c);
+        finite_or(&mut self.setpoint_gaming_c, d.setpoint_gaming_c);
+        finite_or(&mut self.setpoint_charging_c, d.setpoint_charging_c);
+        finite_or(&mut self.kp, d.kp);
+        finite_or(&mut self.ki, d.ki);
+        finite_or(&mut self.kd, d.kd);
+        self.setpoint_normal_c = self.setpoint_normal_c.clamp(30.0, 90.0);
+        self.setpoint_gaming_c = self.setpoint_gaming_c.clamp(30.0, 90.0);
+        self.setpoint_charging_c = self.setpoint_charging_c.clamp(30.0, 90.0);
+        self.kp = self.kp.clamp(0.0, 1.0);
+        self.ki = self.ki.clamp(0.0, 0.1);
+        self.kd = self.kd.clamp(0.0, 1.0);
+        self.max_reduce_percent = self.max_reduce_percent.min(95);
+        self.max_step_down = self.max_step_down.clamp(1, 10);
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ThermalControlConfig {
+    pub mode: ThermalControlMode,
+    pub pid: PidConfig,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThermalAttributionConfig {
+    /// Off = every domain takes the full cut (legacy behaviour).
+    pub enabled: bool,
+    /// A domain this many °C below the hotspot gets only `min_share_percent` of the cut.
+    pub spread_c: f32,
+    /// Share of the cut every domain takes regardless of its own temperature.
+    pub min_share_percent: u8,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+impl Default for ThermalAttributionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            spread_c: 8.0,
+            min_share_percent: 25,
+            extra: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThermalPredictConfig {
+    pub enabled: bool,
+    /// History used for the dT/dt fit.
+    pub window_s: u32,
+    /// Rise (°C/min) at or above which the curve sees the extrapolated temperature.
+    pub rise_c_per_min: f32,
+    /// Fall (°C/min, positive) at or above which caps are released early.
+    pub fall_c_per_min: f32,
+    /// How far ahead to extrapolate.
+    pub lead_s: f32,
+    /// Upper bound on the adjustment in either direction (°C).
+    pub max_lead_c: f32,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+impl Default for ThermalPredictConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            window_s: 12,
+            rise_c_per_min: 4.0,
+            fall_c_per_min: 4.0,
+            lead_s: 20.0,
+            max_lead_c: 3.0,
+            extra: BTreeMap::new(),
+        }
+    }
+}
+
+impl ThermalPredictConfig {
+    pub fn validate_and_normalize(&mut self) {
+        let d = Self::default();
+        finite_or(&mut self.rise_c_per_min, d.rise_c_per_min);
+        finite_or(&mut self.fall_c_per_min, d.fall_c_per_min);
+        finite_or(&mut self.lead_s, d.lead_s);
+        finite_or(&mut self.max_lead_c, d.max_lead_c);
+        self.window_s = self.window_s.clamp(3, 120);
+        self.rise_c_per_min = self.rise_c_per_min.clamp(0.5, 60.0);
+        self.fall_c_per_min = self.fall_c_per_min.clamp(0.5, 60.0);
+        self.lead_s = self.lead_s.clamp(0.0, 120.0);
+        self.max_lead_c = self.max_lead_c.clamp(0.0, 10.0);
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ControlTempSource {
+    /// Battery sensor, SoC when it's missing.
+    #[default]
+    Battery,
+    /// Hotter of the CPU/GPU averages.
+    Soc,
+    /// Hotter of battery and SoC.
+    Max,
+    /// `batt_weight·batt + soc_weight·soc`.
+    Blend,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ControlTempFormula {
+    pub source: ControlTempSource,
+    /// Blend weights (used by `blend` only). A missing sensor is replaced by the other one.
+    pub batt_weight: f32,
+    pub soc_weight: f32,
+    /// Added to the result (°C), e.g. -3.
+    pub offset_c: f32,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+impl Default for ControlTempFormula {
+    fn default() -> Self {
+        Self {
+            source: ControlTempSource::Battery,
+            batt_weight: 0.5,
+            soc_weight: 0.5,
+            offset_c: 0.0,
+            extra: BTreeMap::new(),
+        }
+    }
+}
+
+impl ControlTempFormula {
+    pub fn uses_battery(&self) -> bool {
+        self.source != ControlTempSource::Soc
+    }
+
+    pub fn uses_soc(&self) -> bool {
+        self.source != ControlTempSource::Battery
+    }
+
+    /// A blend with no weight left falls back to the default weights.
+    pub fn validate_and_normalize(&mut self) {
+        let d = Self::default();
+        finite_or(&mut self.batt_weight, d.batt_weight);
+        finite_or(&mut self.soc_weight, d.soc_weight);
+        finite_or(&mut self.offset_c, d.offset_c);
+        self.batt_weight = self.batt_weight.clamp(0.0, 2.0);
+        self.soc_weight = self.soc_weight.clamp(0.0, 2.0);
+        self.offset_c = self.offset_c.clamp(-20.0, 20.0);
+        if self.batt_weight + self.soc_weight <= 0.0 {
+            self.batt_weight = d.batt_weight;
+            self.soc_weight = d.soc_weight;
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ControlTempConfig {
+    pub normal: ControlTempFormula,
+    /// Used while charging (None = `normal`).
+    pub charging: Option<ControlTempFormula>,
+    /// Used in game mode; wins over `charging` (None = fall through).
+    pub gaming: Option<ControlTempFormula>,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+impl ControlTempConfig {
+    /// Formula reported by `select` under `name`.
+    pub fn by_name(&self, name: &str) -> &ControlTempFormula {
+        let f = match name {
+            "gaming" => self.gaming.as_ref(),
+            "charging" => self.charging.as_ref(),
+            _ => None,
+        };
+        f.unwrap_or(&self.normal)
+    }
+
+    /// Formula for the current state and its name (`gaming` / `charging` / `normal`).
+    pub fn select(&self, game_mode: bool, charging: bool) -> (&'static str, &ControlTempFormula) {
+        match (&self.gaming, &self.charging) {
+            (Some(f), _) if game_mode => ("gaming", f),
+            (_, Some(f)) if charging => ("charging", f),
+            _ => ("normal", &self.normal),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SensorHealthConfig {
+    /// Off = only failed reads are dropped (legacy behaviour).
+    pub enabled: bool,
+    /// Plausible range (°C); anything outside is garbage.
+    pub min_c: f32,
+    pub max_c: f32,
+    /// A sensor unchanged this long while the SoC median moved `stuck_peer_delta_c` is stuck.
+    pub stuck_after_s: u32,
+    pub stuck_peer_delta_c: f32,
+    /// Deviation from the group median (groups of 3+ sensors) that marks an outlier (°C).
+    pub outlier_c: f32,
+    /// Clean streak required before an excluded sensor is used again.
+    pub recover_s: u32,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+impl Default for SensorHealthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_c: -20.0,
+            max_c: 125.0,
+            stuck_after_s: 600,
+            stuck_peer_delta_c: 3.0,
+            outlier_c: 15.0,
+            recover_s: 30,
+            extra: BTreeMap::new(),
+        }
+    }
+}
+
+impl SensorHealthConfig {
+    pub fn validate_and_normalize(&mut self) {
+        let d = Self::default();
+        finite_or(&mut self.min_c, d.min_c);
+        finite_or(&mut self.max_c, d.max_c);
+        finite_or(&mut self.stuck_peer_delta_c, d.stuck_peer_delta_c);
+        finite_or(&mut self.outlier_c, d.outlier_c);
+        self.min_c = self.min_c.clamp(-40.0, 50.0);
+        self.max_c = self.max_c.clamp(60.0, 200.0);
+        self.stuck_after_s = self.stuck_after_s.clamp(30, 3600);
+        self.stuck_peer_delta_c = self.stuck_peer_delta_c.clamp(0.5, 20.0);
+        self.outlier_c = self.outlier_c.clamp(3.0, 50.0);
+        self.recover_s = self.recover_s.clamp(0, 600);
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CriticalConfig {
+    /// SoC hotspot (hottest CPU/GPU sensor) that pins all caps to minimum and the fan to max.
+    pub soc_critical_c: f32,
+    /// SoC hotspot at which stock thermal services are restored as well.
+    pub soc_shutdown_c: f32,
+    pub batt_critical_c: f32,
+    pub batt_shutdown_c: f32,
+    /// Drop below a threshold by this much before the level is released.
+    pub release_c: f32,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+impl Default for CriticalConfig {
+    fn default() -> Self {
+        Self {
+            soc_critical_c: 100.0,
+            soc_shutdown_c: 110.0,
+            batt_critical_c: 60.0,
+            batt_shutdown_c: 65.0,
+            release_c: 3.0,
+            extra: BTreeMap::new(),
+        }
+    }
+}
+
+impl CriticalConfig {
+    /// The layer can be tuned but not switched off: values are clamped into a safe window.
+    pub fn validate_and_normalize(&mut self) {
+        let d = Self::default();
+        finite_or(&mut self.soc_critical_c, d.soc_critical_c);
+        finite_or(&mut self.soc_shutdown_c, d.soc_shutdown_c);
+        finite_or(&mut self.batt_critical_c, d.batt_critical_c);
+        finite_or(&mut self.batt_shutdown_c, d.batt_shutdown_c);
+        finite_or(&mut self.release_c, d.release_c);
+        self.soc_critical_c = self.soc_critical_c.clamp(70.0, 115.0);
+        self.soc_shutdown_c = self.soc_shutdown_c.clamp(self.soc_critical_c + 1.0, 120.0);
+        self.batt_critical_c = self.batt_critical_c.clamp(45.0, 65.0);
+        self.batt_shutdown_c = self.batt_shutdown_c.clamp(self.batt_critical_c + 1.0, 70.0);
+        self.release_c = self.release_c.clamp(1.0, 10.0);
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchdogConfig {
+    pub enabled: bool,
+    /// Heartbeat older than this is a stall (20..600). The loop never sleeps longer than
+    /// `control::MAX_SLEEP_MS` (15 s), whatever the profile's `sleep_scale`.
+    pub stall_s: u32,
+    /// Fan level pushed on a stall (caps go to each domain's base frequency).
+    pub safe_fan_level: u8,
+    /// Re-exec the daemon when the stall lasts `restart_after_s`.
+    pub restart: bool,
+    pub restart_after_s: u32,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            stall_s: 45,
+            safe_fan_level: 3,
+            restart: false,
+            restart_after_s: 120,
+            extra: BTreeMap::new(),
+        }
+    }
+}
+
+impl WatchdogConfig {
+    pub fn validate_and_normalize(&mut self) {
+        self.stall_s = self.stall_s.clamp(20, 600);
+        self.safe_fan_level = self.safe_fan_level.min(5);
+        self.restart_after_s = self.restart_after_s.clamp(self.stall_s, 3600);
+    }
+}
+
+/// Control tunables of a CPU cluster or the GPU. Unset fields keep the built-in value
+/// (`base_ratio` / `down_after_ms`: the device descriptor's).
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DomainTunables {
+    /// Utilization (%) that steps the cap up by one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub up_util: Option<u8>,
+    /// Utilization rise (% per iteration) that steps up by two / four.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spike_delta2: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spike_delta4: Option<u8>,
+    /// Utilization (%) that steps up by two / four.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub high_jump2: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub high_jump4: Option<u8>,
+    /// At or below these (%), step down after `down_after_ms[0]` / `[1]`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub down_util1: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub down_util2: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub down_after_ms: Option<[u64; 2]>,
+    /// Position of the base (floor) frequency in the table, 0..1.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_ratio: Option<f32>,
+    /// Position of the min frequency (scaling_min_freq), 0..1. Unset: table minimum, ~50% in games.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_ratio: Option<f32>,
+    /// Highest position the cap may step up to, 0..1.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_ratio: Option<f32>,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+impl DomainTunables {
+    /// A ratio that isn't a number is unset (built-in value).
+    pub fn validate_and_normalize(&mut self) {
+        for v in [
+            &mut self.up_util,
+            &mut self.spike_delta2,
+            &mut self.spike_delta4,
+            &mut self.high_jump2,
+            &mut self.high_jump4,
+            &mut self.down_util1,
+            &mut self.down_util2,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *v = (*v).min(100);
+        }
+        if let Some(ms) = &mut self.down_after_ms {
+            for v in ms.iter_mut() {
+                *v = (*v).clamp(100, 60_000);
+            }
+        }
+        for v in [&mut self.base_ratio, &mut self.min_ratio, &mut self.max_ratio] {
+            *v = v.filter(|r| r.is_finite()).map(|r| r.clamp(0.0, 1.0));
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DomainTuningConfig {
+    /// Applies to every domain.
+    pub all: DomainTunables,
+    /// Per domain label (`CPU0`, `CPU7`, `GPU`, ...), over `all`.
+    pub domains: BTreeMap<String, DomainTunables>,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+impl DomainTuningConfig {
+    pub fn validate_and_normalize(&mut self) {
+        self.all.validate_and_normalize();
+        for t in self.domains.values_mut() {
+            t.validate_and_normalize();
+        }
+    }
+}
+
+pub const TRACE_MIN_KB: u32 = 64;
+pub const TRACE_MAX_KB: u32 = 64 * 1024;
+
+fn default_trace_max_kb() -> u32 { 4096 }
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TraceConfig {
+    /// Record every loop iteration to trace.jsonl.
+    /// Default: false (debugging aid, opt-in).
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Size cap of the active trace file. When exceeded, the file is rotated to `trace.jsonl.1`
+    /// (only one old file is kept). Clamped to 64 KiB..64 MiB.
+    #[serde(default = "default_trace_max_kb")]
+    pub max_kb: u32,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+impl Default for TraceConfig {
+    fn default() -> Self {
+        Self { enabled: false, max_kb: default_trace_max_kb(), extra: BTreeMap::new() }
+    }
+}
+
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct NotificationsConfig {
@@ -372,38 +1136,95 @@ pub struct ProfileConfig {
     #[serde(default)]
     pub external_led: Option<ExternalLedSetting>,
 
+    /// Domain tunables while this profile is active, over the global `domain_tuning`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub domain_tuning: Option<DomainTuningConfig>,
+
+    /// scaling_governor per cluster label (`CPU0`, `CPU7`, ...), over the device's game/normal one.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub governors: BTreeMap<String, String>,
+
+    /// Thermal curve used instead of the global `thermal_curve` (one without bands is ignored).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thermal_curve: Option<ThermalCurveConfig>,
+
+    /// Fan curve adjustment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fan: Option<ProfileFanConfig>,
+
+    /// Battery saver switch used instead of the global `battery_saver`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub battery_saver: Option<BatterySaverConfig>,
+
+    /// Multiplier on the loop sleep (0.25..4); critical and fast-poll bands are not scaled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sleep_scale: Option<f32>,
+
+    /// Activation rule of a custom profile; a custom profile without one never activates.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub when: Option<ProfileConditions>,
+
     #[serde(flatten)]
     pub extra: BTreeMap<String, Value>,
 }
 
 impl ProfileConfig {
+    pub fn validate_and_normalize(&mut self) -> Result<(), String> {
+        if let Some(t) = &mut self.domain_tuning {
+            t.validate_and_normalize();
+        }
+        if self.thermal_curve.as_mut().is_some_and(|c| c.validate_and_normalize().is_err()) {
+            self.thermal_curve = None;
+        }
+        if let Some(f) = &mut self.fan {
+            f.validate_and_normalize();
+        }
+        self.sleep_scale = self.sleep_scale.filter(|v| v.is_finite()).map(|v| v.clamp(0.25, 4.0));
+        if let Some(w) = &mut self.when {
+            w.validate_and_normalize().map_err(|e| format!("profile {}: {}", self.name, e))?;
+        }
+        Ok(())
+    }
+
+    /// Empty profile of the given name: LEDs off, no performance overrides.
+    pub fn empty(name: &str, profile_type: ProfileType) -> Self {
+        Self {
+            name: name.to_string(),
+            profile_type,
+            priority: 0,
+            enabled: true,
+            fan_led: None,
+            external_led: None,
+            domain_tuning: None,
+            governors: BTreeMap::new(),
+            thermal_curve: None,
+            fan: None,
+            battery_saver: None,
+            sleep_scale: None,
+            when: None,
+            extra: BTreeMap::new(),
+        }
+    }
+
     pub fn normal_default() -> Self {
         Self {
-            name: "Normal".to_string(),
-            profile_type: ProfileType::Normal,
             priority: 1,
-            enabled: true,
             fan_led: Some(FanLedSetting {
                 mode: FanLedMode::Off,
                 color: FanLedColor::Mixed7,
             }),
-            external_led: None,
-            extra: BTreeMap::new(),
+            ..Self::empty("Normal", ProfileType::Normal)
         }
     }
 
     pub fn gaming_default() -> Self {
         Self {
-            name: "Gaming".to_string(),
-            profile_type: ProfileType::Gaming,
             priority: 10,
-            enabled: true,
             fan_led: Some(FanLedSetting {
                 mode: FanLedMode::Breath,
                 color: FanLedColor::Rose,
             }),
-            external_led: None,
-            extra: BTreeMap::new(),
+            ..Self::empty("Gaming", ProfileType::Gaming)
         }
     }
 }
@@ -416,6 +1237,416 @@ pub enum ProfileType {
     Custom,
 }
 
+/// Daily local-time window `HH:MM`..`HH:MM`; `from > to` wraps past midnight.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TimeWindow {
+    pub from: String,
+    pub to: String,
+}
+
+fn parse_hhmm(s: &str) -> Option<u16> {
+    let (h, m) = s.trim().split_once(':')?;
+    let (h, m): (u16, u16) = (h.parse().ok()?, m.parse().ok()?);
+    (h < 24 && m < 60).then_some(h * 60 + m)
+}
+
+impl TimeWindow {
+    /// Rewrite both ends as zero-padded `HH:MM`; anything else can't be repaired.
+    pub fn validate_and_normalize(&mut self) -> Result<(), String> {
+        for v in [&mut self.from, &mut self.to] {
+            let Some(m) = parse_hhmm(v) else {
+                return Err(format!("time window: {:?} is not HH:MM", v));
+            };
+            *v = format!("{:02}:{:02}", m / 60, m % 60);
+        }
+        Ok(())
+    }
+
+    /// `from` inclusive, `to` exclusive; `from == to` is the whole day.
+    pub fn contains(&self, minute_of_day: u16) -> bool {
+        let (Some(from), Some(to)) = (parse_hhmm(&self.from), parse_hhmm(&self.to)) else { return false };
+        if from <= to {
+            from == to || (from..to).contains(&minute_of_day)
+        } else {
+            minute_of_day >= from || minute_of_day < to
+        }
+    }
+}
+
+/// Conditions of a custom profile; all set ones must hold.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProfileConditions {
+    /// Foreground package, exact or with `*` wildcards (`com.android.chrome`, `org.telegram.*`).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub packages: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub game_mode: Option<bool>,
+    /// Charger connected (hardware state).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub charging: Option<bool>,
+    /// Battery % range, inclusive; fails while the percentage is unknown.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub battery_min: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub battery_max: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub screen_on: Option<bool>,
+    /// Local time of day.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time: Option<TimeWindow>,
+    /// Thermal band range, inclusive (0 = Cool, N = `thermal_curve.bands[N - 1]`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub band_min: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub band_max: Option<u8>,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+impl ProfileConditions {
+    pub fn validate_and_normalize(&mut self) -> Result<(), String> {
+        self.packages.retain(|p| !p.trim().is_empty());
+        self.battery_min = self.battery_min.map(|v| v.min(100));
+        self.battery_max = self.battery_max.map(|v| v.min(100));
+        if let Some(t) = &mut self.time {
+            t.validate_and_normalize()?;
+        }
+        Ok(())
+    }
+}
+
+/// Time-of-day override, e.g. quiet hours 23:00..07:00.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScheduleConfig {
+    pub name: String,
+    pub enabled: bool,
+    pub time: TimeWindow,
+    /// Fan ceiling in the window, 0 keeps the cooler off. The critical safeguard still runs it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fan_max_level: Option<u8>,
+    /// Base LEDs off (charging/profile ones included) and no notification LED.
+    pub leds_off: bool,
+    /// No daemon notifications; critical temperature alerts are still posted.
+    pub mute_notifications: bool,
+    /// Domain tunables over the profile/preset ones, e.g. `{"all":{"max_ratio":0.6}}`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub domain_tuning: Option<DomainTuningConfig>,
+    /// Not applied while a game is in the foreground.
+    pub skip_in_games: bool,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+impl Default for ScheduleConfig {
+    fn default() -> Self {
+        Self {
+            name: "Quiet hours".to_string(),
+            enabled: true,
+            time: TimeWindow { from: "23:00".to_string(), to: "07:00".to_string() },
+            fan_max_level: None,
+            leds_off: true,
+            mute_notifications: true,
+            domain_tuning: None,
+            skip_in_games: true,
+            extra: BTreeMap::new(),
+        }
+    }
+}
+
+impl ScheduleConfig {
+    pub fn validate_and_normalize(&mut self) -> Result<(), String> {
+        self.fan_max_level = self.fan_max_level.map(|v| v.min(5));
+        if let Some(t) = &mut self.domain_tuning {
+            t.validate_and_normalize();
+        }
+        self.time.validate_and_normalize().map_err(|e| format!("schedule {}: {}", self.name, e))
+    }
+}
+
+/// Thresholds of one fan level.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FanCurvePoint {
+    /// Level is entered at or above this temperature (°C).
+    pub up_c: f32,
+    /// Level is left below this temperature (°C); defaults to `up_c` (no hysteresis).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub down_c: Option<f32>,
+    /// Minimum time at this level before it may be left downwards. Stepping up is immediate.
+    #[serde(default)]
+    pub dwell_s: f32,
+}
+
+impl FanCurvePoint {
+    fn new(up_c: f32, down_c: f32, dwell_s: f32) -> Self {
+        Self { up_c, down_c: Some(down_c), dwell_s }
+    }
+
+    pub fn up_mc(&self) -> i32 {
+        (self.up_c * 1000.0).round() as i32
+    }
+
+    pub fn down_mc(&self) -> i32 {
+        (self.down_c.unwrap_or(self.up_c) * 1000.0).round() as i32
+    }
+}
+
+/// Fan levels 1..=N (N <= 5), `levels[0]` being level 1.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FanCurve {
+    pub levels: Vec<FanCurvePoint>,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+impl FanCurve {
+    fn from_points(points: &[(f32, f32)], dwell_s: f32) -> Self {
+        Self {
+            levels: points.iter().map(|&(up, down)| FanCurvePoint::new(up, down, dwell_s)).collect(),
+            extra: BTreeMap::new(),
+        }
+    }
+
+    /// Clamp, sort by `up_c` and drop repeated thresholds; levels past the fifth are dropped.
+    /// Only a curve without a usable level is rejected.
+    pub fn validate_and_normalize(&mut self, name: &str) -> Result<(), String> {
+        self.levels.retain(|p| p.up_c.is_finite());
+        for p in &mut self.levels {
+            p.up_c = p.up_c.clamp(0.0, 120.0);
+            let down = p.down_c.filter(|d| d.is_finite()).unwrap_or(p.up_c);
+            p.down_c = Some(down.min(p.up_c));
+            p.dwell_s = if p.dwell_s.is_finite() { p.dwell_s.clamp(0.0, 600.0) } else { 0.0 };
+        }
+        self.levels.sort_by_key(|p| p.up_mc());
+        self.levels.dedup_by_key(|p| p.up_mc());
+        self.levels.truncate(5);
+        if self.levels.is_empty() {
+            return Err(format!("fan curve {}: at least one level is required", name));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FanCurvesConfig {
+    /// Screen on (hotter of the CPU/GPU averages).
+    pub soc: FanCurve,
+    /// While charging (battery temperature); the higher of this and `soc` applies.
+    pub battery: FanCurve,
+    /// Replaces `soc` in game mode.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub game: Option<FanCurve>,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+impl Default for FanCurvesConfig {
+    /// The original 10 °C (SoC) and ~5 °C (battery) steps, with hysteresis and a short dwell.
+    fn default() -> Self {
+        Self {
+            soc: FanCurve::from_points(&[(50.0, 47.0), (60.0, 57.0), (70.0, 67.0), (80.0, 77.0), (90.0, 87.0)], 10.0),
+            battery: FanCurve::from_points(&[(15.0, 14.0), (25.0, 24.0), (30.0, 29.0), (35.0, 34.0), (42.0, 41.0)], 10.0),
+            game: None,
+            extra: BTreeMap::new(),
+        }
+    }
+}
+
+impl FanCurvesConfig {
+    pub fn validate_and_normalize(&mut self) -> Result<(), String> {
+        self.soc.validate_and_normalize("soc")?;
+        self.battery.validate_and_normalize("battery")?;
+        if self.game.as_mut().is_some_and(|c| c.validate_and_normalize("game").is_err()) {
+            self.game = None;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FanControlMode {
+    /// Levels from `fan_curves`.
+    #[default]
+    Curve,
+    /// Levels from the setpoint controller (`fan_control.setpoint`).
+    Setpoint,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FanSetpointSensor {
+    /// Hotter of the CPU/GPU averages.
+    #[default]
+    Soc,
+    Battery,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FanSetpointConfig {
+    pub sensor: FanSetpointSensor,
+    /// Temperature to hold (°C).
+    pub target_c: f32,
+    /// No integration within ±deadband of the target.
+    pub deadband_c: f32,
+    /// Fan levels per °C above the target.
+    pub kp: f32,
+    /// Fan levels per °C·s of accumulated error.
+    pub ki: f32,
+    /// Minimum time at a level before stepping down (s). Stepping up is immediate.
+    pub min_hold_s: f32,
+    /// Learn per-level cooling rates and stay at the lowest level that is known to cool enough.
+    pub learn: bool,
+    /// Highest level the controller may request (1..5); the critical safeguard still forces 5.
+    pub max_level: u8,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+impl Default for FanSetpointConfig {
+    fn default() -> Self {
+        Self {
+            sensor: FanSetpointSensor::Soc,
+            target_c: 62.0,
+            deadband_c: 1.0,
+            kp: 0.3,
+            ki: 0.01,
+            min_hold_s: 20.0,
+            learn: true,
+            max_level: 5,
+            extra: BTreeMap::new(),
+        }
+    }
+}
+
+impl FanSetpointConfig {
+    pub fn validate_and_normalize(&mut self) {
+        let d = Self::default();
+        finite_or(&mut self.target_c, d.target_c);
+        finite_or(&mut self.deadband_c, d.deadband_c);
+        finite_or(&mut self.kp, d.kp);
+        finite_or(&mut self.ki, d.ki);
+        finite_or(&mut self.min_hold_s, d.min_hold_s);
+        self.target_c = self.target_c.clamp(20.0, 90.0);
+        self.deadband_c = self.deadband_c.clamp(0.0, 10.0);
+        self.kp = self.kp.clamp(0.0, 5.0);
+        self.ki = self.ki.clamp(0.0, 1.0);
+        self.min_hold_s = self.min_hold_s.clamp(0.0, 600.0);
+        self.max_level = self.max_level.clamp(1, 5);
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct FanControlConfig {
+    pub mode: FanControlMode,
+    pub setpoint: FanSetpointConfig,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FanMaintenanceConfig {
+    pub enabled: bool,
+    /// Time between runs (h); 168 = weekly.
+    pub interval_h: u32,
+    /// Runs only with the battery at or below this temperature (°C).
+    pub max_batt_temp_c: f32,
+    /// Time at level 5 (s).
+    pub max_level_s: u32,
+    /// Time at each of levels 4..1 afterwards (s).
+    pub step_s: u32,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+impl Default for FanMaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_h: 168,
+            max_batt_temp_c: 35.0,
+            max_level_s: 30,
+            step_s: 5,
+            extra: BTreeMap::new(),
+        }
+    }
+}
+
+impl FanMaintenanceConfig {
+    pub fn validate_and_normalize(&mut self) {
+        finite_or(&mut self.max_batt_temp_c, Self::default().max_batt_temp_c);
+        self.interval_h = self.interval_h.clamp(1, 24 * 90);
+        self.max_batt_temp_c = self.max_batt_temp_c.clamp(20.0, 45.0);
+        self.max_level_s = self.max_level_s.clamp(5, 300);
+        self.step_s = self.step_s.min(60);
+    }
+}
+
+/// Per-profile fan adjustment on top of the fan curves.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProfileFanConfig {
+    /// Added to the temperatures the fan curve reads (°C): positive spins up earlier.
+    pub temp_offset_c: f32,
+    /// Lowest level while the screen is on or charging.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_level: Option<u8>,
+    /// Highest level outside games and the critical layer.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_level: Option<u8>,
+    /// Curves used instead of the global `fan_curves` ones.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub soc: Option<FanCurve>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub battery: Option<FanCurve>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub game: Option<FanCurve>,
+
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+impl ProfileFanConfig {
+    /// A curve without a usable level is dropped (the global one applies).
+    pub fn validate_and_normalize(&mut self) {
+        if !self.temp_offset_c.is_finite() {
+            self.temp_offset_c = 0.0;
+        }
+        self.temp_offset_c = self.temp_offset_c.clamp(-20.0, 20.0);
+        self.min_level = self.min_level.map(|v| v.min(5));
+        self.max_level = self.max_level.map(|v| v.min(5));
+        for (name, c) in [("soc", &mut self.soc), ("battery", &mut self.battery), ("game", &mut self.game)] {
+            if c.as_mut().is_some_and(|c| c.validate_and_normalize(name).is_err()) {
+                *c = None;
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PerfPreset {
+    /// Lower caps, slower step-up, quieter fan, battery saver on.
+    Battery,
+    /// The active profile as configured.
+    #[default]
+    Balanced,
+    /// Faster step-up, longer hold, earlier fan, battery saver off.
+    Performance,
+}
+
 pub fn ensure_parent_dir(path: &Path) -> io::Result<()> {
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent)?;
@@ -423,37 +1654,38 @@ pub fn ensure_parent_dir(path: &Path) -> io::Result<()> {
     Ok(())
 }
 
-pub fn load_or_init(path: &Path) -> UserConfig {
+/// Load config.json. Missing or unparsable: reset to default and persist. Sections that fail
+/// validation fall back to their defaults in memory only; the file is rewritten only to add a
+/// missing api token.
+/// Returns config and optional error description.
+pub fn load_or_init(path: &Path) -> (UserConfig, Option<String>) {
     match fs::read_to_string(path) {
         Ok(s) => match serde_json::from_str::<UserConfig>(&s) {
             Ok(mut cfg) => {
-                if let Err(e) = cfg.validate_and_normalize() {
-                    eprintln!("CFG: invalid config: {} (reset to default)", e);
-                    let mut def = UserConfig::default();
-                    let _ = ensure_api_token(&mut def);
-                    let _ = write_config_atomic(path, &def);
-                    def
-                } else {
-                    // Ensure token exists; persist if we generated it.
-                    if ensure_api_token(&mut cfg).unwrap_or(false) {
-                        let _ = write_config_atomic(path, &cfg);
-                    }
-                    cfg
+                let resets = cfg.validate_and_normalize();
+                for r in &resets {
+                    eprintln!("CFG: invalid config: {}", r);
+                }
+                // Ensure token exists; persist if we generated it.
+                if ensure_api_token(&mut cfg).unwrap_or(false) {
+                    let _ = write_config_atomic(path, &cfg);
                 }
+                let err = (!resets.is_empty()).then(|| resets.join("; "));
+                (cfg, err)
             }
             Err(e) => {
                 eprintln!("CFG: failed to parse config: {} (reset to default)", e);
                 let mut def = UserConfig::default();
                 let _ = ensure_api_token(&mut def);
                 let _ = write_config_atomic(path, &def);
-                def
+                (def, Some(format!("config reset: {}", e)))
             }
         },
         Err(_) => {
             let mut def = UserConfig::default();
             let _ = ensure_api_token(&mut def);
             let _ = write_config_atomic(path, &def);
-            def
+            (def, None)
         }
     }
 }
@@ -486,3 +1718,116 @@ pub fn write_config_atomic(path: &Path, cfg: &UserConfig) -> io::Result<()> {
     fs::rename(&tmp, path)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn band(temp_c: f32, reduce_percent: u8) -> ThermalBand {
+        ThermalBand::new(temp_c, reduce_percent, 1, false)
+    }
+
+    #[test]
+    fn thermal_curve_is_sorted_merged_and_monotonic() {
+        let mut c = ThermalCurveConfig {
+            bands: vec![band(56.0, 30), band(50.0, 10), band(f32::NAN, 50), band(53.0, 40), band(50.0, 20)],
+            extra: BTreeMap::new(),
+        };
+        c.validate_and_normalize().unwrap();
+        let got: Vec<(f32, u8)> = c.bands.iter().map(|b| (b.temp_c, b.reduce_percent)).collect();
+        // The duplicate 50 °C keeps the stronger band; 56 °C is raised to 53 °C's 40 %.
+        assert_eq!(got, vec![(50.0, 20), (53.0, 40), (56.0, 40)]);
+    }
+
+    #[test]
+    fn invalid_section_is_reset_alone() {
+        let mut cfg = UserConfig::default();
+        cfg.thermal_curve.bands.clear();
+        cfg.fan_maintenance.interval_h = 48;
+        let resets = cfg.validate_and_normalize();
+        assert_eq!(resets.len(), 1, "{:?}", resets);
+        assert_eq!(cfg.thermal_curve.bands.len(), ThermalCurveConfig::default().bands.len());
+        assert_eq!(cfg.fan_maintenance.interval_h, 48);
+    }
+
+    fn window(from: &str, to: &str) -> TimeWindow {
+        TimeWindow { from: from.to_string(), to: to.to_string() }
+    }
+
+    #[test]
+    fn time_window_wraps_past_midnight() {
+        let w = window("23:00", "07:00");
+        assert!(w.contains(23 * 60));
+        assert!(w.contains(0));
+        assert!(w.contains(7 * 60 - 1));
+        assert!(!w.contains(7 * 60));
+        assert!(!w.contains(22 * 60 + 59));
+
+        let day = window("09:00", "17:30");
+        assert!(day.contains(9 * 60) && day.contains(17 * 60 + 29));
+        assert!(!day.contains(17 * 60 + 30) && !day.contains(0));
+        // Same ends: the whole day.
+        assert!(window("06:00", "06:00").contains(3 * 60));
+    }
+
+    #[test]
+    fn time_window_is_normalized() {
+        let mut w = window(" 7:5", "23:00");
+        w.validate_and_normalize().unwrap();
+        assert_eq!((w.from.as_str(), w.to.as_str()), ("07:05", "23:00"));
+        assert!(window("24:00", "07:00").validate_and_normalize().is_err());
+        assert!(window("7", "07:00").validate_and_normalize().is_err());
+        // Never matches rather than matching everything.
+        assert!(!window("xx", "07:00").contains(60));
+    }
+
+    #[test]
+    fn profile_with_bad_time_is_disabled() {
+        let mut cfg = UserConfig::default();
+        let mut v = serde_json::to_value(&cfg.profiles[0]).unwrap();
+        v["name"] = json!("Night");
+        v["type"] = json!("custom");
+        v["enabled"] = json!(true);
+        v["when"] = json!({ "time": { "from": "25:00", "to": "7:5" } });
+        cfg.profiles.push(serde_json::from_value(v).unwrap());
+        let resets = cfg.validate_and_normalize();
+        assert_eq!(resets.len(), 1, "{:?}", resets);
+        assert!(!cfg.profiles.last().unwrap().enabled);
+        // Already disabled: no longer reported, so the config can be saved again.
+        assert!(cfg.validate_and_normalize().is_empty());
+    }
+
+    #[test]
+    fn fan_curve_is_sorted_and_deduped() {
+        let mut c: FanCurve = serde_json::from_value(json!({ "levels": [
+            { "up_c": 70, "down_c": 80 }, { "up_c": 50 }, { "up_c": 50, "down_c": 45 },
+            { "up_c": 60 }, { "up_c": 150 }, { "up_c": 90 }, { "up_c": 95 }, { "up_c": 100 }
+        ] }))
+        .unwrap();
+        c.validate_and_normalize("soc").unwrap();
+        let got: Vec<(f32, Option<f32>)> = c.levels.iter().map(|p| (p.up_c, p.down_c)).collect();
+        // down_c above up_c is clamped; 150 is clamped to 120 and, with 100, falls past level 5.
+        assert_eq!(got, vec![(50.0, Some(50.0)), (60.0, Some(60.0)), (70.0, Some(70.0)), (90.0, Some(90.0)), (95.0, Some(95.0))]);
+
+        let mut empty: FanCurve = serde_json::from_value(json!({ "levels": [] })).unwrap();
+        assert!(empty.validate_and_normalize("soc").is_err());
+    }
+
+    #[test]
+    fn load_keeps_file_with_invalid_section() {
+        let path = std::env::temp_dir().join(format!("perf_daemon_cfg_{}.json", std::process::id()));
+        let mut v = serde_json::to_value(UserConfig::default()).unwrap();
+        v["api_token"] = json!("t");
+        v["thermal_curve"] = json!({ "bands": [] });
+        let text = serde_json::to_string_pretty(&v).unwrap();
+        fs::write(&path, &text).unwrap();
+
+        let (cfg, err) = load_or_init(&path);
+        let on_disk = fs::read_to_string(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert!(err.is_some_and(|e| e.contains("thermal_curve")));
+        assert_eq!(cfg.api_token, "t");
+        assert_eq!(on_disk, text);
+    }
+}
@@ -0,0 +1,173 @@
+//! Control-loop watchdog.
+//!
+//! The main loop bumps a heartbeat every iteration. A separate thread checks its age; on a
+//! stall (blocked sysfs write, stuck lock, hung `Command`) it pushes safe values straight to
+//! sysfs: fan to `safe_fan_level`, every domain back to its min..base window. Optionally the
+//! daemon re-execs itself once the stall lasts `restart_after_s`.
+//! Nothing here takes the shared `RwLock` blocking: config is read with `try_read`.
+
+use std::{
+    env,
+    os::unix::process::CommandExt,
+    path::PathBuf,
+    process::Command,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+        Arc, OnceLock, RwLock,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+use crate::{
+    device, notify::post_notification, profiles::daemon_notifications_allowed, shutdown, state::SharedState, sysfs,
+    user_config::WatchdogConfig,
+};
+
+const CHECK_EVERY: Duration = Duration::from_secs(1);
+/// Carries the restart count across re-execs.
+const RESTARTS_ENV: &str = "MORA_WATCHDOG_RESTARTS";
+
+static START: OnceLock<Instant> = OnceLock::new();
+static BEAT_MS: AtomicU64 = AtomicU64::new(0);
+static STALLED: AtomicBool = AtomicBool::new(false);
+static STALLS: AtomicU32 = AtomicU32::new(0);
+static LAST_STALL_MS: AtomicU64 = AtomicU64::new(0);
+static LONGEST_STALL_MS: AtomicU64 = AtomicU64::new(0);
+
+fn now_ms() -> u64 {
+    START.get_or_init(Instant::now).elapsed().as_millis() as u64
+}
+
+/// Called once per main-loop iteration.
+pub fn beat() {
+    BEAT_MS.store(now_ms(), Ordering::SeqCst);
+}
+
+/// Safe-value pushes so far (one per stall). The main loop compares it across iterations to
+/// learn that sysfs was written behind its write cache.
+pub fn safe_pushes() -> u32 {
+    STALLS.load(Ordering::SeqCst)
+}
+
+fn restarts() -> u32 {
+    env::var(RESTARTS_ENV).ok().and_then(|v| v.parse().ok()).unwrap_or(0)
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct WatchdogStats {
+    pub heartbeat_age_ms: u64,
+    pub stalled: bool,
+    pub stalls: u32,
+    /// Seconds since the last stall was detected.
+    pub last_stall_s: Option<u64>,
+    pub longest_stall_ms: u64,
+    /// Re-execs performed by the watchdog since boot.
+    pub restarts: u32,
+}
+
+pub fn stats() -> WatchdogStats {
+    let now = now_ms();
+    let stalls = STALLS.load(Ordering::SeqCst);
+    WatchdogStats {
+        heartbeat_age_ms: now.saturating_sub(BEAT_MS.load(Ordering::SeqCst)),
+        stalled: STALLED.load(Ordering::SeqCst),
+        stalls,
+        last_stall_s: (stalls > 0).then(|| now.saturating_sub(LAST_STALL_MS.load(Ordering::SeqCst)) / 1000),
+        longest_stall_ms: LONGEST_STALL_MS.load(Ordering::SeqCst),
+        restarts: restarts(),
+    }
+}
+
+/// Safe window of one domain: min at the table minimum, max at the base frequency.
+pub struct SafeCap {
+    pub min_path: PathBuf,
+    pub min_freq: u64,
+    pub max_path: PathBuf,
+    pub base_freq: u64,
+}
+
+fn push_safe_values(caps: &[SafeCap], fan_level: u8) {
+    for c in caps {
+        let _ = sysfs::write_num(&c.min_path, c.min_freq);
+        let _ = sysfs::write_num(&c.max_path, c.base_freq);
+    }
+    let fan = &device::get().fan;
+    let (enable, level) = (sysfs::path(&fan.enable), sysfs::path(&fan.level));
+    if enable.exists() && level.exists() {
+        let _ = sysfs::write_num(&enable, 1);
+        let _ = sysfs::write_num(&level, fan_level as u64);
+    }
+    println!("WDOG: safe values pushed (fan {}, caps at base)", fan_level);
+}
+
+fn reexec() {
+    let exe = match env::current_exe() {
+        Ok(p) => p,
+        Err(e) => {
+            println!("WDOG: re-exec failed ({})", e);
+            return;
+        }
+    };
+    println!("WDOG: re-exec {}", exe.display());
+    // Our fds are CLOEXEC: the touchscreen grab and the web socket go away with the old image.
+    let err = Command::new(&exe)
+        .args(env::args_os().skip(1))
+        .env(RESTARTS_ENV, (restarts() + 1).to_string())
+        .exec();
+    println!("WDOG: re-exec failed ({})", err);
+}
+
+pub fn spawn(shared: Arc<RwLock<SharedState>>, caps: Vec<SafeCap>) {
+    beat();
+    thread::spawn(move || {
+        let mut cfg = WatchdogConfig::default();
+        let mut notify = true;
+        let mut stall_start_ms = 0u64;
+        let mut restart_tried = false;
+        loop {
+            thread::sleep(CHECK_EVERY);
+            if shutdown::requested() {
+                return;
+            }
+            if let Ok(s) = shared.try_read() {
+                cfg = s.config.watchdog.clone();
+                notify = daemon_notifications_allowed(&s.config, s.info.game_mode);
+            }
+
+            let now = now_ms();
+            let beat_ms = BEAT_MS.load(Ordering::SeqCst);
+            let age = now.saturating_sub(beat_ms);
+            let stalled = STALLED.load(Ordering::SeqCst);
+
+            if stalled && age < cfg.stall_s as u64 * 1000 {
+                let dur = beat_ms.saturating_sub(stall_start_ms);
+                LONGEST_STALL_MS.fetch_max(dur, Ordering::SeqCst);
+                STALLED.store(false, Ordering::SeqCst);
+                restart_tried = false;
+                println!("WDOG: control loop resumed after {:.1}s", dur as f32 / 1000.0);
+                continue;
+            }
+            if !cfg.enabled {
+                continue;
+            }
+
+            if !stalled && age >= cfg.stall_s as u64 * 1000 {
+                stall_start_ms = beat_ms;
+                STALLED.store(true, Ordering::SeqCst);
+                STALLS.fetch_add(1, Ordering::SeqCst);
+                LAST_STALL_MS.store(now, Ordering::SeqCst);
+                println!("WDOG: control loop stalled for {}s", age / 1000);
+                push_safe_values(&caps, cfg.safe_fan_level);
+                if notify {
+                    post_notification(&format!("Control loop stalled for {}s: safe fan/caps applied", age / 1000));
+                }
+            } else if stalled && cfg.restart && !restart_tried && age >= cfg.restart_after_s as u64 * 1000 {
+                restart_tried = true;
+                reexec();
+            }
+        }
+    });
+}
@@ -1,10 +1,18 @@
 use crate::{
     config_watch,
-    games::{GameEntry, SplitChargeConfig, TriggersConfig},
+    device,
+    games::{sanitize_pkg, AppEntry, GameEntry, SplitChargeConfig, TriggersConfig},
     games_watch,
     mem::read_vmrss_kb,
-    state::SharedState,
-    user_config::{FanLedSetting, NotificationsConfig, ProfileConfig, ProfileType, UserConfig},
+    profiles,
+    state::{FanOverride, SharedState},
+    sysfs, thermal, watchdog,
+    user_config::{
+        FanLedSetting, NotificationsConfig, ProfileConfig, ProfileType, ControlTempConfig, PidConfig, ThermalBand, ThermalControlMode,
+        ThermalCurveConfig, UserConfig, DomainTuningConfig, PerfPreset, ScheduleConfig, FanCurvesConfig, FanControlMode, FanSetpointConfig,
+        FanMaintenanceConfig,
+        TRACE_PATH,
+    },
 };
 use serde::Deserialize;
 use serde_json::{json, Value};
@@ -329,6 +337,7 @@ fn build_state_json(shared: &Arc<RwLock<SharedState>>) -> Value {
     let s = shared.read().unwrap();
 
     let to_c = |mc: Option<i32>| mc.map(|v| (v as f64) / 1000.0);
+    let control_expr = serde_json::to_value(s.config.control_temp.by_name(s.info.control_formula)).unwrap_or_else(|_| json!({}));
 
     let ext_active = s.leds.external_active;
     let now = std::time::Instant::now();
@@ -347,9 +356,62 @@ fn build_state_json(shared: &Arc<RwLock<SharedState>>) -> Value {
             "soc": to_c(s.info.soc_mc),
             "batt": to_c(s.info.batt_mc)
         },
+        "control_temp": {
+            "formula": s.info.control_formula,
+            "expr": control_expr,
+            "inputs": {
+                "batt": to_c(s.info.batt_mc),
+                "soc": to_c(thermal::read_soc_temp_mc(s.info.cpu_avg_mc, s.info.gpu_avg_mc))
+            },
+            "value": to_c(s.info.soc_mc)
+        },
+        "critical": {
+            "level": s.info.critical_level,
+            "soc_hot": to_c(s.info.soc_hot_mc),
+            "batt": to_c(s.info.batt_mc),
+            "stock_thermal_restored": s.info.stock_thermal_restored,
+            "thermal_takeover": s.info.thermal_takeover_active,
+            "limits": serde_json::to_value(&s.config.critical).unwrap_or_else(|_| json!({}))
+        },
+        "watchdog": {
+            "stats": serde_json::to_value(watchdog::stats()).unwrap_or_else(|_| json!({})),
+            "config": serde_json::to_value(&s.config.watchdog).unwrap_or_else(|_| json!({}))
+        },
+        "sensors": {
+            "control_trusted": s.info.control_sensor_trusted,
+            "list": serde_json::to_value(&s.info.sensors).unwrap_or_else(|_| json!([]))
+        },
         "zone": {
             "name": s.info.temp_zone.clone(),
-            "reduce_percent": s.info.reduce_percent
+            "reduce_percent": s.info.reduce_percent,
+            "slope_c_per_min": s.info.temp_slope_c_per_min,
+            "predicted": to_c(s.info.zone_temp_mc)
+        },
+        "thermal_control": {
+            "mode": s.config.thermal_control.mode,
+            "pid": s.info.thermal_pid
+        },
+        "fan": s.info.fan,
+        "fan_maintenance": s.info.fan_maintenance,
+        "fan_override": s.fan_override.as_ref().map(|o| json!({
+            "level": o.level,
+            "remaining_s": o.remaining_s(now),
+            "until_game_exit": o.until_game_exit,
+            "active_s": now.duration_since(o.since).as_secs()
+        })),
+        "fan_control": {
+            "mode": s.config.fan_control.mode,
+            "setpoint": s.info.fan_setpoint
+        },
+        "thermal_domains": {
+            "hotspot": s.info.thermal_hotspot,
+            "domains": s.info.thermal_domains.iter().map(|d| json!({
+                "label": d.label,
+                "temp": to_c(d.temp_mc),
+                "share_percent": d.share_percent,
+                "reduce_percent": d.reduce_percent,
+                "reason": d.reason.clone()
+            })).collect::<Vec<_>>()
         },
         "screen_on": s.info.screen_on,
         "charging": {
@@ -387,8 +449,17 @@ fn build_state_json(shared: &Arc<RwLock<SharedState>>) -> Value {
             "right": s.info.triggers_right
         },
         "idle_mode": s.info.idle_mode,
+        "trace": {
+            "enabled": s.config.trace.enabled,
+            "active": s.info.trace_active,
+            "bytes": s.info.trace_bytes
+        },
         "daemon_notifications": s.config.daemon_notifications,
         "active_profile": s.info.active_profile.clone(),
+        "profile_rule": s.info.profile_rule.clone(),
+        "app_rule": s.info.app_rule.clone(),
+        "schedule": s.info.schedule.clone(),
+        "perf_preset": s.config.perf_preset,
         "led_profile": s.info.led_profile.clone(),
         "leds": {
             "base_external_desired": s.leds.base_external_desired.clone(),
@@ -1191,6 +1262,409 @@ fn handle_api_battery_saver_set(
     config_watch::apply_and_persist(shared, cfg_path, cfg)
 }
 
+#[derive(Deserialize)]
+struct TracePayload {
+    enabled: bool,
+    #[serde(default)]
+    max_kb: Option<u32>,
+}
+
+fn build_trace_json(shared: &Arc<RwLock<SharedState>>) -> Value {
+    let s = shared.read().unwrap();
+    json!({
+        "enabled": s.config.trace.enabled,
+        "max_kb": s.config.trace.max_kb,
+        "path": sysfs::path(TRACE_PATH).display().to_string(),
+        "active": s.info.trace_active,
+        "bytes": s.info.trace_bytes,
+        "last_error": s.info.trace_last_error.clone()
+    })
+}
+
+fn handle_api_trace_set(
+    shared: &Arc<RwLock<SharedState>>,
+    cfg_path: &Path,
+    body: &[u8],
+) -> Result<(), String> {
+    let payload: TracePayload =
+        serde_json::from_slice(body).map_err(|e| format!("bad json: {}", e))?;
+
+    let mut cfg = { shared.read().unwrap().config.clone() };
+    cfg.trace.enabled = payload.enabled;
+    if let Some(kb) = payload.max_kb {
+        cfg.trace.max_kb = kb;
+    }
+
+    config_watch::apply_and_persist(shared, cfg_path, cfg)
+}
+
+#[derive(Deserialize)]
+struct ThermalCurvePayload {
+    /// Replacement band list; omitted together with `reset: true` restores the stock ladder.
+    #[serde(default)]
+    bands: Option<Vec<ThermalBand>>,
+    #[serde(default)]
+    reset: bool,
+}
+
+fn build_thermal_curve_json(shared: &Arc<RwLock<SharedState>>) -> Value {
+    let s = shared.read().unwrap();
+    json!({
+        "bands": serde_json::to_value(&s.config.thermal_curve.bands).unwrap_or_else(|_| json!([])),
+        "zone": s.info.temp_zone.clone()
+    })
+}
+
+fn handle_api_thermal_curve_set(
+    shared: &Arc<RwLock<SharedState>>,
+    cfg_path: &Path,
+    body: &[u8],
+) -> Result<(), String> {
+    let payload: ThermalCurvePayload =
+        serde_json::from_slice(body).map_err(|e| format!("bad json: {}", e))?;
+
+    let mut cfg = { shared.read().unwrap().config.clone() };
+    if payload.reset {
+        cfg.thermal_curve = ThermalCurveConfig::default();
+    } else if let Some(bands) = payload.bands {
+        cfg.thermal_curve.bands = bands;
+    } else {
+        return Err("expected bands or reset".to_string());
+    }
+
+    config_watch::apply_and_persist(shared, cfg_path, cfg)
+}
+
+#[derive(Deserialize)]
+struct ThermalControlPayload {
+    #[serde(default)]
+    mode: Option<ThermalControlMode>,
+    #[serde(default)]
+    pid: Option<PidConfig>,
+}
+
+fn build_thermal_control_json(shared: &Arc<RwLock<SharedState>>) -> Value {
+    let s = shared.read().unwrap();
+    json!({
+        "mode": s.config.thermal_control.mode,
+        "pid": serde_json::to_value(&s.config.thermal_control.pid).unwrap_or_else(|_| json!({})),
+        "status": s.info.thermal_pid
+    })
+}
+
+fn handle_api_thermal_control_set(
+    shared: &Arc<RwLock<SharedState>>,
+    cfg_path: &Path,
+    body: &[u8],
+) -> Result<(), String> {
+    let payload: ThermalControlPayload =
+        serde_json::from_slice(body).map_err(|e| format!("bad json: {}", e))?;
+
+    let mut cfg = { shared.read().unwrap().config.clone() };
+    if let Some(mode) = payload.mode {
+        cfg.thermal_control.mode = mode;
+    }
+    if let Some(pid) = payload.pid {
+        cfg.thermal_control.pid = pid;
+    }
+
+    config_watch::apply_and_persist(shared, cfg_path, cfg)
+}
+
+fn build_control_temp_json(shared: &Arc<RwLock<SharedState>>) -> Value {
+    let s = shared.read().unwrap();
+    json!({
+        "config": serde_json::to_value(&s.config.control_temp).unwrap_or_else(|_| json!({})),
+        "formula": s.info.control_formula,
+        "value_mc": s.info.soc_mc
+    })
+}
+
+fn handle_api_control_temp_set(
+    shared: &Arc<RwLock<SharedState>>,
+    cfg_path: &Path,
+    body: &[u8],
+) -> Result<(), String> {
+    let payload: ControlTempConfig =
+        serde_json::from_slice(body).map_err(|e| format!("bad json: {}", e))?;
+
+    let mut cfg = { shared.read().unwrap().config.clone() };
+    cfg.control_temp = payload;
+
+    config_watch::apply_and_persist(shared, cfg_path, cfg)
+}
+
+#[derive(Deserialize)]
+struct DomainTuningPayload {
+    /// Profile name to set the override of; the global `domain_tuning` if absent.
+    #[serde(default)]
+    profile: Option<String>,
+    /// New tunables; with `profile`, `null` drops that profile's override.
+    #[serde(default)]
+    tuning: Option<DomainTuningConfig>,
+}
+
+fn build_domain_tuning_json(shared: &Arc<RwLock<SharedState>>) -> Value {
+    let s = shared.read().unwrap();
+    let profiles: serde_json::Map<String, Value> = s
+        .config
+        .profiles
+        .iter()
+        .filter_map(|p| {
+            let t = p.domain_tuning.as_ref()?;
+            Some((p.name.clone(), serde_json::to_value(t).unwrap_or(Value::Null)))
+        })
+        .collect();
+    let effective: serde_json::Map<String, Value> = s
+        .info
+        .domain_tuning
+        .iter()
+        .map(|(label, t)| (label.to_string(), serde_json::to_value(t).unwrap_or(Value::Null)))
+        .collect();
+    json!({
+        "config": serde_json::to_value(&s.config.domain_tuning).unwrap_or_else(|_| json!({})),
+        "profiles": profiles,
+        "active_profile": s.info.active_profile,
+        "effective": effective
+    })
+}
+
+fn handle_api_domain_tuning_set(
+    shared: &Arc<RwLock<SharedState>>,
+    cfg_path: &Path,
+    body: &[u8],
+) -> Result<(), String> {
+    let payload: DomainTuningPayload =
+        serde_json::from_slice(body).map_err(|e| format!("bad json: {}", e))?;
+
+    let mut cfg = { shared.read().unwrap().config.clone() };
+    match payload.profile {
+        Some(name) => {
+            let p = cfg
+                .profiles
+                .iter_mut()
+                .find(|p| p.name == name)
+                .ok_or_else(|| format!("unknown profile {}", name))?;
+            p.domain_tuning = payload.tuning;
+        }
+        None => cfg.domain_tuning = payload.tuning.ok_or("tuning required")?,
+    }
+
+    config_watch::apply_and_persist(shared, cfg_path, cfg)
+}
+
+#[derive(Deserialize)]
+struct PerfPresetPayload {
+    preset: PerfPreset,
+}
+
+fn build_perf_preset_json(shared: &Arc<RwLock<SharedState>>) -> Value {
+    let s = shared.read().unwrap();
+    json!({
+        "preset": s.config.perf_preset,
+        "overrides": serde_json::to_value(profiles::preset_profile(s.config.perf_preset)).unwrap_or(Value::Null),
+        "active_profile": s.info.active_profile
+    })
+}
+
+fn handle_api_perf_preset_set(
+    shared: &Arc<RwLock<SharedState>>,
+    cfg_path: &Path,
+    body: &[u8],
+) -> Result<(), String> {
+    let payload: PerfPresetPayload =
+        serde_json::from_slice(body).map_err(|e| format!("bad json: {}", e))?;
+
+    let mut cfg = { shared.read().unwrap().config.clone() };
+    cfg.perf_preset = payload.preset;
+
+    config_watch::apply_and_persist(shared, cfg_path, cfg)
+}
+
+#[derive(Deserialize)]
+struct FanCurvesPayload {
+    #[serde(default)]
+    reset: bool,
+    #[serde(default)]
+    curves: Option<FanCurvesConfig>,
+}
+
+fn build_fan_curves_json(shared: &Arc<RwLock<SharedState>>) -> Value {
+    let s = shared.read().unwrap();
+    serde_json::to_value(&s.config.fan_curves).unwrap_or(Value::Null)
+}
+
+fn handle_api_fan_curves_set(
+    shared: &Arc<RwLock<SharedState>>,
+    cfg_path: &Path,
+    body: &[u8],
+) -> Result<(), String> {
+    let payload: FanCurvesPayload =
+        serde_json::from_slice(body).map_err(|e| format!("bad json: {}", e))?;
+
+    let mut cfg = { shared.read().unwrap().config.clone() };
+    cfg.fan_curves = match (payload.reset, payload.curves) {
+        (true, _) => FanCurvesConfig::default(),
+        (false, Some(c)) => c,
+        (false, None) => return Err("curves or reset required".into()),
+    };
+
+    config_watch::apply_and_persist(shared, cfg_path, cfg)
+}
+
+#[derive(Deserialize)]
+struct FanControlPayload {
+    #[serde(default)]
+    mode: Option<FanControlMode>,
+    #[serde(default)]
+    setpoint: Option<FanSetpointConfig>,
+}
+
+fn build_fan_control_json(shared: &Arc<RwLock<SharedState>>) -> Value {
+    let s = shared.read().unwrap();
+    json!({
+        "mode": s.config.fan_control.mode,
+        "setpoint": serde_json::to_value(&s.config.fan_control.setpoint).unwrap_or_else(|_| json!({})),
+        "status": s.info.fan_setpoint
+    })
+}
+
+fn handle_api_fan_control_set(
+    shared: &Arc<RwLock<SharedState>>,
+    cfg_path: &Path,
+    body: &[u8],
+) -> Result<(), String> {
+    let payload: FanControlPayload =
+        serde_json::from_slice(body).map_err(|e| format!("bad json: {}", e))?;
+
+    let mut cfg = { shared.read().unwrap().config.clone() };
+    if let Some(mode) = payload.mode {
+        cfg.fan_control.mode = mode;
+    }
+    if let Some(sp) = payload.setpoint {
+        cfg.fan_control.setpoint = sp;
+    }
+
+    config_watch::apply_and_persist(shared, cfg_path, cfg)
+}
+
+#[derive(Deserialize)]
+struct FanOverridePayload {
+    level: u8,
+    #[serde(default)]
+    duration_s: Option<u64>,
+    #[serde(default)]
+    until_game_exit: bool,
+}
+
+/// Not persisted: a restart drops the override.
+fn handle_api_fan_override_set(shared: &Arc<RwLock<SharedState>>, body: &[u8]) -> Result<(), String> {
+    let payload: FanOverridePayload =
+        serde_json::from_slice(body).map_err(|e| format!("bad json: {}", e))?;
+    if payload.level > 5 {
+        return Err("level must be in range 0..=5".to_string());
+    }
+    if payload.duration_s.is_some_and(|d| d == 0 || d > 86_400) {
+        return Err("duration_s must be in range 1..=86400".to_string());
+    }
+
+    let mut s = shared.write().unwrap();
+    if payload.until_game_exit && !s.info.game_mode {
+        return Err("until_game_exit: no game in the foreground".to_string());
+    }
+    let now = std::time::Instant::now();
+    s.fan_override = Some(FanOverride {
+        level: payload.level,
+        since: now,
+        ends_at: payload.duration_s.map(|d| now + std::time::Duration::from_secs(d)),
+        until_game_exit: payload.until_game_exit,
+    });
+    println!(
+        "FAN: override {} ({}{})",
+        payload.level,
+        payload.duration_s.map(|d| format!("{}s", d)).unwrap_or_else(|| "no expiry".to_string()),
+        if payload.until_game_exit { ", until game exits" } else { "" }
+    );
+    Ok(())
+}
+
+fn build_fan_maintenance_json(shared: &Arc<RwLock<SharedState>>) -> Value {
+    let s = shared.read().unwrap();
+    json!({
+        "config": serde_json::to_value(&s.config.fan_maintenance).unwrap_or_else(|_| json!({})),
+        "status": s.info.fan_maintenance
+    })
+}
+
+fn handle_api_fan_maintenance_set(
+    shared: &Arc<RwLock<SharedState>>,
+    cfg_path: &Path,
+    body: &[u8],
+) -> Result<(), String> {
+    let payload: FanMaintenanceConfig =
+        serde_json::from_slice(body).map_err(|e| format!("bad json: {}", e))?;
+
+    let mut cfg = { shared.read().unwrap().config.clone() };
+    cfg.fan_maintenance = payload;
+
+    config_watch::apply_and_persist(shared, cfg_path, cfg)
+}
+
+#[derive(Deserialize)]
+struct SchedulesPayload {
+    schedules: Vec<ScheduleConfig>,
+}
+
+fn build_schedules_json(shared: &Arc<RwLock<SharedState>>) -> Value {
+    let s = shared.read().unwrap();
+    json!({
+        "schedules": serde_json::to_value(&s.config.schedules).unwrap_or_else(|_| json!([])),
+        "active": s.info.schedule
+    })
+}
+
+fn handle_api_schedules_set(
+    shared: &Arc<RwLock<SharedState>>,
+    cfg_path: &Path,
+    body: &[u8],
+) -> Result<(), String> {
+    let payload: SchedulesPayload =
+        serde_json::from_slice(body).map_err(|e| format!("bad json: {}", e))?;
+
+    let mut cfg = { shared.read().unwrap().config.clone() };
+    cfg.schedules = payload.schedules;
+
+    config_watch::apply_and_persist(shared, cfg_path, cfg)
+}
+
+#[derive(Deserialize)]
+struct ThermalTakeoverPayload {
+    enabled: bool,
+}
+
+fn build_thermal_takeover_json(shared: &Arc<RwLock<SharedState>>) -> Value {
+    let s = shared.read().unwrap();
+    json!({
+        "enabled": s.config.thermal_takeover,
+        "active": s.info.thermal_takeover_active,
+        "stock_thermal_restored": s.info.stock_thermal_restored,
+        "snapshot": serde_json::to_value(&s.info.thermal_snapshot).unwrap_or(Value::Null)
+    })
+}
+
+/// The main loop follows `thermal_takeover`: enabling snapshots and takes over,
+/// disabling restores the snapshot.
+fn handle_api_thermal_takeover_set(
+    shared: &Arc<RwLock<SharedState>>,
+    cfg_path: &Path,
+    enabled: bool,
+) -> Result<(), String> {
+    let mut cfg = { shared.read().unwrap().config.clone() };
+    cfg.thermal_takeover = enabled;
+
+    config_watch::apply_and_persist(shared, cfg_path, cfg)
+}
+
 #[derive(Deserialize)]
 struct GameAddPayload {
     package: String,
@@ -1515,6 +1989,61 @@ fn handle_api_games_set_disable_thermal_limit(
     games_watch::apply_and_persist(shared, games_path, file)
 }
 
+fn build_apps_json(shared: &Arc<RwLock<SharedState>>) -> Value {
+    let s = shared.read().unwrap();
+    json!({
+        "apps": serde_json::to_value(&s.games.file.apps).unwrap_or_else(|_| json!([])),
+        "active": s.info.app_rule
+    })
+}
+
+/// Add or replace the rule of `package`.
+fn handle_api_apps_set(
+    shared: &Arc<RwLock<SharedState>>,
+    games_path: &Path,
+    body: &[u8],
+) -> Result<(), String> {
+    let mut payload: AppEntry =
+        serde_json::from_slice(body).map_err(|e| format!("bad json: {}", e))?;
+
+    payload.package = sanitize_pkg(&payload.package);
+    if payload.package.is_empty() {
+        return Err("package is empty".to_string());
+    }
+
+    let mut file = { shared.read().unwrap().games.file.clone() };
+    if file.games.iter().any(|g| g.package == payload.package) {
+        return Err(format!("{} is in the games list", payload.package));
+    }
+    // normalize() keeps the last entry per package.
+    file.apps.push(payload);
+
+    games_watch::apply_and_persist(shared, games_path, file)
+}
+
+fn handle_api_apps_remove(
+    shared: &Arc<RwLock<SharedState>>,
+    games_path: &Path,
+    body: &[u8],
+) -> Result<(), String> {
+    let payload: GameRemovePayload =
+        serde_json::from_slice(body).map_err(|e| format!("bad json: {}", e))?;
+
+    let package = sanitize_pkg(&payload.package);
+    if package.is_empty() {
+        return Err("package is empty".to_string());
+    }
+
+    let mut file = { shared.read().unwrap().games.file.clone() };
+    let before = file.apps.len();
+    file.apps.retain(|a| a.package != package);
+    if file.apps.len() == before {
+        return Err("app rule not found".to_string());
+    }
+
+    games_watch::apply_and_persist(shared, games_path, file)
+}
+
 fn handle_api_save(shared: &Arc<RwLock<SharedState>>, cfg_path: &Path, body: &[u8]) -> Result<(), String> {
     let payload: UiSavePayload = serde_json::from_slice(body).map_err(|e| e.to_string())?;
     let mut cfg = { shared.read().unwrap().config.clone() };
@@ -1622,6 +2151,117 @@ pub fn spawn(shared: Arc<RwLock<SharedState>>, _leds: Arc<crate::leds::Leds>, cf
                     Err(e) => bad(400, &e),
                 },
 
+                // Device and diagnostics
+                // Active device descriptor (clusters, sensors, nodes).
+                (Method::Get, "/api/device") => {
+                    ok_json(serde_json::to_value(device::get()).unwrap_or_else(|_| json!({})))
+                },
+
+                // Thermal zones resolved by type pattern (what is averaged and what services disabled).
+                (Method::Get, "/api/thermal_zones") => {
+                    ok_json(serde_json::to_value(thermal::zones()).unwrap_or_else(|_| json!({})))
+                },
+
+                // Loop trace recorder (toggle + size cap).
+                (Method::Get, "/api/trace") => ok_json(build_trace_json(&shared)),
+                (Method::Post, "/api/trace") => match handle_api_trace_set(&shared, &cfg_path, &body) {
+                    Ok(_) => Response::from_string("ok"),
+                    Err(e) => bad(400, &e),
+                },
+
+                // Thermal control
+                // Thermal throttling curve (bands of threshold/reduction/hysteresis).
+                (Method::Get, "/api/thermal_curve") => ok_json(build_thermal_curve_json(&shared)),
+                (Method::Post, "/api/thermal_curve") => match handle_api_thermal_curve_set(&shared, &cfg_path, &body) {
+                    Ok(_) => Response::from_string("ok"),
+                    Err(e) => bad(400, &e),
+                },
+
+                // Thermal controller mode (zones / pid) and PID tuning.
+                (Method::Get, "/api/thermal_control") => ok_json(build_thermal_control_json(&shared)),
+                (Method::Post, "/api/thermal_control") => match handle_api_thermal_control_set(&shared, &cfg_path, &body) {
+                    Ok(_) => Response::from_string("ok"),
+                    Err(e) => bad(400, &e),
+                },
+
+                // Control temperature formula (normal / charging / gaming).
+                (Method::Get, "/api/control_temp") => ok_json(build_control_temp_json(&shared)),
+                (Method::Post, "/api/control_temp") => match handle_api_control_temp_set(&shared, &cfg_path, &body) {
+                    Ok(_) => Response::from_string("ok"),
+                    Err(e) => bad(400, &e),
+                },
+
+                // Stock thermal takeover; restore = takeover off + snapshot put back.
+                (Method::Get, "/api/thermal/takeover") => ok_json(build_thermal_takeover_json(&shared)),
+                (Method::Post, "/api/thermal/takeover") => match serde_json::from_slice::<ThermalTakeoverPayload>(&body)
+                    .map_err(|e| format!("bad json: {}", e))
+                    .and_then(|p| handle_api_thermal_takeover_set(&shared, &cfg_path, p.enabled))
+                {
+                    Ok(_) => Response::from_string("ok"),
+                    Err(e) => bad(400, &e),
+                },
+                (Method::Post, "/api/thermal/restore") => match handle_api_thermal_takeover_set(&shared, &cfg_path, false) {
+                    Ok(_) => Response::from_string("ok"),
+                    Err(e) => bad(400, &e),
+                },
+
+                // Performance tuning
+                // CPU/GPU domain tunables, global or per profile.
+                (Method::Get, "/api/domain_tuning") => ok_json(build_domain_tuning_json(&shared)),
+                (Method::Post, "/api/domain_tuning") => match handle_api_domain_tuning_set(&shared, &cfg_path, &body) {
+                    Ok(_) => Response::from_string("ok"),
+                    Err(e) => bad(400, &e),
+                },
+
+                // Battery / Balanced / Performance preset over the active profile.
+                (Method::Get, "/api/perf_preset") => ok_json(build_perf_preset_json(&shared)),
+                (Method::Post, "/api/perf_preset") => match handle_api_perf_preset_set(&shared, &cfg_path, &body) {
+                    Ok(_) => Response::from_string("ok"),
+                    Err(e) => bad(400, &e),
+                },
+
+                // Fan
+                // Fan curves: replaced as a whole or reset to the defaults.
+                (Method::Get, "/api/fan_curves") => ok_json(build_fan_curves_json(&shared)),
+                (Method::Post, "/api/fan_curves") => match handle_api_fan_curves_set(&shared, &cfg_path, &body) {
+                    Ok(_) => Response::from_string("ok"),
+                    Err(e) => bad(400, &e),
+                },
+
+                // Fan mode and setpoint controller tuning; GET adds the live status.
+                (Method::Get, "/api/fan_control") => ok_json(build_fan_control_json(&shared)),
+                (Method::Post, "/api/fan_control") => match handle_api_fan_control_set(&shared, &cfg_path, &body) {
+                    Ok(_) => Response::from_string("ok"),
+                    Err(e) => bad(400, &e),
+                },
+
+                // Manual fan level over curves/setpoint (critical still forces 5); DELETE releases it.
+                (Method::Post, "/api/fan/override") => match handle_api_fan_override_set(&shared, &body) {
+                    Ok(_) => Response::from_string("ok"),
+                    Err(e) => bad(400, &e),
+                },
+                (Method::Delete, "/api/fan/override") => {
+                    if shared.write().unwrap().fan_override.take().is_some() {
+                        println!("FAN: override released (api)");
+                    }
+                    Response::from_string("ok")
+                },
+
+                // Dust-clearing cycle settings (replaced as a whole); GET adds the run status.
+                (Method::Get, "/api/fan_maintenance") => ok_json(build_fan_maintenance_json(&shared)),
+                (Method::Post, "/api/fan_maintenance") => match handle_api_fan_maintenance_set(&shared, &cfg_path, &body) {
+                    Ok(_) => Response::from_string("ok"),
+                    Err(e) => bad(400, &e),
+                },
+
+                // Schedules
+                // Time-of-day schedules (quiet hours), replaced as a whole.
+                (Method::Get, "/api/schedules") => ok_json(build_schedules_json(&shared)),
+                (Method::Post, "/api/schedules") => match handle_api_schedules_set(&shared, &cfg_path, &body) {
+                    Ok(_) => Response::from_string("ok"),
+                    Err(e) => bad(400, &e),
+                },
+
                 // Games list (games.json)
                 (Method::Get, "/api/games") => ok_json(build_games_json(&shared)),
 
@@ -1661,6 +2301,17 @@ pub fn spawn(shared: Arc<RwLock<SharedState>>, _leds: Arc<crate::leds::Leds>, cf
                     Err(e) => bad(400, &e),
                 },
 
+                // Per-app rules for non-game packages (stored in games.json `apps`).
+                (Method::Get, "/api/apps") => ok_json(build_apps_json(&shared)),
+                (Method::Post, "/api/apps/set") => match handle_api_apps_set(&shared, &games_path, &body) {
+                    Ok(_) => Response::from_string("ok"),
+                    Err(e) => bad(400, &e),
+                },
+                (Method::Post, "/api/apps/remove") => match handle_api_apps_remove(&shared, &games_path, &body) {
+                    Ok(_) => Response::from_string("ok"),
+                    Err(e) => bad(400, &e),
+                },
+
                 // Save (UI/app) config changes.
                 (Method::Post, "/api/save") => match handle_api_save(&shared, &cfg_path, &body) {
                     Ok(_) => Response::from_string("ok"),
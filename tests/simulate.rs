@@ -0,0 +1,34 @@
+//! Golden `--simulate` run: a warm-up, a game heating through the thermal bands, charging and a
+//! screen-off cool-down, replayed on the built-in descriptor with the default config.
+//! After an intended policy change, regenerate with `UPDATE_GOLDEN=1 cargo test --test simulate`
+//! and review the diff of `tests/sim/expected.jsonl`.
+
+use std::{fs, path::Path, process::Command};
+
+#[test]
+fn simulate_matches_golden() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/sim");
+    let out = Path::new(env!("CARGO_TARGET_TMPDIR")).join("simulate.jsonl");
+    let run = Command::new(env!("CARGO_BIN_EXE_perf_daemon"))
+        .arg("--simulate")
+        .arg(dir.join("trace.jsonl"))
+        .arg("--games")
+        .arg(dir.join("games.json"))
+        .arg("--out")
+        .arg(&out)
+        .output()
+        .expect("run perf_daemon");
+    assert!(run.status.success(), "simulate failed: {}", String::from_utf8_lossy(&run.stderr));
+
+    let got = fs::read_to_string(&out).expect("read simulate output");
+    let golden = dir.join("expected.jsonl");
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&golden, &got).expect("write golden");
+        return;
+    }
+    let want = fs::read_to_string(&golden).expect("read golden");
+    for (i, (g, w)) in got.lines().zip(want.lines()).enumerate() {
+        assert_eq!(g, w, "step {} differs from the golden trace", i);
+    }
+    assert_eq!(got.lines().count(), want.lines().count(), "step count differs from the golden trace");
+}